@@ -0,0 +1,1675 @@
+use crate::math::Fraction;
+use crate::music::{
+    Accidental, Articulation, ChordNote, Clef, ClefSign, Duration, Dynamic, Event, Key,
+    KeySignature, Measure, Mode, Note, NoteName, Part, Pitch, Rest, Score, Staff, Tie,
+    TimeSignature, Tuplet, Voice, A, B, C, D, E, F, G, NATURAL, SHARP,
+};
+use contracts::requires;
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Tune {
+    number: u32,
+    tempo: Option<(Fraction, u32)>,
+    score: Score,
+}
+
+impl Tune {
+    pub fn new(number: u32, score: Score) -> Tune {
+        Tune {
+            number,
+            tempo: None,
+            score,
+        }
+    }
+
+    #[requires(beat > Fraction::ZERO, "beat must be positive")]
+    #[requires(bpm > 0, "tempo must be positive")]
+    pub fn with_tempo(self, beat: Fraction, bpm: u32) -> Tune {
+        Tune {
+            tempo: Some((beat, bpm)),
+            ..self
+        }
+    }
+
+    pub fn number(&self) -> u32 {
+        self.number
+    }
+
+    pub fn tempo(&self) -> Option<(Fraction, u32)> {
+        self.tempo
+    }
+
+    pub fn score(&self) -> &Score {
+        &self.score
+    }
+}
+
+pub fn read(input: &str) -> Result<Vec<Tune>, &'static str> {
+    let lines: Vec<&str> = input.lines().collect();
+    if !lines.iter().any(|line| line.starts_with("X:")) {
+        return Ok(vec![read_tune(&lines)?]);
+    }
+
+    // Tunes start with an X: field and end at a blank line; text between them is ignored.
+    let mut tunes = Vec::new();
+    let mut tune: Option<Vec<&str>> = None;
+    for line in lines {
+        if line.starts_with("X:") {
+            if let Some(lines) = tune.take() {
+                tunes.push(read_tune(&lines)?);
+            }
+            tune = Some(vec![line]);
+        } else if line.trim().is_empty() {
+            if let Some(lines) = tune.take() {
+                tunes.push(read_tune(&lines)?);
+            }
+        } else if let Some(lines) = tune.as_mut() {
+            lines.push(line);
+        }
+    }
+    if let Some(lines) = tune {
+        tunes.push(read_tune(&lines)?);
+    }
+    Ok(tunes)
+}
+
+fn read_tune(lines: &[&str]) -> Result<Tune, &'static str> {
+    let mut reader = TuneReader::new();
+    let mut header = true;
+    for line in lines {
+        let line = match line.find('%') {
+            Some(index) => &line[..index],
+            None => line,
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let bytes = line.as_bytes();
+        if bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' {
+            let field = bytes[0] as char;
+            let value = line[2..].trim();
+            if header {
+                reader.header_field(field, value)?;
+                header = field != 'K';
+            } else {
+                reader.field(field, value)?;
+            }
+        } else if header {
+            return Err("music before the K: field");
+        } else {
+            reader.music(line)?;
+        }
+    }
+    if header {
+        return Err("missing K: field");
+    }
+    reader.finish()
+}
+
+fn parse_meter(value: &str) -> Result<Option<TimeSignature>, &'static str> {
+    match value.trim() {
+        "" | "none" => Ok(None),
+        "C" => Ok(Some(TimeSignature::new(4, 4))),
+        "C|" => Ok(Some(TimeSignature::new(2, 2))),
+        meter => TimeSignature::from_str(&meter.replace(['(', ')'], "")).map(Some),
+    }
+}
+
+fn parse_fraction(value: &str) -> Option<Fraction> {
+    let (numerator, denominator) = value.trim().split_once('/')?;
+    let numerator: i32 = numerator.trim().parse().ok()?;
+    let denominator: i32 = denominator.trim().parse().ok()?;
+    if numerator <= 0 || denominator <= 0 {
+        return None;
+    }
+    Some(Fraction::new(numerator, denominator))
+}
+
+// Reads tempos such as `1/4=120`, `"Allegro" 3/8=60` or a bare `120` in unit notes.
+fn parse_tempo(value: &str, unit: Fraction) -> Option<(Fraction, u32)> {
+    let text: String = value.split('"').step_by(2).collect();
+    let (beat, bpm) = match text.split_once('=') {
+        Some((beats, bpm)) => {
+            let mut beat = Fraction::ZERO;
+            for fraction in beats.split_whitespace() {
+                beat += parse_fraction(fraction)?;
+            }
+            (beat, bpm)
+        }
+        None => (unit, text.as_str()),
+    };
+    match bpm.trim().parse() {
+        Ok(bpm) if bpm > 0 && beat > Fraction::ZERO => Some((beat, bpm)),
+        _ => None,
+    }
+}
+
+fn parse_clef(name: &str) -> Option<Clef> {
+    let (name, octave_change) = if let Some(name) = name.strip_suffix("+8") {
+        (name, 1)
+    } else if let Some(name) = name.strip_suffix("-8") {
+        (name, -1)
+    } else {
+        (name, 0)
+    };
+    let (sign, line) = match name {
+        "treble" => (ClefSign::G, 2),
+        "bass" => (ClefSign::F, 4),
+        "bass3" => (ClefSign::F, 3),
+        "alto" => (ClefSign::C, 3),
+        "alto1" => (ClefSign::C, 1),
+        "alto2" => (ClefSign::C, 2),
+        "tenor" => (ClefSign::C, 4),
+        "perc" | "percussion" => (ClefSign::Percussion, 3),
+        _ => return None,
+    };
+    Some(Clef::new(sign, line, octave_change))
+}
+
+fn parse_mode(mode: &str) -> Option<Mode> {
+    let mode = mode.to_ascii_lowercase();
+    Some(match mode.get(..3).unwrap_or(&mode) {
+        "" | "maj" | "ion" => Mode::Major,
+        "m" | "min" | "aeo" => Mode::Minor,
+        "dor" => Mode::Dorian,
+        "phr" => Mode::Phrygian,
+        "lyd" => Mode::Lydian,
+        "mix" => Mode::Mixolydian,
+        "loc" => Mode::Locrian,
+        _ => return None,
+    })
+}
+
+fn parse_accidental(text: &str) -> Option<(Accidental, &str)> {
+    for (prefix, cents) in [
+        ("^^", 200),
+        ("__", -200),
+        ("^/", 50),
+        ("_/", -50),
+        ("^", 100),
+        ("_", -100),
+        ("=", 0),
+    ]
+    .iter()
+    {
+        if let Some(rest) = text.strip_prefix(prefix) {
+            return Some((Accidental::from_cents(*cents), rest));
+        }
+    }
+    None
+}
+
+type KeyField = (Option<KeySignature>, Option<Mode>, Option<Clef>);
+
+// A K: field holds a tonic and mode, explicit accidentals and clef settings,
+// any of which may be missing. The mode is only kept for keys given by a tonic.
+fn parse_key(value: &str) -> Result<KeyField, &'static str> {
+    let mut tokens: Vec<&str> = value.split_whitespace().collect();
+    let mut signature = None;
+    let mut key_mode = None;
+    let mut clef = None;
+
+    if let Some(first) = tokens.first() {
+        let tonic = match *first {
+            "none" | "HP" => Some(KeySignature::new(0)),
+            "Hp" => Some(KeySignature::from_accidentals(&[(F, SHARP), (C, SHARP)])),
+            _ if first.starts_with(|c: char| ('A'..='G').contains(&c)) => {
+                let name = NoteName::from_str(&first[..1])?;
+                let (accidental, mode) = match &first[1..] {
+                    rest if rest.starts_with('#') => (SHARP, &rest[1..]),
+                    rest if rest.starts_with('b') => (Accidental::new(-1), &rest[1..]),
+                    rest => (NATURAL, rest),
+                };
+                let mode = match parse_mode(mode) {
+                    Some(Mode::Major) if mode.is_empty() => {
+                        match tokens.get(1).and_then(|token| parse_mode(token)) {
+                            Some(mode) => {
+                                tokens.remove(1);
+                                mode
+                            }
+                            None => Mode::Major,
+                        }
+                    }
+                    Some(mode) => mode,
+                    None => return Err("unknown mode"),
+                };
+                key_mode = Some(mode);
+                Some(Key::new(name, accidental, mode).signature())
+            }
+            _ => None,
+        };
+        if tonic.is_some() {
+            signature = tonic;
+            tokens.remove(0);
+        }
+    }
+
+    let mut explicit = Vec::new();
+    let mut exp = false;
+    for token in tokens {
+        if token == "exp" {
+            exp = true;
+        } else if let Some(name) = token.strip_prefix("clef=") {
+            clef = parse_clef(name);
+        } else if let Some(parsed) = parse_clef(token) {
+            clef = Some(parsed);
+        } else if let Some((accidental, name)) = parse_accidental(token) {
+            explicit.push((NoteName::from_str(name)?, accidental));
+        }
+    }
+    if exp || !explicit.is_empty() {
+        let base = match signature {
+            Some(signature) if !exp => signature,
+            _ => KeySignature::new(0),
+        };
+        let accidentals: Vec<(NoteName, Accidental)> = [C, D, E, F, G, A, B]
+            .iter()
+            .map(|name| {
+                let accidental = explicit
+                    .iter()
+                    .rev()
+                    .find(|(other, _)| other == name)
+                    .map(|(_, accidental)| *accidental)
+                    .unwrap_or_else(|| base.accidental(*name));
+                (*name, accidental)
+            })
+            .collect();
+        signature = Some(KeySignature::from_accidentals(&accidentals));
+        key_mode = None;
+    }
+    Ok((signature, key_mode, clef))
+}
+
+fn parse_number(chars: &[char], i: &mut usize) -> Option<i32> {
+    let start = *i;
+    while *i < chars.len() && chars[*i].is_ascii_digit() {
+        *i += 1;
+    }
+    chars[start..*i].iter().collect::<String>().parse().ok()
+}
+
+// Limits that keep note length arithmetic within i32 and bound the work done
+// for a single note, multi-measure rest or ending.
+const MAX_LENGTH_NUMBER: i32 = 128;
+const MAX_NOTE_LENGTH: i32 = 64;
+const MAX_NOTE_DENOMINATOR: i32 = 4096;
+const MAX_REST_MEASURES: i32 = 1024;
+const MAX_ENDING: i32 = 32;
+
+// Reads a unit note length such as `1/8`.
+fn parse_unit(value: &str) -> Result<Fraction, &'static str> {
+    match parse_fraction(value) {
+        Some(unit)
+            if unit.numerator() <= MAX_LENGTH_NUMBER && unit.denominator() <= MAX_LENGTH_NUMBER =>
+        {
+            Ok(unit)
+        }
+        _ => Err("invalid L: field"),
+    }
+}
+
+// Reads a length multiplier such as `3`, `/`, `//`, `/4` or `3/2`.
+fn parse_length(chars: &[char], i: &mut usize) -> Result<Fraction, &'static str> {
+    let numerator = parse_number(chars, i).unwrap_or(1);
+    let mut denominator = 1;
+    while *i < chars.len() && chars[*i] == '/' {
+        *i += 1;
+        denominator = parse_number(chars, i)
+            .unwrap_or(2)
+            .checked_mul(denominator)
+            .ok_or("note length out of range")?;
+    }
+    if numerator <= 0 || denominator <= 0 {
+        return Err("note length must be positive");
+    }
+    if numerator > MAX_LENGTH_NUMBER || denominator > MAX_LENGTH_NUMBER {
+        return Err("note length out of range");
+    }
+    Ok(Fraction::new(numerator, denominator))
+}
+
+// Reads an accidental, note letter and octave marks, leaving the length unread.
+fn parse_note(
+    chars: &[char],
+    i: &mut usize,
+) -> Result<(Option<Accidental>, NoteName, i32), &'static str> {
+    let rest: String = chars[*i..std::cmp::min(*i + 2, chars.len())]
+        .iter()
+        .collect();
+    let accidental = match parse_accidental(&rest) {
+        Some((accidental, after)) => {
+            *i += rest.len() - after.len();
+            Some(accidental)
+        }
+        None => None,
+    };
+    let letter = *chars.get(*i).ok_or("missing note letter")?;
+    let name = NoteName::from_str(&letter.to_string())
+        .map_err(|_| "accidental must be followed by a note")?;
+    let mut octave = if letter.is_ascii_lowercase() { 5 } else { 4 };
+    *i += 1;
+    while *i < chars.len() {
+        match chars[*i] {
+            '\'' => octave += 1,
+            ',' => octave -= 1,
+            _ => break,
+        }
+        *i += 1;
+    }
+    if !(-1..=9).contains(&octave) {
+        return Err("octave out of range");
+    }
+    Ok((accidental, name, octave))
+}
+
+fn find(chars: &[char], from: usize, c: char) -> Result<usize, &'static str> {
+    chars[from..]
+        .iter()
+        .position(|other| *other == c)
+        .map(|index| index + from)
+        .ok_or("unterminated element in tune body")
+}
+
+#[derive(Clone)]
+struct Item {
+    pitches: Vec<Pitch>,
+    length: Fraction,
+    tuplet: Option<Tuplet>,
+    tie: bool,
+    articulations: Vec<Articulation>,
+    dynamics: Option<Dynamic>,
+}
+
+impl Item {
+    // The events written for the item, tied together when one duration cannot hold it.
+    fn events(&self, tied: &mut bool) -> Result<Vec<Event>, &'static str> {
+        let in_range = |length: Fraction| {
+            let length = length.to_irreducible();
+            length <= Fraction::new(MAX_NOTE_LENGTH, 1)
+                && length.denominator() <= MAX_NOTE_DENOMINATOR
+        };
+        let sounding = match self.tuplet {
+            Some(tuplet) => self.length * tuplet.ratio(),
+            None => self.length,
+        };
+        if !in_range(self.length) || !in_range(sounding) {
+            return Err("note length out of range");
+        }
+        let durations = match self.tuplet {
+            Some(tuplet) => match Duration::try_from(self.length) {
+                Ok(duration) if duration.tuplet().is_none() => vec![duration.with_tuplet(tuplet)],
+                _ => Duration::decompose(self.length * tuplet.ratio())?,
+            },
+            None => match Duration::try_from(self.length) {
+                Ok(duration) => vec![duration],
+                Err(_) => Duration::decompose(self.length)?,
+            },
+        };
+
+        let count = durations.len();
+        let mut events = Vec::with_capacity(count);
+        for (n, duration) in durations.into_iter().enumerate() {
+            let tie = match (*tied || n > 0, self.tie || n + 1 < count) {
+                (true, true) => Some(Tie::Continue),
+                (true, false) => Some(Tie::Stop),
+                (false, true) => Some(Tie::Start),
+                (false, false) => None,
+            };
+            let first = n == 0;
+            events.push(match self.pitches.len() {
+                0 => Rest::new(duration).into(),
+                1 => {
+                    let mut note = Note::new(self.pitches[0], duration);
+                    if let Some(tie) = tie {
+                        note = note.with_tie(tie);
+                    }
+                    for articulation in self.articulations.iter().filter(|_| first) {
+                        note = note.with_articulation(*articulation);
+                    }
+                    if let Some(dynamics) = self.dynamics.filter(|_| first) {
+                        note = note.with_dynamics(dynamics);
+                    }
+                    note.into()
+                }
+                _ => {
+                    let mut chord = ChordNote::new(self.pitches.clone(), duration);
+                    if let Some(tie) = tie {
+                        chord = chord.with_tie(tie);
+                    }
+                    for articulation in self.articulations.iter().filter(|_| first) {
+                        chord = chord.with_articulation(*articulation);
+                    }
+                    if let Some(dynamics) = self.dynamics.filter(|_| first) {
+                        chord = chord.with_dynamics(dynamics);
+                    }
+                    chord.into()
+                }
+            });
+        }
+        *tied = self.tie && !self.pitches.is_empty();
+        Ok(events)
+    }
+}
+
+#[derive(Clone)]
+struct Bar {
+    voices: Vec<Vec<Item>>,
+    start_repeat: bool,
+    end_repeat: bool,
+    ending: Option<Vec<u32>>,
+    time: Option<TimeSignature>,
+    key: Option<KeySignature>,
+    mode: Option<Mode>,
+    clef: Option<Clef>,
+}
+
+impl Bar {
+    fn new() -> Bar {
+        Bar {
+            voices: vec![Vec::new()],
+            start_repeat: false,
+            end_repeat: false,
+            ending: None,
+            time: None,
+            key: None,
+            mode: None,
+            clef: None,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.voices.iter().all(Vec::is_empty)
+    }
+}
+
+struct VoiceReader {
+    id: String,
+    name: String,
+    bars: Vec<Bar>,
+    bar: Bar,
+    key: KeySignature,
+    overlay: usize,
+    accidentals: Vec<(NoteName, i32, Accidental)>,
+    tuplet: Option<(Tuplet, usize)>,
+    broken: Option<Fraction>,
+    articulations: Vec<Articulation>,
+    dynamics: Option<Dynamic>,
+}
+
+impl VoiceReader {
+    fn new(id: &str, name: &str) -> VoiceReader {
+        VoiceReader {
+            id: id.to_string(),
+            name: name.to_string(),
+            bars: Vec::new(),
+            bar: Bar::new(),
+            key: KeySignature::new(0),
+            overlay: 0,
+            accidentals: Vec::new(),
+            tuplet: None,
+            broken: None,
+            articulations: Vec::new(),
+            dynamics: None,
+        }
+    }
+
+    // Accidentals hold for the same note and octave until the next bar line.
+    fn pitch(&mut self, explicit: Option<Accidental>, name: NoteName, octave: i32) -> Pitch {
+        let accidental = match explicit {
+            Some(accidental) => {
+                self.accidentals.push((name, octave, accidental));
+                accidental
+            }
+            None => self
+                .accidentals
+                .iter()
+                .rev()
+                .find(|(other, other_octave, _)| *other == name && *other_octave == octave)
+                .map(|(_, _, accidental)| *accidental)
+                .unwrap_or_else(|| self.key.accidental(name)),
+        };
+        Pitch::new_with_accidental(name, accidental, octave)
+    }
+
+    fn push(&mut self, pitches: Vec<Pitch>, length: Fraction) {
+        let length = match self.broken.take() {
+            Some(factor) => length * factor,
+            None => length,
+        };
+        let tuplet = match self.tuplet {
+            Some((tuplet, remaining)) => {
+                self.tuplet = if remaining > 1 {
+                    Some((tuplet, remaining - 1))
+                } else {
+                    None
+                };
+                Some(tuplet)
+            }
+            None => None,
+        };
+        let item = Item {
+            pitches,
+            length,
+            tuplet,
+            tie: false,
+            articulations: std::mem::take(&mut self.articulations),
+            dynamics: self.dynamics.take(),
+        };
+        self.bar.voices[self.overlay].push(item);
+    }
+
+    fn last(&mut self) -> Option<&mut Item> {
+        self.bar.voices[self.overlay].last_mut()
+    }
+
+    fn overlay(&mut self) {
+        self.overlay += 1;
+        if self.bar.voices.len() <= self.overlay {
+            self.bar.voices.push(Vec::new());
+        }
+    }
+
+    // Ends the current bar. An ending started by `|1` carries on over plain bar
+    // lines until a repeat, a double bar or another ending.
+    fn bar_line(
+        &mut self,
+        end_repeat: bool,
+        start_repeat: bool,
+        plain: bool,
+        ending: Option<Vec<u32>>,
+    ) {
+        let finished = std::mem::replace(&mut self.bar, Bar::new());
+        self.overlay = 0;
+        self.accidentals.clear();
+
+        let previous = if finished.is_empty() {
+            if end_repeat {
+                if let Some(last) = self.bars.last_mut() {
+                    last.end_repeat = true;
+                }
+            }
+            self.bar.start_repeat = finished.start_repeat;
+            self.bar.time = finished.time;
+            self.bar.key = finished.key;
+            self.bar.mode = finished.mode;
+            self.bar.clef = finished.clef;
+            finished.ending
+        } else {
+            let mut finished = finished;
+            finished.end_repeat |= end_repeat;
+            let previous = finished.ending.clone();
+            self.bars.push(finished);
+            previous
+        };
+
+        self.bar.start_repeat |= start_repeat;
+        self.bar.ending = match ending {
+            Some(ending) => Some(ending),
+            None if plain => previous,
+            None => None,
+        };
+    }
+
+    fn finish(&mut self) {
+        if !self.bar.is_empty() {
+            let bar = std::mem::replace(&mut self.bar, Bar::new());
+            self.bars.push(bar);
+        }
+    }
+}
+
+struct TuneReader {
+    number: u32,
+    title: Option<String>,
+    tempo: Option<(Fraction, u32)>,
+    meter: Option<TimeSignature>,
+    unit: Option<Fraction>,
+    key: KeySignature,
+    mode: Option<Mode>,
+    clef: Option<Clef>,
+    voices: Vec<VoiceReader>,
+    current: Option<usize>,
+}
+
+impl TuneReader {
+    fn new() -> TuneReader {
+        TuneReader {
+            number: 1,
+            title: None,
+            tempo: None,
+            meter: None,
+            unit: None,
+            key: KeySignature::new(0),
+            mode: None,
+            clef: None,
+            voices: Vec::new(),
+            current: None,
+        }
+    }
+
+    // The unit note length defaults to a sixteenth in meters shorter than 3/4.
+    fn unit(&self) -> Fraction {
+        match (self.unit, &self.meter) {
+            (Some(unit), _) => unit,
+            (None, Some(meter)) if meter.measure_length() < Fraction::new(3, 4) => {
+                Fraction::new(1, 16)
+            }
+            _ => Fraction::new(1, 8),
+        }
+    }
+
+    fn voice(&mut self) -> &mut VoiceReader {
+        if self.voices.is_empty() {
+            let mut voice = VoiceReader::new("1", "");
+            voice.bar.time = self.meter.clone();
+            voice.bar.key = Some(self.key);
+            voice.bar.mode = self.mode;
+            voice.key = self.key;
+            voice.bar.clef = self.clef;
+            self.voices.push(voice);
+        }
+        let index = *self.current.get_or_insert(0);
+        &mut self.voices[index]
+    }
+
+    fn header_field(&mut self, field: char, value: &str) -> Result<(), &'static str> {
+        match field {
+            'X' => self.number = value.parse().map_err(|_| "invalid X: field")?,
+            'T' if self.title.is_none() => self.title = Some(value.to_string()),
+            'Q' => self.tempo = parse_tempo(value, self.unit()),
+            'M' => self.meter = parse_meter(value)?,
+            'L' => self.unit = Some(parse_unit(value)?),
+            'V' => self.select_voice(value),
+            'K' => {
+                let (key, mode, clef) = parse_key(value)?;
+                if let Some(key) = key {
+                    self.key = key;
+                    self.mode = mode;
+                }
+                self.clef = clef.or(self.clef);
+                self.unit = Some(self.unit());
+                // Voices declared in the header start with the header's settings.
+                for voice in self.voices.iter_mut() {
+                    voice.bar.time = self.meter.clone();
+                    voice.bar.key = Some(self.key);
+                    voice.bar.mode = self.mode;
+                    voice.key = self.key;
+                    voice.bar.clef = voice.bar.clef.or(self.clef);
+                }
+                self.current = None;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn field(&mut self, field: char, value: &str) -> Result<(), &'static str> {
+        match field {
+            'M' => {
+                self.meter = parse_meter(value)?;
+                let meter = self.meter.clone();
+                if meter.is_some() {
+                    self.voice().bar.time = meter;
+                }
+            }
+            'L' => self.unit = Some(parse_unit(value)?),
+            'K' => {
+                let (key, mode, clef) = parse_key(value)?;
+                let voice = self.voice();
+                if let Some(key) = key {
+                    voice.bar.key = Some(key);
+                    voice.bar.mode = mode;
+                    voice.key = key;
+                }
+                if clef.is_some() {
+                    voice.bar.clef = clef;
+                }
+            }
+            'V' => self.select_voice(value),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // Switches to the voice named by a V: field, declaring it if it is new.
+    fn select_voice(&mut self, value: &str) {
+        // Quoted names may hold spaces, so they are taken out before splitting the rest.
+        let mut name = None;
+        let mut rest = value.to_string();
+        for prefix in ["name=\"", "nm=\""].iter() {
+            if let Some(start) = rest.find(prefix) {
+                let end = rest[start + prefix.len()..]
+                    .find('"')
+                    .map_or(rest.len(), |end| start + prefix.len() + end + 1);
+                name = Some(
+                    rest[start + prefix.len()..end]
+                        .trim_end_matches('"')
+                        .to_string(),
+                );
+                rest.replace_range(start..end, "");
+            }
+        }
+        let mut tokens = rest.split_whitespace();
+        let id = tokens.next().unwrap_or("1");
+        let mut clef = None;
+        for token in tokens {
+            if let Some(value) = token
+                .strip_prefix("name=")
+                .or_else(|| token.strip_prefix("nm="))
+            {
+                name = Some(value.to_string());
+            } else if let Some(value) = token.strip_prefix("clef=") {
+                clef = parse_clef(value);
+            } else if let Some(value) = parse_clef(token) {
+                clef = Some(value);
+            }
+        }
+
+        let index = match self.voices.iter().position(|voice| voice.id == id) {
+            Some(index) => index,
+            None => {
+                let mut voice = VoiceReader::new(id, name.as_deref().unwrap_or(id));
+                voice.bar.time = self.meter.clone();
+                voice.bar.key = Some(self.key);
+                voice.bar.mode = self.mode;
+                voice.key = self.key;
+                voice.bar.clef = self.clef;
+                self.voices.push(voice);
+                self.voices.len() - 1
+            }
+        };
+        if let Some(name) = name {
+            self.voices[index].name = name;
+        }
+        if clef.is_some() {
+            self.voices[index].bar.clef = clef;
+        }
+        self.current = Some(index);
+    }
+
+    fn decoration(&mut self, name: &str) {
+        let articulation = match name {
+            "staccato" => Articulation::Staccato,
+            "wedge" => Articulation::Staccatissimo,
+            "tenuto" => Articulation::Tenuto,
+            "accent" | ">" | "emphasis" => Articulation::Accent,
+            "marcato" => Articulation::Marcato,
+            "fermata" => Articulation::Fermata,
+            _ => {
+                if let Some((_, dynamics)) = DYNAMICS.iter().find(|(other, _)| *other == name) {
+                    self.voice().dynamics = Some(*dynamics);
+                }
+                return;
+            }
+        };
+        self.voice().articulations.push(articulation);
+    }
+
+    fn music(&mut self, line: &str) -> Result<(), &'static str> {
+        let chars: Vec<char> = line.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let next = chars.get(i + 1).copied();
+            match chars[i] {
+                '"' => i = find(&chars, i + 1, '"')? + 1,
+                '{' => i = find(&chars, i + 1, '}')? + 1,
+                '!' | '+' => {
+                    let end = find(&chars, i + 1, chars[i])?;
+                    let name: String = chars[i + 1..end].iter().collect();
+                    self.decoration(&name);
+                    i = end + 1;
+                }
+                '.' => {
+                    self.decoration("staccato");
+                    i += 1;
+                }
+                'H' => {
+                    self.decoration("fermata");
+                    i += 1;
+                }
+                'L' => {
+                    self.decoration("accent");
+                    i += 1;
+                }
+                '(' if next.is_some_and(|c| c.is_ascii_digit()) => {
+                    i += 1;
+                    self.tuplet(&chars, &mut i)?;
+                }
+                '-' => {
+                    if let Some(item) = self.voice().last() {
+                        item.tie = true;
+                    }
+                    i += 1;
+                }
+                '>' | '<' => {
+                    let c = chars[i];
+                    let mut count = 0;
+                    while i < chars.len() && chars[i] == c {
+                        count += 1;
+                        i += 1;
+                    }
+                    let short = Fraction::new(1, 1 << std::cmp::min(count, 3));
+                    let long = Fraction::new(2, 1) - short;
+                    let (before, after) = if c == '>' {
+                        (long, short)
+                    } else {
+                        (short, long)
+                    };
+                    let voice = self.voice();
+                    if let Some(item) = voice.last() {
+                        item.length *= before;
+                        voice.broken = Some(after);
+                    }
+                }
+                '&' => {
+                    self.voice().overlay();
+                    i += 1;
+                }
+                '[' if next.is_some_and(|c| c.is_ascii_alphabetic())
+                    && chars.get(i + 2) == Some(&':') =>
+                {
+                    let end = find(&chars, i, ']')?;
+                    let value: String = chars[i + 3..end].iter().collect();
+                    self.field(chars[i + 1], value.trim())?;
+                    i = end + 1;
+                }
+                '[' if next.is_some_and(|c| c.is_ascii_digit()) => {
+                    i += 1;
+                    let ending = parse_ending(&chars, &mut i)?;
+                    self.voice().bar.ending = Some(ending);
+                }
+                '[' if next == Some('|') => self.bar_line(&chars, &mut i)?,
+                '[' => self.chord(&chars, &mut i)?,
+                '|' | ':' => self.bar_line(&chars, &mut i)?,
+                'z' | 'x' => {
+                    i += 1;
+                    let length = self.unit() * parse_length(&chars, &mut i)?;
+                    self.voice().push(Vec::new(), length);
+                }
+                'Z' | 'X' => {
+                    i += 1;
+                    let count = parse_number(&chars, &mut i).unwrap_or(1);
+                    if count > MAX_REST_MEASURES {
+                        return Err("multi-measure rest is too long");
+                    }
+                    let length = self
+                        .meter
+                        .as_ref()
+                        .map_or(Fraction::ONE, TimeSignature::measure_length);
+                    let voice = self.voice();
+                    for n in 0..count {
+                        if n > 0 {
+                            voice.bar_line(false, false, true, None);
+                        }
+                        voice.push(Vec::new(), length);
+                    }
+                }
+                '^' | '_' | '=' | 'A'..='G' | 'a'..='g' => {
+                    let (accidental, name, octave) = parse_note(&chars, &mut i)?;
+                    let length = self.unit() * parse_length(&chars, &mut i)?;
+                    let voice = self.voice();
+                    let pitch = voice.pitch(accidental, name, octave);
+                    voice.push(vec![pitch], length);
+                }
+                _ => i += 1,
+            }
+        }
+        Ok(())
+    }
+
+    // Reads `(p:q:r`: p notes in the time of q, for the next r notes.
+    fn tuplet(&mut self, chars: &[char], i: &mut usize) -> Result<(), &'static str> {
+        let actual = parse_number(chars, i).ok_or("invalid tuplet")?;
+        let mut numbers = Vec::new();
+        while *i < chars.len() && chars[*i] == ':' {
+            *i += 1;
+            numbers.push(parse_number(chars, i));
+        }
+        let compound = self.meter.as_ref().is_some_and(TimeSignature::is_compound);
+        let normal = match numbers.first().copied().flatten() {
+            Some(normal) => normal,
+            None => match actual {
+                2 | 4 | 8 => 3,
+                3 | 6 => 2,
+                _ if compound => 3,
+                _ => 2,
+            },
+        };
+        let count = numbers.get(1).copied().flatten().unwrap_or(actual);
+        if actual <= 0 || normal <= 0 || count <= 0 {
+            return Err("invalid tuplet");
+        }
+        if actual > MAX_LENGTH_NUMBER || normal > MAX_LENGTH_NUMBER {
+            return Err("invalid tuplet");
+        }
+        let actual = u16::try_from(actual).map_err(|_| "invalid tuplet")?;
+        let normal = u16::try_from(normal).map_err(|_| "invalid tuplet")?;
+        self.voice().tuplet = Some((Tuplet::new(actual, normal), count as usize));
+        Ok(())
+    }
+
+    fn chord(&mut self, chars: &[char], i: &mut usize) -> Result<(), &'static str> {
+        *i += 1;
+        let unit = self.unit();
+        let voice = self.voice();
+        let mut pitches = Vec::new();
+        let mut length = None;
+        let mut tie = false;
+        loop {
+            match chars.get(*i) {
+                Some(']') => break,
+                Some('-') => {
+                    tie = true;
+                    *i += 1;
+                }
+                Some(' ') | Some('.') => *i += 1,
+                Some(_) => {
+                    let (accidental, name, octave) = parse_note(chars, i)?;
+                    let note_length = parse_length(chars, i)?;
+                    length.get_or_insert(note_length);
+                    pitches.push(voice.pitch(accidental, name, octave));
+                }
+                None => return Err("unterminated chord"),
+            }
+        }
+        *i += 1;
+        let length = length.ok_or("empty chord")?;
+        let length = unit * length * parse_length(chars, i)?;
+        voice.push(pitches, length);
+        if let Some(item) = voice.last() {
+            item.tie = tie;
+        }
+        Ok(())
+    }
+
+    fn bar_line(&mut self, chars: &[char], i: &mut usize) -> Result<(), &'static str> {
+        let start = *i;
+        if chars[*i] == '[' {
+            *i += 1;
+        }
+        while *i < chars.len() {
+            match chars[*i] {
+                '|' | ':' => *i += 1,
+                ']' if *i > start && chars[*i - 1] == '|' => *i += 1,
+                _ => break,
+            }
+        }
+        let token: String = chars[start..*i].iter().collect();
+        let ending = match chars.get(*i) {
+            Some(c) if c.is_ascii_digit() => Some(parse_ending(chars, i)?),
+            _ => None,
+        };
+        let end_repeat = token.starts_with(':');
+        let start_repeat = token.ends_with(':') && token.len() > 1;
+        self.voice()
+            .bar_line(end_repeat, start_repeat, token == "|", ending);
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Tune, &'static str> {
+        let mut score = Score::default();
+        if let Some(title) = &self.title {
+            score = score.with_title(title);
+        }
+        for voice in self.voices.iter_mut() {
+            voice.finish();
+            let measures = measures(&unfold(&voice.bars))?;
+            score.push(Part::new(&voice.name, vec![Staff::new(measures)]));
+        }
+        Ok(Tune {
+            number: self.number,
+            tempo: self.tempo,
+            score,
+        })
+    }
+}
+
+// Reads ending numbers such as `1`, `1,3` or `1-3`.
+fn parse_ending(chars: &[char], i: &mut usize) -> Result<Vec<u32>, &'static str> {
+    let mut numbers = Vec::new();
+    while let Some(first) = parse_number(chars, i) {
+        let mut last = first;
+        if chars.get(*i) == Some(&'-') {
+            *i += 1;
+            last = parse_number(chars, i).unwrap_or(first);
+        }
+        if first > MAX_ENDING || last > MAX_ENDING {
+            return Err("ending number out of range");
+        }
+        numbers.extend((first..=last).map(|n| n as u32));
+        if chars.get(*i) != Some(&',') {
+            break;
+        }
+        *i += 1;
+    }
+    Ok(numbers)
+}
+
+// Plays repeats and numbered endings out in full, as scores have no repeat structure.
+fn unfold(bars: &[Bar]) -> Vec<&Bar> {
+    let mut played = Vec::new();
+    let mut start = 0;
+    let mut pass = 1;
+    let mut i = 0;
+    while i < bars.len() {
+        let bar = &bars[i];
+        if bar.start_repeat && pass == 1 {
+            start = i;
+        }
+        match &bar.ending {
+            Some(ending) if !ending.contains(&pass) => {
+                i += 1;
+                continue;
+            }
+            None if pass > 1 && i > 0 && bars[i - 1].ending.is_some() => {
+                pass = 1;
+                start = i;
+            }
+            _ => {}
+        }
+        played.push(bar);
+
+        if bar.end_repeat {
+            let again = match bar.ending {
+                Some(_) => bars[i + 1..]
+                    .iter()
+                    .take_while(|later| !later.start_repeat)
+                    .any(|later| {
+                        later
+                            .ending
+                            .as_ref()
+                            .is_some_and(|ending| ending.contains(&(pass + 1)))
+                    }),
+                None => pass == 1,
+            };
+            if again {
+                pass += 1;
+                i = start;
+                continue;
+            }
+            if bar.ending.is_none() {
+                pass = 1;
+                start = i + 1;
+            }
+        }
+        i += 1;
+    }
+    played
+}
+
+fn measures(bars: &[&Bar]) -> Result<Vec<Measure>, &'static str> {
+    let mut tied: Vec<bool> = Vec::new();
+    let mut meter: Option<TimeSignature> = None;
+    let mut key: Option<KeySignature> = None;
+    let mut mode: Option<Mode> = None;
+    let mut clef: Option<Clef> = None;
+    let mut measures = Vec::with_capacity(bars.len());
+
+    for (index, bar) in bars.iter().enumerate() {
+        let mut voices = Vec::new();
+        for (n, items) in bar.voices.iter().enumerate() {
+            if tied.len() <= n {
+                tied.resize(n + 1, false);
+            }
+            let mut events = Vec::new();
+            for item in items.iter() {
+                events.extend(item.events(&mut tied[n])?);
+            }
+            if n == 0 || !events.is_empty() {
+                voices.push(Voice::new(events));
+            }
+        }
+
+        // Attributes repeated by unfolding are only kept where they change.
+        let mut measure = Measure::new(voices);
+        if bar.time.is_some() && bar.time != meter {
+            meter = bar.time.clone();
+            measure = measure.with_time_signature(bar.time.clone().unwrap());
+        }
+        if let Some(signature) = bar
+            .key
+            .filter(|signature| Some(*signature) != key || bar.mode != mode)
+        {
+            key = Some(signature);
+            mode = bar.mode;
+            measure = measure.with_key_signature(signature);
+            if let Some(mode) = mode {
+                measure = measure.with_mode(mode);
+            }
+        }
+        if let Some(value) = bar.clef.filter(|value| Some(*value) != clef) {
+            clef = Some(value);
+            measure = measure.with_clef(value);
+        }
+
+        let length = measure
+            .voices()
+            .iter()
+            .map(Voice::length)
+            .max()
+            .unwrap_or(Fraction::ZERO);
+        let short = meter
+            .as_ref()
+            .is_some_and(|meter| length < meter.measure_length());
+        if short && (index == 0 || index + 1 == bars.len()) {
+            measure = measure.with_pickup();
+        }
+        measures.push(measure);
+    }
+    Ok(measures)
+}
+
+const DYNAMICS: [(&str, Dynamic); 8] = [
+    ("ppp", Dynamic::Ppp),
+    ("pp", Dynamic::Pp),
+    ("p", Dynamic::P),
+    ("mp", Dynamic::Mp),
+    ("mf", Dynamic::Mf),
+    ("f", Dynamic::F),
+    ("ff", Dynamic::Ff),
+    ("fff", Dynamic::Fff),
+];
+
+// Tunes are written with an eighth-note unit length.
+fn unit() -> Fraction {
+    Fraction::new(1, 8)
+}
+
+pub fn write(tunes: &[Tune]) -> Result<String, &'static str> {
+    let mut out = String::new();
+    for (index, tune) in tunes.iter().enumerate() {
+        if index > 0 {
+            out.push('\n');
+        }
+        write_tune(&mut out, tune)?;
+    }
+    Ok(out)
+}
+
+fn write_tune(out: &mut String, tune: &Tune) -> Result<(), &'static str> {
+    let score = tune.score();
+    let staves: Vec<(&Part, &Staff)> = score
+        .parts()
+        .iter()
+        .flat_map(|part| part.staves().iter().map(move |staff| (part, staff)))
+        .collect();
+
+    out.push_str(&format!("X:{}\n", tune.number()));
+    if let Some(title) = score.title() {
+        out.push_str(&format!("T:{}\n", title));
+    }
+    let meter = staves.first().map_or_else(
+        || TimeSignature::new(4, 4),
+        |(_, staff)| staff.time_signature_at(0),
+    );
+    out.push_str(&format!("M:{}\n", meter));
+    out.push_str(&format!("L:{}\n", unit()));
+    if let Some((beat, bpm)) = tune.tempo() {
+        out.push_str(&format!("Q:{}={}\n", beat.to_irreducible(), bpm));
+    }
+    if staves.len() > 1 {
+        for (index, (part, staff)) in staves.iter().enumerate() {
+            out.push_str(&format!("V:{} name=\"{}\"", index + 1, part.name()));
+            if let Some(clef) = clef_text(&staff.clef_at(0)) {
+                out.push_str(&format!(" clef={}", clef));
+            }
+            out.push('\n');
+        }
+    }
+    let (key, mode) = staves.first().map_or_else(
+        || (KeySignature::new(0), Mode::Major),
+        |(_, staff)| (staff.key_signature_at(0), staff.mode_at(0)),
+    );
+    out.push_str(&format!("K:{}", key_text(&key, mode)?));
+    if let [(_, staff)] = staves.as_slice() {
+        if let Some(clef) = clef_text(&staff.clef_at(0)) {
+            out.push_str(&format!(" clef={}", clef));
+        }
+    }
+    out.push('\n');
+
+    for (index, (_, staff)) in staves.iter().enumerate() {
+        if staves.len() > 1 {
+            out.push_str(&format!("V:{}\n", index + 1));
+        }
+        write_staff(out, staff)?;
+    }
+    Ok(())
+}
+
+fn key_text(signature: &KeySignature, mode: Mode) -> Result<String, &'static str> {
+    if let Some(key) = signature.key(mode) {
+        let accidental = key.accidental().semitones();
+        let marks = if accidental > 0 { "#" } else { "b" };
+        let mode = match mode {
+            Mode::Major => "",
+            Mode::Minor => "m",
+            Mode::Dorian => "Dor",
+            Mode::Phrygian => "Phr",
+            Mode::Lydian => "Lyd",
+            Mode::Mixolydian => "Mix",
+            Mode::Locrian => "Loc",
+        };
+        return Ok(format!(
+            "{}{}{}",
+            key.name(),
+            marks.repeat(accidental.unsigned_abs() as usize),
+            mode
+        ));
+    }
+    let mut text = "C exp".to_string();
+    for name in [C, D, E, F, G, A, B].iter() {
+        let accidental = signature.accidental(*name);
+        if accidental != NATURAL {
+            text.push_str(&format!(
+                " {}{}",
+                accidental_text(accidental)?,
+                name.to_char().to_ascii_lowercase()
+            ));
+        }
+    }
+    Ok(text)
+}
+
+fn clef_text(clef: &Clef) -> Option<String> {
+    let name = match (clef.sign(), clef.line()) {
+        (ClefSign::G, 2) if clef.octave_change() == 0 => return None,
+        (ClefSign::G, 2) => "treble",
+        (ClefSign::F, 4) => "bass",
+        (ClefSign::F, 3) => "bass3",
+        (ClefSign::C, 1) => "alto1",
+        (ClefSign::C, 2) => "alto2",
+        (ClefSign::C, 3) => "alto",
+        (ClefSign::C, 4) => "tenor",
+        (ClefSign::Percussion, _) => "perc",
+        _ => return None,
+    };
+    let octave = match clef.octave_change() {
+        1 => "+8",
+        -1 => "-8",
+        _ => "",
+    };
+    Some(format!("{}{}", name, octave))
+}
+
+fn accidental_text(accidental: Accidental) -> Result<&'static str, &'static str> {
+    Ok(match accidental.cents() {
+        200 => "^^",
+        100 => "^",
+        50 => "^/",
+        0 => "=",
+        -50 => "_/",
+        -100 => "_",
+        -200 => "__",
+        _ => return Err("accidental cannot be written in ABC"),
+    })
+}
+
+fn length_text(length: Fraction) -> String {
+    let length = length.to_irreducible();
+    match (length.numerator(), length.denominator()) {
+        (1, 1) => String::new(),
+        (n, 1) => n.to_string(),
+        (1, 2) => "/".to_string(),
+        (1, d) => format!("/{}", d),
+        (n, d) => format!("{}/{}", n, d),
+    }
+}
+
+fn write_staff(out: &mut String, staff: &Staff) -> Result<(), &'static str> {
+    let measures = staff.measures();
+    for (index, measure) in measures.iter().enumerate() {
+        if index > 0 {
+            if let Some(time) = measure.time_signature() {
+                out.push_str(&format!("[M:{}] ", time));
+            }
+            if let Some(key) = measure.key_signature() {
+                let mode = measure.mode().unwrap_or(Mode::Major);
+                out.push_str(&format!("[K:{}] ", key_text(&key, mode)?));
+            }
+            if let Some(clef) = measure.clef() {
+                let clef = clef_text(&clef).unwrap_or_else(|| "treble".to_string());
+                out.push_str(&format!("[K:clef={}] ", clef));
+            }
+        }
+        let meter = staff.time_signature_at(index);
+        let key = staff.key_signature_at(index);
+        let voices = measure
+            .voices()
+            .iter()
+            .map(|voice| write_voice(voice, &meter, &key))
+            .collect::<Result<Vec<String>, _>>()?;
+        if voices.is_empty() {
+            out.push_str(&format!(
+                "z{}",
+                length_text(meter.measure_length() / unit())
+            ));
+        }
+        out.push_str(&voices.join(" & "));
+        if index + 1 == measures.len() {
+            out.push_str(" |]\n");
+        } else if index % 4 == 3 {
+            out.push_str(" |\n");
+        } else {
+            out.push_str(" | ");
+        }
+    }
+    Ok(())
+}
+
+fn note_text(
+    pitch: Pitch,
+    key: &KeySignature,
+    shown: &mut Vec<(NoteName, i32, Accidental)>,
+) -> Result<String, &'static str> {
+    let current = shown
+        .iter()
+        .rev()
+        .find(|(name, octave, _)| *name == pitch.name() && *octave == pitch.octave())
+        .map(|(_, _, accidental)| *accidental)
+        .unwrap_or_else(|| key.accidental(pitch.name()));
+    let mut text = String::new();
+    if pitch.accidental() != current {
+        text.push_str(accidental_text(pitch.accidental())?);
+        shown.push((pitch.name(), pitch.octave(), pitch.accidental()));
+    }
+    let letter = pitch.name().to_char();
+    if pitch.octave() >= 5 {
+        text.push(letter.to_ascii_lowercase());
+        text.push_str(&"'".repeat((pitch.octave() - 5) as usize));
+    } else {
+        text.push(letter);
+        text.push_str(&",".repeat((4 - pitch.octave()) as usize));
+    }
+    Ok(text)
+}
+
+// Notes of an eighth or shorter within the same beat are written together so they beam.
+fn write_voice(
+    voice: &Voice,
+    meter: &TimeSignature,
+    key: &KeySignature,
+) -> Result<String, &'static str> {
+    let events = voice.events();
+    let offsets = voice.offsets();
+    let bounds = meter.beat_offsets();
+    let beat = |index: usize| bounds.iter().rposition(|bound| *bound <= offsets[index]);
+    let beamed =
+        |index: usize| !events[index].is_rest() && events[index].duration().denominator() >= 8;
+
+    let mut shown = Vec::new();
+    let mut tuplet_left = 0;
+    let mut text = String::new();
+    for (index, event) in events.iter().enumerate() {
+        if index > 0 && !(beamed(index - 1) && beamed(index) && beat(index - 1) == beat(index)) {
+            text.push(' ');
+        }
+
+        let duration = event.duration();
+        let mut written = event.length();
+        match duration.tuplet() {
+            Some(tuplet) => {
+                written /= tuplet.ratio();
+                if tuplet_left == 0 {
+                    tuplet_left = events[index..]
+                        .iter()
+                        .take_while(|other| other.duration().tuplet() == Some(tuplet))
+                        .count();
+                    text.push_str(&format!(
+                        "({}:{}:{}",
+                        tuplet.actual(),
+                        tuplet.normal(),
+                        tuplet_left
+                    ));
+                }
+                tuplet_left -= 1;
+            }
+            None => tuplet_left = 0,
+        }
+
+        let (tie, articulations, dynamics) = match event {
+            Event::Note(note) => (note.tie(), note.articulations(), note.dynamics()),
+            Event::Chord(chord) => (chord.tie(), chord.articulations(), chord.dynamics()),
+            Event::Rest(_) => (None, &[][..], None),
+        };
+        if let Some(dynamics) = dynamics {
+            let (name, _) = DYNAMICS
+                .iter()
+                .find(|(_, other)| *other == dynamics)
+                .unwrap();
+            text.push_str(&format!("!{}!", name));
+        }
+        for articulation in articulations.iter() {
+            text.push_str(match articulation {
+                Articulation::Staccato => ".",
+                Articulation::Staccatissimo => "!wedge!",
+                Articulation::Tenuto => "!tenuto!",
+                Articulation::Accent => "!accent!",
+                Articulation::Marcato => "!marcato!",
+                Articulation::Fermata => "!fermata!",
+            });
+        }
+
+        match event {
+            Event::Note(note) => text.push_str(&note_text(note.pitch(), key, &mut shown)?),
+            Event::Chord(chord) => {
+                text.push('[');
+                for pitch in chord.pitches().iter() {
+                    text.push_str(&note_text(*pitch, key, &mut shown)?);
+                }
+                text.push(']');
+            }
+            Event::Rest(_) => text.push('z'),
+        }
+        text.push_str(&length_text(written / unit()));
+        if let Some(Tie::Start) | Some(Tie::Continue) = tie {
+            text.push('-');
+        }
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::music::pitch::*;
+
+    const TUNE: &str = "X:3
+T:Test
+M:3/4
+L:1/8
+Q:1/4=96
+K:D
+!p!A,2 ^D.=c HD2 | f'2 [ceg]2 z d | % comment
+F>G A<B (3cde |]
+";
+
+    const VOICES: &str = "X:1
+M:4/4
+L:1/4
+V:1 name=\"Upper voice\"
+V:2 name=\"Lower\" clef=bass
+K:C
+V:1
+c4 |]
+V:2
+E,4 & C,2 z2 |]
+";
+
+    fn events(tune: &Tune, measure: usize) -> &[Event] {
+        tune.score().parts()[0].staves()[0].measures()[measure].voices()[0].events()
+    }
+
+    #[test]
+    fn test_read() {
+        let tunes = read(TUNE).unwrap();
+        assert_eq!(1, tunes.len());
+        let tune = &tunes[0];
+        assert_eq!(3, tune.number());
+        assert_eq!(Some((Fraction::new(1, 4), 96)), tune.tempo());
+        assert_eq!(Some("Test"), tune.score().title());
+        assert_eq!(Ok(()), tune.score().validate());
+
+        let measures = tune.score().parts()[0].staves()[0].measures();
+        assert_eq!(3, measures.len());
+        assert_eq!(
+            Some(&TimeSignature::new(3, 4)),
+            measures[0].time_signature()
+        );
+        assert_eq!(Some(KeySignature::new(2)), measures[0].key_signature());
+        assert!(!measures[0].is_pickup());
+        assert_eq!(
+            vec![
+                Event::from(Note::new(A3, Duration::new(4)).with_dynamics(Dynamic::P)),
+                Note::new(Ds4, Duration::new(8)).into(),
+                Note::new(C5, Duration::new(8))
+                    .with_articulation(Articulation::Staccato)
+                    .into(),
+                Note::new(Ds4, Duration::new(4))
+                    .with_articulation(Articulation::Fermata)
+                    .into(),
+            ],
+            events(tune, 0)
+        );
+        assert_eq!(
+            vec![
+                Event::from(Note::new(Fs6, Duration::new(4))),
+                ChordNote::new(vec![Cs5, E5, G5], Duration::new(4)).into(),
+                Rest::new(Duration::new(8)).into(),
+                Note::new(D5, Duration::new(8)).into(),
+            ],
+            events(tune, 1)
+        );
+        let triplet = Tuplet::new(3, 2);
+        assert_eq!(
+            vec![
+                Event::from(Note::new(Fs4, Duration::new_with_dots(8, 1))),
+                Note::new(G4, Duration::new(16)).into(),
+                Note::new(A4, Duration::new(16)).into(),
+                Note::new(B4, Duration::new_with_dots(8, 1)).into(),
+                Note::new(Cs5, Duration::new(8).with_tuplet(triplet)).into(),
+                Note::new(D5, Duration::new(8).with_tuplet(triplet)).into(),
+                Note::new(E5, Duration::new(8).with_tuplet(triplet)).into(),
+            ],
+            events(tune, 2)
+        );
+    }
+
+    #[test]
+    fn test_read_repeats() {
+        let tune = &read("M:2/4\nL:1/4\nK:C\nG |: C D |1 E2 :|2 F2 |]\n").unwrap()[0];
+        let measures = tune.score().parts()[0].staves()[0].measures();
+        let first: Vec<&Event> = measures
+            .iter()
+            .map(|measure| &measure.voices()[0].events()[0])
+            .collect();
+        assert_eq!(
+            vec![
+                &Event::from(Note::new(G4, Duration::new(4))),
+                &Note::new(C4, Duration::new(4)).into(),
+                &Note::new(E4, Duration::new(2)).into(),
+                &Note::new(C4, Duration::new(4)).into(),
+                &Note::new(F4, Duration::new(2)).into(),
+            ],
+            first
+        );
+        assert!(measures[0].is_pickup());
+        assert!(!measures[1].is_pickup());
+    }
+
+    #[test]
+    fn test_read_ties_and_voices() {
+        let tune = &read("M:2/4\nL:1/4\nK:C\nc2- | c d |]\n").unwrap()[0];
+        assert_eq!(
+            vec![Event::from(
+                Note::new(C5, Duration::new(2)).with_tie(Tie::Start)
+            )],
+            events(tune, 0)
+        );
+        assert_eq!(
+            &Event::from(Note::new(C5, Duration::new(4)).with_tie(Tie::Stop)),
+            &events(tune, 1)[0]
+        );
+
+        let tune = &read(VOICES).unwrap()[0];
+        let parts = tune.score().parts();
+        let names: Vec<&str> = parts.iter().map(Part::name).collect();
+        assert_eq!(vec!["Upper voice", "Lower"], names);
+        let lower = &parts[1].staves()[0].measures()[0];
+        assert_eq!(Some(Clef::BASS), lower.clef());
+        assert_eq!(2, lower.voices().len());
+        assert_eq!(
+            vec![
+                Event::from(Note::new(C3, Duration::new(2))),
+                Rest::new(Duration::new(2)).into(),
+            ],
+            lower.voices()[1].events()
+        );
+
+        // A key change in one voice leaves the spelling of the others alone.
+        let tune = &read("L:1/4\nV:1\nV:2\nK:C\nV:1\nK:G\nF4 |]\nV:2\nF4 |]\n").unwrap()[0];
+        let parts = tune.score().parts();
+        let upper = parts[0].staves()[0].measures()[0].voices()[0].events();
+        let lower = parts[1].staves()[0].measures()[0].voices()[0].events();
+        assert_eq!(vec![Event::from(Note::new(Fs4, Duration::new(1)))], upper);
+        assert_eq!(vec![Event::from(Note::new(F4, Duration::new(1)))], lower);
+    }
+
+    #[test]
+    fn test_read_errors() {
+        assert!(read("X:1\nabc\nK:C\n").is_err());
+        assert!(read("X:1\nT:No key\n").is_err());
+        assert!(read("K:C\n[ceg\n").is_err());
+        assert!(read("K:C\nC,,,,,,\n").is_err());
+        assert!(read("K:Cxyz\n").is_err());
+        assert_eq!(Err("invalid tuplet"), read("K:C\n(65536abc\n"));
+        let slashes = "/".repeat(40);
+        assert_eq!(
+            Err("note length out of range"),
+            read(&format!("K:C\nA{}\n", slashes))
+        );
+        assert_eq!(
+            Err("note length out of range"),
+            read("L:1/8\nK:C\nC2147483647\n")
+        );
+        assert_eq!(Err("note length out of range"), read("L:1/1\nK:C\nC128\n"));
+        assert_eq!(Err("invalid L: field"), read("L:1/2147483647\nK:C\nC\n"));
+        assert_eq!(
+            Err("multi-measure rest is too long"),
+            read("K:C\nZ2147483647\n")
+        );
+        assert_eq!(
+            Err("ending number out of range"),
+            read("K:C\nC |1-2000000000 D :|\n")
+        );
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        for input in [TUNE, VOICES].iter() {
+            let tunes = read(input).unwrap();
+            let written = write(&tunes).unwrap();
+            assert_eq!(tunes, read(&written).unwrap());
+        }
+
+        let written = write(&read(TUNE).unwrap()).unwrap();
+        assert!(written.starts_with("X:3\nT:Test\nM:3/4\nL:1/8\nQ:1/4=96\nK:D\n"));
+        assert!(written.contains("!p!A,2 ^D.=c !fermata!D2 |"));
+        assert!(written.contains("F3/2G/ A/B3/2 (3:2:3cde |]"));
+
+        let written = write(&read(VOICES).unwrap()).unwrap();
+        assert!(written.contains("V:2 name=\"Lower\" clef=bass\n"));
+        assert!(written.contains("E,8 & C,4 z4 |]"));
+
+        let tunes = read("M:4/4\nL:1/4\nK:Am\nA4 | [K:Dmix] d4 |]\n").unwrap();
+        let measures = tunes[0].score().parts()[0].staves()[0].measures();
+        assert_eq!(Some(Mode::Minor), measures[0].mode());
+        assert_eq!(Some(Mode::Mixolydian), measures[1].mode());
+        let written = write(&tunes).unwrap();
+        assert!(written.contains("K:Am\n"));
+        assert!(written.contains("[K:DMix] "));
+        assert_eq!(tunes, read(&written).unwrap());
+    }
+}
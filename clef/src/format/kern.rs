@@ -0,0 +1,935 @@
+use crate::math::Fraction;
+use crate::music::{
+    Accidental, Articulation, ChordNote, Clef, ClefSign, Duration, Event, KeySignature, Measure,
+    Note, NoteName, Part, Pitch, Rest, Score, Staff, Tie, TimeSignature, Voice, A, B, C, D, E, F,
+    G, NATURAL,
+};
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+// Humdrum lists staves from the bottom up, so the leftmost spine is the
+// lowest staff. Spines are reversed when reading and writing to keep scores
+// in top to bottom order.
+
+pub fn read(input: &str) -> Result<Score, &'static str> {
+    let mut reader = Reader::default();
+    for line in input.lines() {
+        let line = line.trim_end_matches('\r');
+        if let Some(title) = line.strip_prefix("!!!OTL:") {
+            reader.title.get_or_insert_with(|| title.trim().to_string());
+            continue;
+        }
+        if line.is_empty() || line.starts_with('!') {
+            continue;
+        }
+        let tokens: Vec<&str> = line.split('\t').collect();
+        if reader.columns.is_empty() {
+            if !tokens.iter().all(|token| token.starts_with("**")) {
+                return Err("missing exclusive interpretations");
+            }
+            reader.exclusive(&tokens);
+            continue;
+        }
+        if tokens.len() != reader.columns.len() {
+            return Err("record has the wrong number of fields");
+        }
+        if tokens[0].starts_with('*') {
+            reader.interpretations(&tokens)?;
+        } else if tokens[0].starts_with('=') {
+            reader.barline()?;
+        } else {
+            reader.data(&tokens)?;
+        }
+    }
+    reader.finish()
+}
+
+// Keeps the arithmetic on reciprocal durations within i32.
+const MAX_RECIP: i32 = 4096;
+
+fn recip(text: &str) -> Result<Duration, &'static str> {
+    let digits = text.find(|c: char| !c.is_ascii_digit() && c != '%');
+    let (number, rest) = text.split_at(digits.unwrap_or(text.len()));
+    let (whole, part) = match number.split_once('%') {
+        Some((whole, part)) => (whole, part),
+        None => (number, "1"),
+    };
+    let whole: i32 = whole.parse().map_err(|_| "missing duration")?;
+    let part: i32 = part.parse().map_err(|_| "invalid duration")?;
+    if whole == 0 || part == 0 {
+        return Err("breve and longer durations are not supported");
+    }
+    if whole > MAX_RECIP || part > MAX_RECIP {
+        return Err("duration out of range");
+    }
+    let base = Duration::try_from(Fraction::new(part, whole))?;
+    let dots = rest.chars().take_while(|c| *c == '.').count();
+    if dots > 4 {
+        return Err("too many dots");
+    }
+    let duration = Duration::new_with_dots(base.denominator(), dots as u8);
+    Ok(match base.tuplet() {
+        Some(tuplet) => duration.with_tuplet(tuplet),
+        None => duration,
+    })
+}
+
+fn write_recip(duration: &Duration) -> String {
+    let base = match duration.tuplet() {
+        Some(tuplet) => Duration::new(duration.denominator()).with_tuplet(tuplet),
+        None => Duration::new(duration.denominator()),
+    };
+    let length = base.to_fraction().to_irreducible();
+    let number = match length.numerator() {
+        1 => length.denominator().to_string(),
+        n => format!("{}%{}", length.denominator(), n),
+    };
+    format!("{}{}", number, ".".repeat(duration.dots() as usize))
+}
+
+fn accidental(text: &str) -> Option<Accidental> {
+    match text {
+        "" | "n" => Some(NATURAL),
+        "#" => Some(Accidental::new(1)),
+        "##" => Some(Accidental::new(2)),
+        "-" => Some(Accidental::new(-1)),
+        "--" => Some(Accidental::new(-2)),
+        _ => None,
+    }
+}
+
+fn write_accidental(accidental: Accidental) -> Result<&'static str, &'static str> {
+    Ok(match accidental.cents() {
+        200 => "##",
+        100 => "#",
+        0 => "",
+        -100 => "-",
+        -200 => "--",
+        _ => return Err("microtonal accidentals cannot be written in kern"),
+    })
+}
+
+// Reads a pitch such as `cc#`, `BB-` or `e`: lowercase letters count octaves
+// up from middle C and uppercase letters count down from the octave below it.
+fn pitch(text: &str) -> Result<Pitch, &'static str> {
+    let letter = text.chars().next().ok_or("missing pitch")?;
+    let count = text.chars().take_while(|c| *c == letter).count() as i32;
+    let name = NoteName::from_str(&letter.to_string())?;
+    let octave = if letter.is_ascii_lowercase() {
+        3 + count
+    } else {
+        4 - count
+    };
+    if !(-1..=9).contains(&octave) {
+        return Err("octave out of range");
+    }
+    let rest = &text[count as usize..];
+    let accidental = accidental(rest).ok_or("invalid accidental")?;
+    Ok(Pitch::new_with_accidental(name, accidental, octave))
+}
+
+fn write_pitch(pitch: &Pitch) -> Result<String, &'static str> {
+    let letter = pitch.name().to_char();
+    let text = if pitch.octave() >= 4 {
+        letter
+            .to_ascii_lowercase()
+            .to_string()
+            .repeat((pitch.octave() - 3) as usize)
+    } else {
+        letter.to_string().repeat((4 - pitch.octave()) as usize)
+    };
+    Ok(format!("{}{}", text, write_accidental(pitch.accidental())?))
+}
+
+struct Token {
+    duration: Duration,
+    pitch: Option<Pitch>,
+    tie: Option<Tie>,
+    articulations: Vec<Articulation>,
+}
+
+// Reads one subtoken: a recip, a pitch or rest, and any signifiers around them.
+// Beams, stems, slurs and phrases are not kept.
+fn token(text: &str) -> Result<Option<Token>, &'static str> {
+    if text.contains('q') || text.contains('Q') {
+        return Ok(None);
+    }
+    let start = text
+        .find(|c: char| c.is_ascii_digit())
+        .ok_or("missing duration")?;
+    let end = text[start..]
+        .find(|c: char| !c.is_ascii_digit() && c != '%' && c != '.')
+        .map_or(text.len(), |end| start + end);
+    let duration = recip(&text[start..end])?;
+
+    let rest = &text[end..];
+    let pitch = match rest.find(|c: char| ('a'..='g').contains(&c.to_ascii_lowercase())) {
+        Some(index) if !rest.starts_with('r') => {
+            let letters = rest[index..]
+                .find(|c: char| !matches!(c, 'a'..='g' | 'A'..='G' | '#' | '-' | 'n'))
+                .map_or(rest.len(), |end| index + end);
+            Some(pitch(&rest[index..letters])?)
+        }
+        _ if rest.contains('r') => None,
+        _ => return Err("missing pitch"),
+    };
+
+    let tie = if text.contains('[') {
+        Some(Tie::Start)
+    } else if text.contains('_') {
+        Some(Tie::Continue)
+    } else if text.contains(']') {
+        Some(Tie::Stop)
+    } else {
+        None
+    };
+    let mut articulations = Vec::new();
+    for (signifier, articulation) in [
+        ("'", Articulation::Staccato),
+        ("`", Articulation::Staccatissimo),
+        ("~", Articulation::Tenuto),
+        (";", Articulation::Fermata),
+    ]
+    .iter()
+    {
+        if text.contains(signifier) {
+            articulations.push(*articulation);
+        }
+    }
+    if text.contains("^^") {
+        articulations.push(Articulation::Marcato);
+    } else if text.contains('^') {
+        articulations.push(Articulation::Accent);
+    }
+
+    Ok(Some(Token {
+        duration,
+        pitch,
+        tie,
+        articulations,
+    }))
+}
+
+fn event(text: &str) -> Result<Option<Event>, &'static str> {
+    let mut tokens = Vec::new();
+    for subtoken in text.split(' ') {
+        if let Some(token) = token(subtoken)? {
+            tokens.push(token);
+        }
+    }
+    let first = match tokens.first() {
+        Some(first) => first,
+        None => return Ok(None),
+    };
+    let pitches: Vec<Pitch> = tokens.iter().filter_map(|token| token.pitch).collect();
+    let event = match pitches.len() {
+        0 => Rest::new(first.duration).into(),
+        1 => {
+            let mut note = Note::new(pitches[0], first.duration);
+            if let Some(tie) = first.tie {
+                note = note.with_tie(tie);
+            }
+            for articulation in first.articulations.iter() {
+                note = note.with_articulation(*articulation);
+            }
+            note.into()
+        }
+        _ => {
+            let mut chord = ChordNote::new(pitches, first.duration);
+            if let Some(tie) = first.tie {
+                chord = chord.with_tie(tie);
+            }
+            for articulation in first.articulations.iter() {
+                chord = chord.with_articulation(*articulation);
+            }
+            chord.into()
+        }
+    };
+    Ok(Some(event))
+}
+
+fn key_signature(text: &str) -> Result<KeySignature, &'static str> {
+    let mut accidentals = Vec::new();
+    let mut rest = text;
+    while let Some(letter) = rest.chars().next() {
+        let name = NoteName::from_str(&letter.to_string())?;
+        let length = rest[1..]
+            .find(|c: char| c != '#' && c != '-' && c != 'n')
+            .map_or(rest.len(), |end| end + 1);
+        let accidental = accidental(&rest[1..length]).ok_or("invalid key signature")?;
+        accidentals.push((name, accidental));
+        rest = &rest[length..];
+    }
+    Ok(KeySignature::from_accidentals(&accidentals))
+}
+
+fn write_key_signature(signature: &KeySignature) -> Result<String, &'static str> {
+    // Sharps and flats are listed in the order they appear in standard signatures.
+    let sharps = [F, C, G, D, A, E, B];
+    let flats = [B, E, A, D, G, C, F];
+    let order = match signature.fifths() {
+        Some(fifths) if fifths < 0 => flats,
+        _ => sharps,
+    };
+    let mut text = String::new();
+    for name in order.iter() {
+        let accidental = signature.accidental(*name);
+        if accidental != NATURAL {
+            text.push(name.to_char().to_ascii_lowercase());
+            text.push_str(write_accidental(accidental)?);
+        }
+    }
+    Ok(format!("*k[{}]", text))
+}
+
+fn clef(text: &str) -> Result<Clef, &'static str> {
+    if text == "X" {
+        return Ok(Clef::PERCUSSION);
+    }
+    let sign = match text.chars().next() {
+        Some('G') => ClefSign::G,
+        Some('F') => ClefSign::F,
+        Some('C') => ClefSign::C,
+        _ => return Err("unknown clef"),
+    };
+    let rest = &text[1..];
+    let octave_change = -(rest.matches('v').count() as i32) + rest.matches('^').count() as i32;
+    let line = rest
+        .trim_start_matches(['v', '^'])
+        .parse()
+        .map_err(|_| "invalid clef line")?;
+    Ok(Clef::new(sign, line, octave_change))
+}
+
+fn write_clef(clef: &Clef) -> Result<String, &'static str> {
+    let sign = match clef.sign() {
+        ClefSign::G => 'G',
+        ClefSign::F => 'F',
+        ClefSign::C => 'C',
+        ClefSign::Percussion => return Ok("*clefX".to_string()),
+        ClefSign::Tab => return Err("tablature clefs cannot be written in kern"),
+    };
+    let octave = match clef.octave_change() {
+        change if change < 0 => "v".repeat(change.unsigned_abs() as usize),
+        change => "^".repeat(change as usize),
+    };
+    Ok(format!("*clef{}{}{}", sign, octave, clef.line()))
+}
+
+#[derive(Default)]
+struct Spine {
+    kern: bool,
+    part: Option<String>,
+    name: Option<String>,
+    voices: Vec<Vec<Event>>,
+    measures: Vec<Measure>,
+    time: Option<TimeSignature>,
+    key: Option<KeySignature>,
+    clef: Option<Clef>,
+}
+
+impl Spine {
+    fn interpretation(&mut self, token: &str) -> Result<(), &'static str> {
+        if let Some(meter) = token.strip_prefix("*M") {
+            if meter.starts_with(|c: char| c.is_ascii_digit()) {
+                self.time = Some(TimeSignature::from_str(meter)?);
+            }
+        } else if let Some(key) = token
+            .strip_prefix("*k[")
+            .and_then(|key| key.strip_suffix(']'))
+        {
+            self.key = Some(key_signature(key)?);
+        } else if let Some(text) = token.strip_prefix("*clef") {
+            self.clef = Some(clef(text)?);
+        } else if let Some(name) = token.strip_prefix("*I\"") {
+            self.name = Some(name.to_string());
+        } else if let Some(part) = token.strip_prefix("*part") {
+            self.part = Some(part.to_string());
+        }
+        Ok(())
+    }
+
+    // Ends the current measure, keeping attributes read before any notes for the next one.
+    fn barline(&mut self) {
+        while self.voices.last().is_some_and(Vec::is_empty) {
+            self.voices.pop();
+        }
+        if self.voices.is_empty() {
+            return;
+        }
+        let voices = std::mem::take(&mut self.voices)
+            .into_iter()
+            .map(Voice::new)
+            .collect();
+        let mut measure = Measure::new(voices);
+        if let Some(time) = self.time.take() {
+            measure = measure.with_time_signature(time);
+        }
+        if let Some(key) = self.key.take() {
+            measure = measure.with_key_signature(key);
+        }
+        if let Some(clef) = self.clef.take() {
+            measure = measure.with_clef(clef);
+        }
+        self.measures.push(measure);
+    }
+}
+
+// Each column of a record belongs to a spine and, once the spine has been
+// split, to one of its voices.
+#[derive(Clone, Copy)]
+struct Column {
+    spine: usize,
+    voice: usize,
+}
+
+#[derive(Default)]
+struct Reader {
+    title: Option<String>,
+    spines: Vec<Spine>,
+    columns: Vec<Column>,
+}
+
+impl Reader {
+    fn exclusive(&mut self, tokens: &[&str]) {
+        for (index, token) in tokens.iter().enumerate() {
+            self.spines.push(Spine {
+                kern: *token == "**kern",
+                ..Spine::default()
+            });
+            self.columns.push(Column {
+                spine: index,
+                voice: 0,
+            });
+        }
+    }
+
+    fn interpretations(&mut self, tokens: &[&str]) -> Result<(), &'static str> {
+        let mut columns = Vec::with_capacity(self.columns.len());
+        let mut index = 0;
+        while index < tokens.len() {
+            let column = self.columns[index];
+            let token = tokens[index];
+            let spine = &mut self.spines[column.spine];
+            match token {
+                "*^" => {
+                    columns.push(column);
+                    columns.push(column);
+                }
+                "*v" => {
+                    columns.push(column);
+                    while index + 1 < tokens.len()
+                        && tokens[index + 1] == "*v"
+                        && self.columns[index + 1].spine == column.spine
+                    {
+                        index += 1;
+                    }
+                }
+                "*-" => {}
+                "*+" | "*x" => return Err("unsupported spine manipulation"),
+                _ => {
+                    columns.push(column);
+                    // Attributes are taken from the first voice of each spine.
+                    if column.voice == 0 {
+                        spine.interpretation(token)?;
+                    }
+                }
+            }
+            index += 1;
+        }
+
+        // Voices are numbered by their order within the spine.
+        let mut counts = vec![0; self.spines.len()];
+        for column in columns.iter_mut() {
+            column.voice = counts[column.spine];
+            counts[column.spine] += 1;
+        }
+        self.columns = columns;
+        Ok(())
+    }
+
+    fn barline(&mut self) -> Result<(), &'static str> {
+        for spine in self.spines.iter_mut().filter(|spine| spine.kern) {
+            spine.barline();
+        }
+        Ok(())
+    }
+
+    fn data(&mut self, tokens: &[&str]) -> Result<(), &'static str> {
+        for (column, token) in self.columns.iter().zip(tokens.iter()) {
+            let spine = &mut self.spines[column.spine];
+            if !spine.kern || *token == "." {
+                continue;
+            }
+            if let Some(event) = event(token)? {
+                if spine.voices.len() <= column.voice {
+                    spine.voices.resize(column.voice + 1, Vec::new());
+                }
+                spine.voices[column.voice].push(event);
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Score, &'static str> {
+        self.barline()?;
+        let mut parts: Vec<Part> = Vec::new();
+        let mut part_ids: Vec<Option<String>> = Vec::new();
+        for spine in self.spines.into_iter().rev().filter(|spine| spine.kern) {
+            let part = spine.part;
+            let mut measures = spine.measures;
+            mark_pickups(&mut measures);
+            let staff = Staff::new(measures);
+            match part_ids.iter().position(|id| id.is_some() && *id == part) {
+                Some(index) => parts[index].push(staff),
+                None => {
+                    let name = spine.name.unwrap_or_default();
+                    parts.push(Part::new(&name, vec![staff]));
+                    part_ids.push(part);
+                }
+            }
+        }
+        if parts.is_empty() {
+            return Err("no **kern spines");
+        }
+        let score = Score::new(parts);
+        Ok(match self.title {
+            Some(title) => score.with_title(&title),
+            None => score,
+        })
+    }
+}
+
+// Short first and last measures are pickups; any other short measure is left as written.
+fn mark_pickups(measures: &mut [Measure]) {
+    let mut meter: Option<TimeSignature> = None;
+    let count = measures.len();
+    for (index, measure) in measures.iter_mut().enumerate() {
+        if let Some(time) = measure.time_signature() {
+            meter = Some(time.clone());
+        }
+        let meter = meter.clone().unwrap_or_else(|| TimeSignature::new(4, 4));
+        let length = measure
+            .voices()
+            .iter()
+            .map(Voice::length)
+            .max()
+            .unwrap_or(Fraction::ZERO);
+        if length < meter.measure_length() && (index == 0 || index + 1 == count) {
+            *measure = measure.clone().with_pickup();
+        }
+    }
+}
+
+fn write_event(event: &Event) -> Result<String, &'static str> {
+    let recip = write_recip(&event.duration());
+    let (pitches, tie, articulations) = match event {
+        Event::Note(note) => (vec![note.pitch()], note.tie(), note.articulations()),
+        Event::Chord(chord) => (chord.pitches().to_vec(), chord.tie(), chord.articulations()),
+        Event::Rest(_) => return Ok(format!("{}r", recip)),
+    };
+    let mut tokens = Vec::with_capacity(pitches.len());
+    for pitch in pitches.iter() {
+        let mut token = String::new();
+        if tie == Some(Tie::Start) {
+            token.push('[');
+        }
+        token.push_str(&recip);
+        token.push_str(&write_pitch(pitch)?);
+        for articulation in articulations.iter() {
+            token.push_str(match articulation {
+                Articulation::Staccato => "'",
+                Articulation::Staccatissimo => "`",
+                Articulation::Tenuto => "~",
+                Articulation::Accent => "^",
+                Articulation::Marcato => "^^",
+                Articulation::Fermata => ";",
+            });
+        }
+        match tie {
+            Some(Tie::Continue) => token.push('_'),
+            Some(Tie::Stop) => token.push(']'),
+            _ => {}
+        }
+        tokens.push(token);
+    }
+    Ok(tokens.join(" "))
+}
+
+// One staff written as a spine, split into as many columns as it has voices.
+struct SpineWriter<'a> {
+    staff: &'a Staff,
+    part: usize,
+    name: &'a str,
+    voices: usize,
+}
+
+impl<'a> SpineWriter<'a> {
+    fn tokens(&self, token: &str) -> Vec<String> {
+        vec![token.to_string(); self.voices]
+    }
+}
+
+pub fn write(score: &Score) -> Result<String, &'static str> {
+    let mut spines = Vec::new();
+    for (index, part) in score.parts().iter().enumerate().rev() {
+        for staff in part.staves().iter().rev() {
+            let voices = staff
+                .measures()
+                .iter()
+                .map(|measure| measure.voices().len())
+                .max()
+                .unwrap_or(1)
+                .max(1);
+            spines.push(SpineWriter {
+                staff,
+                part: index + 1,
+                name: part.name(),
+                voices,
+            });
+        }
+    }
+    if spines.is_empty() {
+        return Err("score has no staves");
+    }
+
+    let mut out = String::new();
+    if let Some(title) = score.title() {
+        out.push_str(&format!("!!!OTL: {}\n", title));
+    }
+    let record = |out: &mut String, fields: Vec<String>| {
+        out.push_str(&fields.join("\t"));
+        out.push('\n');
+    };
+    let all = |token: &str| -> Vec<String> { spines.iter().map(|_| token.to_string()).collect() };
+    record(&mut out, all("**kern"));
+    record(
+        &mut out,
+        spines
+            .iter()
+            .map(|spine| format!("*part{}", spine.part))
+            .collect(),
+    );
+    if spines.iter().any(|spine| !spine.name.is_empty()) {
+        record(
+            &mut out,
+            spines
+                .iter()
+                .map(|spine| match spine.name {
+                    "" => "*".to_string(),
+                    name => format!("*I\"{}", name),
+                })
+                .collect(),
+        );
+    }
+
+    // Spines with several voices are split one column at a time.
+    let mut columns = vec![1; spines.len()];
+    while let Some(split) = (0..spines.len()).find(|i| columns[*i] < spines[*i].voices) {
+        let mut fields = Vec::new();
+        for (index, count) in columns.iter().enumerate() {
+            fields.resize(fields.len() + *count - 1, "*".to_string());
+            fields.push(if index == split { "*^" } else { "*" }.to_string());
+        }
+        record(&mut out, fields);
+        columns[split] += 1;
+    }
+
+    let measures = spines
+        .iter()
+        .map(|spine| spine.staff.measures().len())
+        .max()
+        .unwrap_or(0);
+    let mut number = 1;
+    for index in 0..measures {
+        let pickup = spines
+            .iter()
+            .all(|spine| match spine.staff.measures().get(index) {
+                Some(measure) => measure.is_pickup(),
+                None => true,
+            });
+        if index > 0 || !pickup {
+            let bar = if index == 0 {
+                format!("={}-", number)
+            } else {
+                format!("={}", number)
+            };
+            let fields = spines.iter().flat_map(|spine| spine.tokens(&bar)).collect();
+            record(&mut out, fields);
+            number += 1;
+        }
+        write_attributes(&mut out, &spines, index)?;
+        write_measure(&mut out, &spines, index)?;
+    }
+
+    record(
+        &mut out,
+        spines.iter().flat_map(|spine| spine.tokens("==")).collect(),
+    );
+    let mut fields = Vec::new();
+    for spine in spines.iter() {
+        if spine.voices > 1 {
+            fields.extend(spine.tokens("*v"));
+        } else {
+            fields.push("*".to_string());
+        }
+    }
+    if spines.iter().any(|spine| spine.voices > 1) {
+        record(&mut out, fields);
+    }
+    record(&mut out, all("*-"));
+    Ok(out)
+}
+
+fn write_attributes(
+    out: &mut String,
+    spines: &[SpineWriter],
+    index: usize,
+) -> Result<(), &'static str> {
+    let mut records: Vec<Vec<String>> = vec![Vec::new(); 3];
+    let mut used = [false; 3];
+    for spine in spines.iter() {
+        let measure = spine.staff.measures().get(index);
+        let first = index == 0;
+        let changed = |present: bool| first || present;
+        let values = [
+            match measure {
+                Some(measure) if changed(measure.clef().is_some()) => {
+                    Some(write_clef(&spine.staff.clef_at(index))?)
+                }
+                _ => None,
+            },
+            match measure {
+                Some(measure) if changed(measure.key_signature().is_some()) => {
+                    Some(write_key_signature(&spine.staff.key_signature_at(index))?)
+                }
+                _ => None,
+            },
+            match measure {
+                Some(measure) if changed(measure.time_signature().is_some()) => {
+                    Some(format!("*M{}", spine.staff.time_signature_at(index)))
+                }
+                _ => None,
+            },
+        ];
+        for (n, value) in values.iter().enumerate() {
+            used[n] |= value.is_some();
+            records[n].extend(spine.tokens(value.as_deref().unwrap_or("*")));
+        }
+    }
+    for (fields, used) in records.into_iter().zip(used.iter()) {
+        if *used {
+            out.push_str(&fields.join("\t"));
+            out.push('\n');
+        }
+    }
+    Ok(())
+}
+
+// Writes a record for every onset in the measure, with null tokens in columns
+// where nothing starts.
+fn write_measure(
+    out: &mut String,
+    spines: &[SpineWriter],
+    index: usize,
+) -> Result<(), &'static str> {
+    let mut columns: Vec<Vec<(Fraction, &Event)>> = Vec::new();
+    for spine in spines.iter() {
+        let voices = spine
+            .staff
+            .measures()
+            .get(index)
+            .map_or(&[][..], Measure::voices);
+        for voice in 0..spine.voices {
+            columns.push(match voices.get(voice) {
+                Some(voice) => voice.offsets().into_iter().zip(voice.events()).collect(),
+                None => Vec::new(),
+            });
+        }
+    }
+
+    let mut onsets: Vec<Fraction> = columns
+        .iter()
+        .flat_map(|column| column.iter().map(|(offset, _)| *offset))
+        .collect();
+    onsets.sort();
+    onsets.dedup();
+    for onset in onsets {
+        let mut fields = Vec::with_capacity(columns.len());
+        for column in columns.iter() {
+            fields.push(match column.iter().find(|(offset, _)| *offset == onset) {
+                Some((_, event)) => write_event(event)?,
+                None => ".".to_string(),
+            });
+        }
+        out.push_str(&fields.join("\t"));
+        out.push('\n');
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::music::pitch::*;
+    use crate::music::Tuplet;
+
+    const KERN: &str = "!!!OTL: Chorale
+**kern\t**kern\t**dynam
+*part1\t*part1\t*
+*I\"Piano\t*I\"Piano\t*
+*clefF4\t*clefG2\t*
+*k[f#]\t*k[f#]\t*
+*M3/4\t*M3/4\t*
+4GG\t4d'\tp
+=1\t=1\t=1
+*\t*^\t*
+2.G\t[2bL\t4g\t.
+.\t.\t4a\t.
+.\t4b]J\t4cc#\t.
+=2\t=2\t=2\t=2
+*\t*v\t*v\t*
+4r\t12eL\t.
+.\t12f#\t.
+.\t12gJ\t.
+4C 4E 4G\t4an\t.
+4D\t4dd;\t.
+==\t==\t==
+*-\t*-\t*-
+";
+
+    #[test]
+    fn test_read() {
+        let score = read(KERN).unwrap();
+        assert_eq!(Some("Chorale"), score.title());
+        assert_eq!(Ok(()), score.validate());
+        assert_eq!(1, score.parts().len());
+        let part = &score.parts()[0];
+        assert_eq!("Piano", part.name());
+        assert_eq!(2, part.staves().len());
+
+        let upper = part.staves()[0].measures();
+        let lower = part.staves()[1].measures();
+        assert_eq!(3, upper.len());
+        assert!(upper[0].is_pickup());
+        assert!(!upper[1].is_pickup());
+        assert_eq!(Some(&TimeSignature::new(3, 4)), upper[0].time_signature());
+        assert_eq!(Some(KeySignature::new(1)), upper[0].key_signature());
+        assert_eq!(Some(Clef::BASS), lower[0].clef());
+        assert_eq!(
+            vec![Event::from(
+                Note::new(D4, Duration::new(4)).with_articulation(Articulation::Staccato)
+            )],
+            upper[0].voices()[0].events()
+        );
+
+        assert_eq!(2, upper[1].voices().len());
+        assert_eq!(
+            vec![
+                Event::from(Note::new(B4, Duration::new(2)).with_tie(Tie::Start)),
+                Note::new(B4, Duration::new(4)).with_tie(Tie::Stop).into(),
+            ],
+            upper[1].voices()[0].events()
+        );
+        assert_eq!(
+            vec![
+                Event::from(Note::new(G4, Duration::new(4))),
+                Note::new(A4, Duration::new(4)).into(),
+                Note::new(Cs5, Duration::new(4)).into(),
+            ],
+            upper[1].voices()[1].events()
+        );
+
+        let triplet = Tuplet::new(3, 2);
+        assert_eq!(1, upper[2].voices().len());
+        assert_eq!(
+            vec![
+                Event::from(Note::new(E4, Duration::new(8).with_tuplet(triplet))),
+                Note::new(Fs4, Duration::new(8).with_tuplet(triplet)).into(),
+                Note::new(G4, Duration::new(8).with_tuplet(triplet)).into(),
+                Note::new(A4, Duration::new(4)).into(),
+                Note::new(D5, Duration::new(4))
+                    .with_articulation(Articulation::Fermata)
+                    .into(),
+            ],
+            upper[2].voices()[0].events()
+        );
+        assert_eq!(
+            vec![
+                Event::from(Rest::new(Duration::new(4))),
+                ChordNote::new(vec![C3, E3, G3], Duration::new(4)).into(),
+                Note::new(D3, Duration::new(4)).into(),
+            ],
+            lower[2].voices()[0].events()
+        );
+        assert_eq!(
+            vec![Event::from(Note::new(G3, Duration::new_with_dots(2, 1)))],
+            lower[1].voices()[0].events()
+        );
+    }
+
+    #[test]
+    fn test_recip() {
+        assert_eq!(Ok(Duration::new(4)), recip("4"));
+        assert_eq!(Ok(Duration::new_with_dots(8, 2)), recip("8.."));
+        assert_eq!(
+            Ok(Duration::new(8).with_tuplet(Tuplet::new(3, 2))),
+            recip("12")
+        );
+        assert_eq!(
+            Ok(Duration::new(16).with_tuplet(Tuplet::new(5, 4))),
+            recip("20")
+        );
+        assert_eq!(
+            Ok(Duration::new(1).with_tuplet(Tuplet::new(3, 2))),
+            recip("3%2")
+        );
+        assert!(recip("0").is_err());
+        for recip in ["4", "8..", "12", "20", "3%2", "6."].iter() {
+            assert_eq!(*recip, write_recip(&super::recip(recip).unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_pitch() {
+        assert_eq!(Ok(C4), pitch("c"));
+        assert_eq!(Ok(Cs5), pitch("cc#"));
+        assert_eq!(Ok(Bb2), pitch("BB-"));
+        assert_eq!(Ok(C3), pitch("Cn"));
+        assert!(pitch("c+").is_err());
+        assert_eq!(Ok(B9), pitch("bbbbbb"));
+        assert_eq!(Err("octave out of range"), pitch("cccccccc"));
+        assert_eq!(Err("octave out of range"), pitch("CCCCCC"));
+        assert_eq!(Ok("cc#".to_string()), write_pitch(&Cs5));
+        assert_eq!(Ok("BB-".to_string()), write_pitch(&Bb2));
+    }
+
+    #[test]
+    fn test_read_errors() {
+        assert!(read("4c\n").is_err());
+        assert!(read("**kern\n4c\t4d\n").is_err());
+        assert!(read("**kern\n4x\n").is_err());
+        assert!(read("**text\nhello\n").is_err());
+        assert_eq!(
+            Err("duration out of range"),
+            read("**kern\n1%2147483647c\n*-\n")
+        );
+        assert_eq!(
+            Err("duration out of range"),
+            read("**kern\n2147483647c\n*-\n")
+        );
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        let score = read(KERN).unwrap();
+        let written = write(&score).unwrap();
+        assert_eq!(score, read(&written).unwrap());
+        assert!(written.starts_with("!!!OTL: Chorale\n**kern\t**kern\n*part1\t*part1\n"));
+        assert!(written.contains("*\t*^\n*clefF4\t*clefG2\t*clefG2\n"));
+        assert!(written.contains("*M3/4\t*M3/4\t*M3/4\n4GG\t4d'\t.\n=1\t=1\t=1\n"));
+        assert!(written.contains("2.G\t[2b\t4g\n"));
+        assert!(written.contains("4C 4E 4G\t4a\t.\n"));
+        assert!(written.ends_with("==\t==\t==\n*\t*v\t*v\n*-\t*-\n"));
+    }
+}
@@ -0,0 +1,529 @@
+use crate::math::Fraction;
+use crate::music::{
+    Articulation, Clef, ClefSign, Duration, Dynamic, Event, KeySignature, Mode, Pitch, Score,
+    Staff, Tie, TimeSignature, A, B, C, C4, D, E, F, G, NATURAL,
+};
+use std::convert::TryFrom;
+
+const VERSION: &str = "2.24.0";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PitchMode {
+    Absolute,
+    Relative,
+}
+
+pub fn write(score: &Score, mode: PitchMode) -> Result<String, &'static str> {
+    let mut out = format!("\\version \"{}\"\n", VERSION);
+    if let Some(title) = score.title() {
+        out.push_str(&format!("\\header {{\n  title = {}\n}}\n", string(title)));
+    }
+    out.push_str("\\score {\n  <<\n");
+    for part in score.parts() {
+        let with = if part.name().is_empty() {
+            String::new()
+        } else {
+            format!(" \\with {{ instrumentName = {} }}", string(part.name()))
+        };
+        match part.staves() {
+            [staff] => out.push_str(&write_staff(staff, &with, mode, 2)?),
+            staves => {
+                out.push_str(&format!("    \\new PianoStaff{} <<\n", with));
+                for staff in staves.iter() {
+                    out.push_str(&write_staff(staff, "", mode, 3)?);
+                }
+                out.push_str("    >>\n");
+            }
+        }
+    }
+    out.push_str("  >>\n  \\layout { }\n}\n");
+    Ok(out)
+}
+
+// Writes a sequence of events as a single music expression, without any score around it.
+pub fn write_events(events: &[Event], mode: PitchMode) -> Result<String, &'static str> {
+    let mut writer = VoiceWriter::new(mode);
+    let mut music = Vec::with_capacity(events.len());
+    let mut index = 0;
+    while index < events.len() {
+        index = writer.event_group(events, index, &mut music)?;
+    }
+    Ok(format!("{}{{ {} }}", writer.opening(), music.join(" ")))
+}
+
+fn string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn pitch_name(pitch: &Pitch) -> Result<String, &'static str> {
+    let letter = pitch.name().to_char().to_ascii_lowercase();
+    // Flats on e and a drop the vowel of the suffix, as in `es` and `as`.
+    let vowel = letter == 'e' || letter == 'a';
+    let suffix = match pitch.accidental().cents() {
+        200 => "isis",
+        150 => "isih",
+        100 => "is",
+        50 => "ih",
+        0 => "",
+        -50 => "eh",
+        -100 if vowel => "s",
+        -100 => "es",
+        -150 if vowel => "seh",
+        -150 => "eseh",
+        -200 if vowel => "ses",
+        -200 => "eses",
+        _ => return Err("accidental cannot be written in LilyPond"),
+    };
+    Ok(format!("{}{}", letter, suffix))
+}
+
+fn octave_marks(octaves: i32) -> String {
+    if octaves >= 0 {
+        "'".repeat(octaves as usize)
+    } else {
+        ",".repeat(octaves.unsigned_abs() as usize)
+    }
+}
+
+fn steps(pitch: &Pitch) -> i32 {
+    pitch.octave() * 7 + pitch.name().index()
+}
+
+fn duration(duration: &Duration) -> String {
+    format!(
+        "{}{}",
+        duration.denominator(),
+        ".".repeat(duration.dots() as usize)
+    )
+}
+
+// A length as a LilyPond duration, scaled when no single duration matches it.
+fn length(length: Fraction) -> String {
+    match Duration::try_from(length) {
+        Ok(value) if value.tuplet().is_none() => duration(&value),
+        _ => {
+            let length = length.to_irreducible();
+            format!("1*{}/{}", length.numerator(), length.denominator())
+        }
+    }
+}
+
+fn key(signature: &KeySignature) -> Result<String, &'static str> {
+    if let Some(key) = signature.key(Mode::Major) {
+        let tonic = Pitch::new_with_accidental(key.name(), key.accidental(), 4);
+        return Ok(format!("\\key {} \\major", pitch_name(&tonic)?));
+    }
+    // Other signatures list their alterations in whole tones, by step from c.
+    let mut alterations = Vec::new();
+    for name in [C, D, E, F, G, A, B].iter() {
+        let accidental = signature.accidental(*name);
+        if accidental != NATURAL {
+            let alteration = Fraction::new(accidental.cents(), 200).to_irreducible();
+            alterations.push(format!("({} . {})", name.index(), alteration));
+        }
+    }
+    Ok(format!(
+        "\\set Staff.keyAlterations = #`({})",
+        alterations.join(" ")
+    ))
+}
+
+fn time(meter: &TimeSignature) -> String {
+    if meter.is_additive() {
+        let groups: Vec<String> = meter.groups().iter().map(u16::to_string).collect();
+        format!(
+            "\\time {} {}/{}",
+            groups.join(","),
+            meter.numerator(),
+            meter.denominator()
+        )
+    } else {
+        format!("\\time {}/{}", meter.numerator(), meter.denominator())
+    }
+}
+
+fn clef(clef: &Clef) -> Result<String, &'static str> {
+    let name = match (clef.sign(), clef.line()) {
+        (ClefSign::G, 1) => "french",
+        (ClefSign::G, 2) => "treble",
+        (ClefSign::C, 1) => "soprano",
+        (ClefSign::C, 2) => "mezzosoprano",
+        (ClefSign::C, 3) => "alto",
+        (ClefSign::C, 4) => "tenor",
+        (ClefSign::C, 5) => "baritone",
+        (ClefSign::F, 3) => "varbaritone",
+        (ClefSign::F, 4) => "bass",
+        (ClefSign::F, 5) => "subbass",
+        (ClefSign::Percussion, _) => "percussion",
+        (ClefSign::Tab, _) => "tab",
+        _ => return Err("clef cannot be written in LilyPond"),
+    };
+    Ok(match clef.octave_change() {
+        0 => format!("\\clef {}", name),
+        octaves => {
+            let mark = if octaves > 0 { '^' } else { '_' };
+            let interval = 7 * octaves.abs() + 1;
+            format!("\\clef \"{}{}{}\"", name, mark, interval)
+        }
+    })
+}
+
+fn articulation(articulation: &Articulation) -> &'static str {
+    match articulation {
+        Articulation::Staccato => "-.",
+        Articulation::Staccatissimo => "-!",
+        Articulation::Tenuto => "--",
+        Articulation::Accent => "->",
+        Articulation::Marcato => "-^",
+        Articulation::Fermata => "\\fermata",
+    }
+}
+
+fn dynamics(dynamics: &Dynamic) -> &'static str {
+    match dynamics {
+        Dynamic::Ppp => "\\ppp",
+        Dynamic::Pp => "\\pp",
+        Dynamic::P => "\\p",
+        Dynamic::Mp => "\\mp",
+        Dynamic::Mf => "\\mf",
+        Dynamic::F => "\\f",
+        Dynamic::Ff => "\\ff",
+        Dynamic::Fff => "\\fff",
+    }
+}
+
+// Tracks the previous pitch in relative mode, where each note is written
+// relative to the one before it.
+struct VoiceWriter {
+    mode: PitchMode,
+    previous: Pitch,
+}
+
+impl VoiceWriter {
+    fn new(mode: PitchMode) -> VoiceWriter {
+        VoiceWriter { mode, previous: C4 }
+    }
+
+    fn opening(&self) -> &'static str {
+        match self.mode {
+            PitchMode::Absolute => "",
+            PitchMode::Relative => "\\relative c' ",
+        }
+    }
+
+    // In relative mode a note is placed within a fourth of the previous one,
+    // counting note names only; octave marks move it from there.
+    fn pitch(&mut self, pitch: &Pitch) -> Result<String, &'static str> {
+        let name = pitch_name(pitch)?;
+        let octaves = match self.mode {
+            PitchMode::Absolute => pitch.octave() - 3,
+            PitchMode::Relative => {
+                let distance = steps(pitch) - steps(&self.previous);
+                self.previous = *pitch;
+                (distance + 3).div_euclid(7)
+            }
+        };
+        Ok(format!("{}{}", name, octave_marks(octaves)))
+    }
+
+    fn event(&mut self, event: &Event) -> Result<String, &'static str> {
+        let (mut text, tie, articulations, dynamic) = match event {
+            Event::Note(note) => (
+                self.pitch(&note.pitch())?,
+                note.tie(),
+                note.articulations(),
+                note.dynamics(),
+            ),
+            Event::Chord(chord) => {
+                let first = chord.pitches().first().copied();
+                let mut pitches = Vec::with_capacity(chord.pitches().len());
+                for pitch in chord.pitches().iter() {
+                    pitches.push(self.pitch(pitch)?);
+                }
+                // The note after a chord is relative to the chord's first note.
+                if let Some(first) = first {
+                    self.previous = first;
+                }
+                (
+                    format!("<{}>", pitches.join(" ")),
+                    chord.tie(),
+                    chord.articulations(),
+                    chord.dynamics(),
+                )
+            }
+            Event::Rest(_) => ("r".to_string(), None, &[][..], None),
+        };
+        text.push_str(&duration(&event.duration()));
+        if let Some(Tie::Start) | Some(Tie::Continue) = tie {
+            text.push('~');
+        }
+        for value in articulations.iter() {
+            text.push_str(articulation(value));
+        }
+        if let Some(value) = dynamic {
+            text.push_str(dynamics(&value));
+        }
+        Ok(text)
+    }
+
+    // Writes the event at `index`, or the whole tuplet starting there, and
+    // returns the index after it.
+    fn event_group(
+        &mut self,
+        events: &[Event],
+        index: usize,
+        music: &mut Vec<String>,
+    ) -> Result<usize, &'static str> {
+        match events[index].duration().tuplet() {
+            Some(tuplet) => {
+                let count = events[index..]
+                    .iter()
+                    .take_while(|event| event.duration().tuplet() == Some(tuplet))
+                    .count();
+                let mut inner = Vec::with_capacity(count);
+                for event in events[index..index + count].iter() {
+                    inner.push(self.event(event)?);
+                }
+                music.push(format!(
+                    "\\tuplet {}/{} {{ {} }}",
+                    tuplet.actual(),
+                    tuplet.normal(),
+                    inner.join(" ")
+                ));
+                Ok(index + count)
+            }
+            None => {
+                music.push(self.event(&events[index])?);
+                Ok(index + 1)
+            }
+        }
+    }
+}
+
+// Each voice is written as one stream through the whole staff, so that relative
+// pitches never depend on how simultaneous voices are ordered. Measures where
+// a voice is silent are filled with spacer rests.
+fn write_staff(
+    staff: &Staff,
+    with: &str,
+    mode: PitchMode,
+    depth: usize,
+) -> Result<String, &'static str> {
+    let indent = "  ".repeat(depth);
+    let measures = staff.measures();
+    let count = measures
+        .iter()
+        .map(|measure| measure.voices().len())
+        .max()
+        .unwrap_or(1)
+        .max(1);
+
+    let inner = if count > 1 { "    " } else { "  " };
+    let mut streams = Vec::with_capacity(count);
+    for voice in 0..count {
+        let mut writer = VoiceWriter::new(mode);
+        let mut lines = Vec::with_capacity(measures.len());
+        for (index, measure) in measures.iter().enumerate() {
+            let meter = staff.time_signature_at(index);
+            let mut music = Vec::new();
+            if voice == 0 {
+                if index == 0 || measure.key_signature().is_some() {
+                    music.push(key(&staff.key_signature_at(index))?);
+                }
+                if index == 0 || measure.time_signature().is_some() {
+                    music.push(time(&meter));
+                }
+                if index == 0 || measure.clef().is_some() {
+                    music.push(clef(&staff.clef_at(index))?);
+                }
+                if measure.is_pickup() {
+                    music.push(format!("\\partial {}", length(measure.length(&meter))));
+                }
+            }
+            match measure.voices().get(voice) {
+                Some(contents) if !contents.events().is_empty() => {
+                    let events = contents.events();
+                    let mut event = 0;
+                    while event < events.len() {
+                        event = writer.event_group(events, event, &mut music)?;
+                    }
+                }
+                _ => music.push(format!("s{}", length(measure.length(&meter)))),
+            }
+            if voice == 0 && index + 1 == measures.len() {
+                music.push("\\bar \"|.\"".to_string());
+            }
+            lines.push(format!("{}{}{} |\n", indent, inner, music.join(" ")));
+        }
+        streams.push((writer.opening(), lines.concat()));
+    }
+
+    let mut out = format!("{}\\new Staff{} ", indent, with);
+    match streams.as_slice() {
+        [(opening, lines)] => {
+            out.push_str(&format!("{}{{\n{}{}}}\n", opening, lines, indent));
+        }
+        _ => {
+            out.push_str("<<\n");
+            for (index, (opening, lines)) in streams.iter().enumerate() {
+                let command = match index {
+                    0 => " \\voiceOne",
+                    1 => " \\voiceTwo",
+                    2 => " \\voiceThree",
+                    3 => " \\voiceFour",
+                    _ => "",
+                };
+                out.push_str(&format!(
+                    "{}  \\new Voice {{{} {}{{\n{}{}  }} }}\n",
+                    indent, command, opening, lines, indent
+                ));
+            }
+            out.push_str(&format!("{}>>\n", indent));
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::music::pitch::*;
+    use crate::music::{
+        Accidental, ChordNote, Key, Measure, Note, Part, Rest, Tuplet, Voice, HALF_FLAT, SHARP,
+    };
+
+    #[test]
+    fn test_pitch_name() {
+        assert_eq!(Ok("c".to_string()), pitch_name(&C4));
+        assert_eq!(Ok("fis".to_string()), pitch_name(&Fs4));
+        assert_eq!(Ok("bes".to_string()), pitch_name(&Bb4));
+        assert_eq!(Ok("es".to_string()), pitch_name(&Eb4));
+        assert_eq!(Ok("as".to_string()), pitch_name(&Ab4));
+        assert_eq!(
+            Ok("eeh".to_string()),
+            pitch_name(&Pitch::new_with_accidental(E, HALF_FLAT, 4))
+        );
+        assert_eq!(
+            Ok("ases".to_string()),
+            pitch_name(&Pitch::new_with_accidental(A, Accidental::new(-2), 4))
+        );
+    }
+
+    #[test]
+    fn test_write_events() {
+        let triplet = Tuplet::new(3, 2);
+        let events: Vec<Event> = vec![
+            Note::new(C4, Duration::new(4))
+                .with_articulation(Articulation::Staccato)
+                .with_dynamics(Dynamic::Mf)
+                .into(),
+            Note::new(G4, Duration::new_with_dots(8, 1))
+                .with_tie(Tie::Start)
+                .into(),
+            Note::new(G4, Duration::new(16)).with_tie(Tie::Stop).into(),
+            ChordNote::new(vec![E3, G3, C4], Duration::new(4)).into(),
+            Rest::new(Duration::new(8).with_tuplet(triplet)).into(),
+            Note::new(Bb4, Duration::new(8).with_tuplet(triplet)).into(),
+            Note::new(A5, Duration::new(8).with_tuplet(triplet)).into(),
+        ];
+        assert_eq!(
+            Ok("{ c'4-.\\mf g'8.~ g'16 <e g c'>4 \\tuplet 3/2 { r8 bes'8 a''8 } }".to_string()),
+            write_events(&events, PitchMode::Absolute)
+        );
+        assert_eq!(
+            Ok(
+                "\\relative c' { c4-.\\mf g'8.~ g16 <e, g c>4 \\tuplet 3/2 { r8 bes''8 a'8 } }"
+                    .to_string()
+            ),
+            write_events(&events, PitchMode::Relative)
+        );
+    }
+
+    #[test]
+    fn test_write_commands() {
+        assert_eq!(
+            Ok("\\key d \\major".to_string()),
+            key(&KeySignature::new(2))
+        );
+        assert_eq!(
+            Ok("\\key bes \\major".to_string()),
+            key(&Key::new(G, NATURAL, Mode::Minor).signature())
+        );
+        assert_eq!(
+            Ok("\\set Staff.keyAlterations = #`((3 . 1/2) (6 . -1/4))".to_string()),
+            key(&KeySignature::from_accidentals(&[
+                (F, SHARP),
+                (B, HALF_FLAT)
+            ]))
+        );
+        assert_eq!("\\time 6/8", time(&TimeSignature::new(6, 8)));
+        assert_eq!(
+            "\\time 3,3,2 8/8",
+            time(&TimeSignature::additive(&[3, 3, 2], 8))
+        );
+        assert_eq!(Ok("\\clef bass".to_string()), clef(&Clef::BASS));
+        assert_eq!(
+            Ok("\\clef \"treble_8\"".to_string()),
+            clef(&Clef::TREBLE_8VB)
+        );
+        assert!(clef(&Clef::new(ClefSign::F, 1, 0)).is_err());
+        assert_eq!("4.", length(Fraction::new(3, 8)));
+        assert_eq!("1*5/8", length(Fraction::new(5, 8)));
+    }
+
+    #[test]
+    fn test_write_score() {
+        let quarter = |pitch| Event::from(Note::new(pitch, Duration::new(4)));
+        let upper = Staff::new(vec![
+            Measure::new(vec![Voice::new(vec![quarter(G4)])])
+                .with_time_signature(TimeSignature::new(3, 4))
+                .with_pickup(),
+            Measure::new(vec![
+                Voice::new(vec![quarter(C5), quarter(B4), quarter(C5)]),
+                Voice::new(vec![Note::new(E4, Duration::new_with_dots(2, 1)).into()]),
+            ]),
+        ]);
+        let lower = Staff::new(vec![
+            Measure::new(vec![Voice::new(vec![Rest::new(Duration::new(4)).into()])])
+                .with_time_signature(TimeSignature::new(3, 4))
+                .with_clef(Clef::BASS)
+                .with_pickup(),
+            Measure::new(vec![Voice::new(vec![Note::new(
+                C3,
+                Duration::new_with_dots(2, 1),
+            )
+            .into()])]),
+        ]);
+        let score =
+            Score::new(vec![Part::new("Piano \"A\"", vec![upper, lower])]).with_title("Minuet");
+
+        let expected = r#"\version "2.24.0"
+\header {
+  title = "Minuet"
+}
+\score {
+  <<
+    \new PianoStaff \with { instrumentName = "Piano \"A\"" } <<
+      \new Staff <<
+        \new Voice { \voiceOne \relative c' {
+          \key c \major \time 3/4 \clef treble \partial 4 g'4 |
+          c4 b4 c4 \bar "|." |
+        } }
+        \new Voice { \voiceTwo \relative c' {
+          s4 |
+          e2. |
+        } }
+      >>
+      \new Staff \relative c' {
+        \key c \major \time 3/4 \clef bass \partial 4 r4 |
+        c,2. \bar "|." |
+      }
+    >>
+  >>
+  \layout { }
+}
+"#;
+        let written = write(&score, PitchMode::Relative).unwrap();
+        assert_eq!(expected, written);
+    }
+}
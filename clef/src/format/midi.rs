@@ -0,0 +1,1270 @@
+use crate::math::Fraction;
+use crate::music::{
+    ChordNote, Clef, Duration, Event, Key, KeySignature, Measure, Mode, Note, Part, Pitch, Rest,
+    Score, Spelling, Staff, Tie, TimeSignature, Voice,
+};
+use contracts::requires;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MidiNote {
+    pitch: Pitch,
+    start: Fraction,
+    length: Fraction,
+    velocity: u8,
+    channel: u8,
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct MidiTrack {
+    name: Option<String>,
+    channel: Option<u8>,
+    program: Option<u8>,
+    notes: Vec<MidiNote>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct MidiFile {
+    format: u16,
+    ppq: u16,
+    tracks: Vec<MidiTrack>,
+    tempos: Vec<(Fraction, u32)>,
+    time_signatures: Vec<(Fraction, TimeSignature)>,
+    keys: Vec<(Fraction, Key)>,
+}
+
+impl MidiNote {
+    #[requires((1..=127).contains(&velocity), "velocity must be in the range 1 to 127")]
+    #[requires(channel < 16, "channel must be less than 16")]
+    pub fn new(
+        pitch: Pitch,
+        start: Fraction,
+        length: Fraction,
+        velocity: u8,
+        channel: u8,
+    ) -> MidiNote {
+        MidiNote {
+            pitch,
+            start,
+            length,
+            velocity,
+            channel,
+        }
+    }
+
+    pub fn pitch(&self) -> Pitch {
+        self.pitch
+    }
+
+    pub fn start(&self) -> Fraction {
+        self.start
+    }
+
+    pub fn length(&self) -> Fraction {
+        self.length
+    }
+
+    pub fn end(&self) -> Fraction {
+        self.start + self.length
+    }
+
+    pub fn velocity(&self) -> u8 {
+        self.velocity
+    }
+
+    pub fn channel(&self) -> u8 {
+        self.channel
+    }
+
+    pub fn quantize(&self, grid: Fraction) -> MidiNote {
+        let start = snap(self.start, grid);
+        let end = std::cmp::max(snap(self.end(), grid), start + grid);
+        MidiNote {
+            start,
+            length: end - start,
+            ..*self
+        }
+    }
+}
+
+impl MidiTrack {
+    pub fn new() -> MidiTrack {
+        Default::default()
+    }
+
+    pub fn with_name(self, name: &str) -> MidiTrack {
+        MidiTrack {
+            name: Some(name.to_string()),
+            ..self
+        }
+    }
+
+    #[requires(channel < 16, "channel must be less than 16")]
+    pub fn with_channel(self, channel: u8) -> MidiTrack {
+        MidiTrack {
+            channel: Some(channel),
+            ..self
+        }
+    }
+
+    #[requires(program <= 127, "program must be no more than 127")]
+    pub fn with_program(self, program: u8) -> MidiTrack {
+        MidiTrack {
+            program: Some(program),
+            ..self
+        }
+    }
+
+    pub fn push(&mut self, note: MidiNote) {
+        self.notes.push(note);
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn channel(&self) -> Option<u8> {
+        self.channel
+    }
+
+    pub fn program(&self) -> Option<u8> {
+        self.program
+    }
+
+    pub fn notes(&self) -> &[MidiNote] {
+        &self.notes
+    }
+}
+
+impl MidiFile {
+    #[requires(ppq > 0 && ppq < 0x8000, "ppq must be between 1 and 32767")]
+    pub fn new(ppq: u16) -> MidiFile {
+        MidiFile {
+            format: 1,
+            ppq,
+            tracks: Vec::new(),
+            tempos: Vec::new(),
+            time_signatures: Vec::new(),
+            keys: Vec::new(),
+        }
+    }
+
+    #[requires(microseconds > 0 && microseconds < 1 << 24, "tempo must fit in 24 bits")]
+    pub fn with_tempo(mut self, offset: Fraction, microseconds: u32) -> MidiFile {
+        self.tempos.push((offset, microseconds));
+        self.tempos.sort_by_key(|(offset, _)| *offset);
+        self
+    }
+
+    pub fn with_time_signature(mut self, offset: Fraction, meter: TimeSignature) -> MidiFile {
+        self.time_signatures.push((offset, meter));
+        self.time_signatures.sort_by_key(|(offset, _)| *offset);
+        self
+    }
+
+    pub fn with_key(mut self, offset: Fraction, key: Key) -> MidiFile {
+        self.keys.push((offset, key));
+        self.keys.sort_by_key(|(offset, _)| *offset);
+        self
+    }
+
+    pub fn push(&mut self, track: MidiTrack) {
+        self.tracks.push(track);
+    }
+
+    pub fn from_score(score: &Score, ppq: u16) -> MidiFile {
+        let mut file = MidiFile::new(ppq);
+        if let Some(staff) = score.parts().first().and_then(|p| p.staves().first()) {
+            for (index, measure) in staff.measures().iter().enumerate() {
+                let offset = staff.measure_offset(index);
+                if let Some(meter) = measure.time_signature() {
+                    file = file.with_time_signature(offset, meter.clone());
+                }
+                let mode = measure.mode().unwrap_or(Mode::Major);
+                if let Some(key) = measure.key_signature().and_then(|s| s.key(mode)) {
+                    file = file.with_key(offset, key);
+                }
+            }
+        }
+
+        for (index, part) in score.parts().iter().enumerate() {
+            let channel = (index % 16) as u8;
+            let mut track = MidiTrack::new()
+                .with_name(part.name())
+                .with_channel(channel);
+            for staff in part.staves().iter() {
+                // Indices of notes whose tie is still waiting for a continuation.
+                let mut tied: Vec<usize> = Vec::new();
+                for (index, measure) in staff.measures().iter().enumerate() {
+                    let offset = staff.measure_offset(index);
+                    for voice in measure.voices().iter() {
+                        for (event, start) in voice.events().iter().zip(voice.offsets()) {
+                            let (tie, velocity) = match event {
+                                Event::Note(note) => (note.tie(), note.velocity()),
+                                Event::Chord(chord) => (chord.tie(), chord.velocity()),
+                                Event::Rest(_) => continue,
+                            };
+                            let start = offset + start;
+                            for pitch in event.pitches() {
+                                let continued = match tie {
+                                    Some(Tie::Stop) | Some(Tie::Continue) => {
+                                        tied.iter().position(|i| {
+                                            let note = &track.notes[*i];
+                                            note.pitch == pitch && note.end() == start
+                                        })
+                                    }
+                                    _ => None,
+                                };
+                                let index = match continued {
+                                    Some(position) => {
+                                        let index = tied.remove(position);
+                                        track.notes[index].length += event.length();
+                                        index
+                                    }
+                                    None => {
+                                        let velocity = std::cmp::max(velocity, 1);
+                                        let note = MidiNote::new(
+                                            pitch,
+                                            start,
+                                            event.length(),
+                                            velocity,
+                                            channel,
+                                        );
+                                        track.push(note);
+                                        track.notes.len() - 1
+                                    }
+                                };
+                                if let Some(Tie::Start) | Some(Tie::Continue) = tie {
+                                    tied.push(index);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            file.push(track);
+        }
+        file
+    }
+
+    pub fn write(&self) -> Result<Vec<u8>, &'static str> {
+        let mut bytes = b"MThd".to_vec();
+        bytes.extend(&6u32.to_be_bytes());
+        bytes.extend(&1u16.to_be_bytes());
+        bytes.extend(&(self.tracks.len() as u16 + 1).to_be_bytes());
+        bytes.extend(&self.ppq.to_be_bytes());
+
+        let mut conductor: Vec<(u32, Vec<u8>)> = Vec::new();
+        for (offset, tempo) in self.tempos.iter() {
+            let mut data = vec![0xFF, 0x51, 0x03];
+            data.extend(&tempo.to_be_bytes()[1..]);
+            conductor.push((self.ticks(*offset)?, data));
+        }
+        for (offset, meter) in self.time_signatures.iter() {
+            if meter.is_irrational() || meter.numerator() > 255 {
+                return Err("time signature cannot be represented in MIDI");
+            }
+            let exponent = meter.denominator().trailing_zeros() as u8;
+            let data = vec![0xFF, 0x58, 0x04, meter.numerator() as u8, exponent, 24, 8];
+            conductor.push((self.ticks(*offset)?, data));
+        }
+        for (offset, key) in self.keys.iter() {
+            let fifths = key.fifths();
+            if !(-7..=7).contains(&fifths) {
+                return Err("key signature cannot be represented in MIDI");
+            }
+            let minor = (key.mode() == Mode::Minor) as u8;
+            let data = vec![0xFF, 0x59, 0x02, fifths as i8 as u8, minor];
+            conductor.push((self.ticks(*offset)?, data));
+        }
+        write_track(&mut bytes, conductor);
+
+        for track in self.tracks.iter() {
+            let mut events: Vec<(u32, Vec<u8>)> = Vec::new();
+            if let Some(name) = track.name() {
+                let mut data = vec![0xFF, 0x03];
+                write_varlen(&mut data, name.len() as u32);
+                data.extend(name.as_bytes());
+                events.push((0, data));
+            }
+            if let Some(program) = track.program {
+                let mut channels: Vec<u8> = track
+                    .notes
+                    .iter()
+                    .map(|note| track.channel.unwrap_or(note.channel))
+                    .collect();
+                channels.sort_unstable();
+                channels.dedup();
+                if channels.is_empty() {
+                    channels.push(track.channel.unwrap_or(0));
+                }
+                for channel in channels {
+                    events.push((0, vec![0xC0 | channel, program]));
+                }
+            }
+            for note in track.notes.iter() {
+                let number = note
+                    .pitch
+                    .to_midi()
+                    .ok_or("pitch cannot be represented in MIDI")?;
+                let channel = track.channel.unwrap_or(note.channel);
+                let start = self.ticks(note.start)?;
+                let end = std::cmp::max(self.ticks(note.end())?, start + 1);
+                events.push((start, vec![0x90 | channel, number, note.velocity]));
+                events.push((end, vec![0x80 | channel, number, 0]));
+            }
+            write_track(&mut bytes, events);
+        }
+        Ok(bytes)
+    }
+
+    fn ticks(&self, offset: Fraction) -> Result<u32, &'static str> {
+        if offset < Fraction::ZERO {
+            return Err("offset must not be negative");
+        }
+        let ticks = snap(offset * (self.ppq as i32 * 4), Fraction::ONE);
+        Ok(ticks.numerator() as u32)
+    }
+
+    pub fn read(bytes: &[u8]) -> Result<MidiFile, &'static str> {
+        Self::parse(bytes, false)
+    }
+
+    // Accepts running status across meta and sysex events, truncated chunks,
+    // missing end-of-track events and notes left sounding at the end of a track.
+    pub fn read_tolerant(bytes: &[u8]) -> Result<MidiFile, &'static str> {
+        Self::parse(bytes, true)
+    }
+
+    pub fn format(&self) -> u16 {
+        self.format
+    }
+
+    pub fn ppq(&self) -> u16 {
+        self.ppq
+    }
+
+    pub fn tracks(&self) -> &[MidiTrack] {
+        &self.tracks
+    }
+
+    pub fn tempos(&self) -> &[(Fraction, u32)] {
+        &self.tempos
+    }
+
+    pub fn time_signatures(&self) -> &[(Fraction, TimeSignature)] {
+        &self.time_signatures
+    }
+
+    pub fn keys(&self) -> &[(Fraction, Key)] {
+        &self.keys
+    }
+
+    pub fn time_signature_at(&self, offset: Fraction) -> TimeSignature {
+        self.time_signatures
+            .iter()
+            .rev()
+            .find(|(start, _)| *start <= offset)
+            .map(|(_, meter)| meter.clone())
+            .unwrap_or_else(|| TimeSignature::new(4, 4))
+    }
+
+    pub fn key_at(&self, offset: Fraction) -> Option<Key> {
+        self.keys
+            .iter()
+            .rev()
+            .find(|(start, _)| *start <= offset)
+            .map(|(_, key)| *key)
+    }
+
+    pub fn quantize(&self, grid: Fraction) -> MidiFile {
+        let mut file = self.clone();
+        for track in file.tracks.iter_mut() {
+            for note in track.notes.iter_mut() {
+                *note = note.quantize(grid);
+            }
+        }
+        file
+    }
+
+    pub fn to_score(&self, grid: Fraction) -> Result<Score, &'static str> {
+        let file = self.quantize(grid);
+        let end = file
+            .tracks
+            .iter()
+            .flat_map(|track| track.notes.iter().map(MidiNote::end))
+            .max()
+            .unwrap_or(Fraction::ZERO);
+        let bars = file.barlines(end);
+
+        let mut score = Score::default();
+        for (index, track) in file.tracks.iter().enumerate() {
+            if track.notes.is_empty() {
+                continue;
+            }
+            let name = match &track.name {
+                Some(name) => name.clone(),
+                None => format!("Track {}", index + 1),
+            };
+            score.push(Part::new(&name, vec![file.staff(track, &bars)?]));
+        }
+        Ok(score)
+    }
+
+    fn barlines(&self, end: Fraction) -> Vec<(Fraction, TimeSignature)> {
+        let mut bars = Vec::new();
+        let mut start = Fraction::ZERO;
+        loop {
+            let meter = self.time_signature_at(start);
+            let next = start + meter.measure_length();
+            bars.push((start, meter));
+            if next >= end {
+                return bars;
+            }
+            start = next;
+        }
+    }
+
+    fn staff(
+        &self,
+        track: &MidiTrack,
+        bars: &[(Fraction, TimeSignature)],
+    ) -> Result<Staff, &'static str> {
+        let (last_start, last_meter) = bars.last().unwrap();
+        let end = *last_start + last_meter.measure_length();
+
+        let mut notes = track.notes.clone();
+        notes.sort_by_key(|note| note.start);
+
+        // Notes starting together become a chord, cut short at the next onset.
+        let mut segments: Vec<(Fraction, Fraction, Vec<Pitch>, u8)> = Vec::new();
+        let mut time = Fraction::ZERO;
+        let mut i = 0;
+        while i < notes.len() {
+            let start = notes[i].start;
+            let group: Vec<&MidiNote> =
+                notes[i..].iter().take_while(|n| n.start == start).collect();
+            i += group.len();
+            if start > time {
+                segments.push((time, start, Vec::new(), 0));
+            }
+            let mut stop = group.iter().map(|n| n.end()).max().unwrap();
+            if let Some(next) = notes.get(i) {
+                stop = std::cmp::min(stop, next.start);
+            }
+            let mut pitches: Vec<Pitch> = group.iter().map(|n| n.pitch).collect();
+            pitches.sort_by_key(|p| p.to_midi());
+            pitches.dedup();
+            let velocity = group.iter().map(|n| n.velocity).max().unwrap();
+            segments.push((start, stop, pitches, velocity));
+            time = stop;
+        }
+        if time < end {
+            segments.push((time, end, Vec::new(), 0));
+        }
+
+        let mut events: Vec<Vec<Event>> = vec![Vec::new(); bars.len()];
+        for (start, stop, pitches, velocity) in segments.iter() {
+            let mut pieces = Vec::new();
+            for (index, (bar, meter)) in bars.iter().enumerate() {
+                let bar_end = *bar + meter.measure_length();
+                let from = std::cmp::max(*start, *bar);
+                let to = std::cmp::min(*stop, bar_end);
+                if from < to {
+                    for duration in Duration::decompose_at(to - from, meter, from - *bar)? {
+                        pieces.push((index, duration));
+                    }
+                }
+            }
+            let count = pieces.len();
+            for (n, (index, duration)) in pieces.into_iter().enumerate() {
+                let tie = match n {
+                    _ if count == 1 || pitches.is_empty() => None,
+                    0 => Some(Tie::Start),
+                    _ if n == count - 1 => Some(Tie::Stop),
+                    _ => Some(Tie::Continue),
+                };
+                events[index].push(event(pitches, *velocity, duration, tie));
+            }
+        }
+
+        let numbers: Vec<i32> = notes
+            .iter()
+            .filter_map(|n| n.pitch.to_midi())
+            .map(|n| n as i32)
+            .collect();
+        let clef = if !numbers.is_empty() && numbers.iter().sum::<i32>() < 60 * numbers.len() as i32
+        {
+            Clef::BASS
+        } else {
+            Clef::TREBLE
+        };
+
+        let mut staff = Staff::default();
+        let mut previous: Option<(&TimeSignature, KeySignature, Mode)> = None;
+        for ((bar, meter), events) in bars.iter().zip(events) {
+            let key = self.key_at(*bar);
+            let signature = key
+                .map(|key| key.signature())
+                .unwrap_or_else(|| KeySignature::new(0));
+            let mode = key.map_or(Mode::Major, |key| key.mode());
+            let mut measure = Measure::new(vec![Voice::new(events)]);
+            match previous {
+                None => {
+                    measure = measure
+                        .with_time_signature(meter.clone())
+                        .with_key_signature(signature)
+                        .with_mode(mode)
+                        .with_clef(clef)
+                }
+                Some((previous_meter, previous_signature, previous_mode)) => {
+                    if previous_meter != meter {
+                        measure = measure.with_time_signature(meter.clone());
+                    }
+                    if previous_signature != signature || previous_mode != mode {
+                        measure = measure.with_key_signature(signature).with_mode(mode);
+                    }
+                }
+            }
+            previous = Some((meter, signature, mode));
+            staff.push(measure);
+        }
+        Ok(staff)
+    }
+
+    fn parse(bytes: &[u8], tolerant: bool) -> Result<MidiFile, &'static str> {
+        let mut reader = Reader::new(bytes);
+        if reader.take(4)? != b"MThd" {
+            return Err("missing MThd header");
+        }
+        let length = reader.u32()? as usize;
+        if length < 6 {
+            return Err("header too short");
+        }
+        let format = reader.u16()?;
+        let count = reader.u16()?;
+        let ppq = reader.u16()?;
+        reader.take(length - 6)?;
+        if format > 2 {
+            return Err("unknown SMF format");
+        }
+        if ppq & 0x8000 != 0 {
+            return Err("SMPTE time division is not supported");
+        }
+        if ppq == 0 {
+            return Err("division must be positive");
+        }
+
+        let mut meta = Meta::default();
+        let mut tracks = Vec::new();
+        while !reader.is_empty() {
+            let id = match reader.take(4) {
+                Ok(id) => id,
+                Err(_) if tolerant => break,
+                Err(error) => return Err(error),
+            };
+            let length = match reader.u32() {
+                Ok(length) => length as usize,
+                Err(_) if tolerant => break,
+                Err(error) => return Err(error),
+            };
+            let data = match reader.take(length) {
+                Ok(data) => data,
+                Err(_) if tolerant => reader.rest(),
+                Err(_) => return Err("truncated chunk"),
+            };
+            if id == b"MTrk" {
+                tracks.push(read_track(data, tolerant, &mut meta)?);
+            }
+        }
+        if !tolerant && tracks.len() != count as usize {
+            return Err("track count does not match header");
+        }
+
+        let offset = |tick: u64| Fraction::new(tick as i32, ppq as i32 * 4).to_irreducible();
+        meta.tempos.sort_by_key(|(tick, _)| *tick);
+        meta.time_signatures.sort_by_key(|(tick, _)| *tick);
+        meta.keys.sort_by_key(|(tick, _)| *tick);
+
+        let mut file = MidiFile {
+            format,
+            ppq,
+            tracks: Vec::new(),
+            tempos: meta.tempos.iter().map(|(t, v)| (offset(*t), *v)).collect(),
+            time_signatures: meta
+                .time_signatures
+                .iter()
+                .map(|(t, v)| (offset(*t), v.clone()))
+                .collect(),
+            keys: meta.keys.iter().map(|(t, v)| (offset(*t), *v)).collect(),
+        };
+
+        for raw in tracks.into_iter() {
+            let mut track = MidiTrack {
+                name: raw.name,
+                channel: raw.channel,
+                program: raw.program,
+                notes: Vec::new(),
+            };
+            for note in raw.notes.iter() {
+                let start = offset(note.start);
+                let spelling = match file.key_at(start) {
+                    Some(key) => Spelling::Key(key),
+                    None => Spelling::Sharps,
+                };
+                track.notes.push(MidiNote {
+                    pitch: Pitch::from_midi(note.number, spelling),
+                    start,
+                    length: offset(note.end - note.start),
+                    velocity: note.velocity,
+                    channel: note.channel,
+                });
+            }
+            file.tracks.push(track);
+        }
+        Ok(file)
+    }
+}
+
+// Events at the same tick keep their order, except that note offs come first.
+fn write_track(bytes: &mut Vec<u8>, mut events: Vec<(u32, Vec<u8>)>) {
+    events.sort_by_key(|(tick, data)| (*tick, data[0] & 0xF0 != 0x80));
+    let mut data = Vec::new();
+    let mut tick = 0;
+    for (time, event) in events.iter() {
+        write_varlen(&mut data, time - tick);
+        data.extend(event);
+        tick = *time;
+    }
+    data.extend(&[0x00, 0xFF, 0x2F, 0x00]);
+    bytes.extend(b"MTrk");
+    bytes.extend(&(data.len() as u32).to_be_bytes());
+    bytes.extend(data);
+}
+
+fn write_varlen(bytes: &mut Vec<u8>, value: u32) {
+    let mut groups = vec![(value & 0x7F) as u8];
+    let mut value = value >> 7;
+    while value > 0 {
+        groups.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.extend(groups.iter().rev());
+}
+
+fn snap(value: Fraction, grid: Fraction) -> Fraction {
+    let steps = value / grid;
+    let numerator = steps.numerator() as i64;
+    let denominator = steps.denominator() as i64;
+    grid * (2 * numerator + denominator).div_euclid(2 * denominator) as i32
+}
+
+fn event(pitches: &[Pitch], velocity: u8, duration: Duration, tie: Option<Tie>) -> Event {
+    match pitches {
+        [] => Rest::new(duration).into(),
+        [pitch] => {
+            let note = Note::new(*pitch, duration).with_velocity(velocity);
+            match tie {
+                Some(tie) => note.with_tie(tie).into(),
+                None => note.into(),
+            }
+        }
+        _ => {
+            let chord = ChordNote::new(pitches.to_vec(), duration).with_velocity(velocity);
+            match tie {
+                Some(tie) => chord.with_tie(tie).into(),
+                None => chord.into(),
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Meta {
+    tempos: Vec<(u64, u32)>,
+    time_signatures: Vec<(u64, TimeSignature)>,
+    keys: Vec<(u64, Key)>,
+}
+
+#[derive(Default)]
+struct RawTrack {
+    name: Option<String>,
+    channel: Option<u8>,
+    program: Option<u8>,
+    notes: Vec<RawNote>,
+}
+
+struct RawNote {
+    number: u8,
+    start: u64,
+    end: u64,
+    velocity: u8,
+    channel: u8,
+}
+
+fn read_track(data: &[u8], tolerant: bool, meta: &mut Meta) -> Result<RawTrack, &'static str> {
+    let mut reader = Reader::new(data);
+    let mut track = RawTrack::default();
+    let mut sounding: Vec<RawNote> = Vec::new();
+    let mut tick = 0u64;
+
+    match read_events(
+        &mut reader,
+        tolerant,
+        meta,
+        &mut track,
+        &mut sounding,
+        &mut tick,
+    ) {
+        Ok(true) => {}
+        Ok(false) if !tolerant => return Err("missing end of track"),
+        Err(error) if !tolerant => return Err(error),
+        _ => {}
+    }
+
+    if tick > i32::MAX as u64 {
+        return Err("track is too long");
+    }
+    if !sounding.is_empty() && !tolerant {
+        return Err("note on without note off");
+    }
+    for mut note in sounding.into_iter() {
+        note.end = tick;
+        track.notes.push(note);
+    }
+    track.notes.sort_by_key(|note| note.start);
+    Ok(track)
+}
+
+// Returns whether an end-of-track event was found.
+fn read_events(
+    reader: &mut Reader,
+    tolerant: bool,
+    meta: &mut Meta,
+    track: &mut RawTrack,
+    sounding: &mut Vec<RawNote>,
+    tick: &mut u64,
+) -> Result<bool, &'static str> {
+    let mut running: Option<u8> = None;
+    while !reader.is_empty() {
+        *tick += reader.varlen()? as u64;
+        let status = match reader.peek() {
+            Some(byte) if byte >= 0x80 => reader.byte()?,
+            _ => running.ok_or("data byte without running status")?,
+        };
+
+        match status {
+            0xFF => {
+                if !tolerant {
+                    running = None;
+                }
+                let kind = reader.byte()?;
+                let length = reader.varlen()? as usize;
+                let data = reader.take(length)?;
+                match kind {
+                    0x03 => track.name = Some(String::from_utf8_lossy(data).into_owned()),
+                    0x51 if length == 3 => {
+                        let tempo = u32::from_be_bytes([0, data[0], data[1], data[2]]);
+                        meta.tempos.push((*tick, tempo));
+                    }
+                    0x58 if length >= 2 => {
+                        if data[0] == 0 || data[1] > 15 {
+                            return Err("invalid time signature");
+                        }
+                        let meter = TimeSignature::new(data[0] as u16, 1 << data[1]);
+                        meta.time_signatures.push((*tick, meter));
+                    }
+                    0x59 if length == 2 => {
+                        let mode = if data[1] == 1 {
+                            Mode::Minor
+                        } else {
+                            Mode::Major
+                        };
+                        let key = KeySignature::new(data[0] as i8 as i32).key(mode).unwrap();
+                        meta.keys.push((*tick, key));
+                    }
+                    0x2F => return Ok(true),
+                    _ => {}
+                }
+            }
+            0xF0 | 0xF7 => {
+                if !tolerant {
+                    running = None;
+                }
+                let length = reader.varlen()? as usize;
+                reader.take(length)?;
+            }
+            0x80..=0xEF => {
+                running = Some(status);
+                let channel = status & 0x0F;
+                match status & 0xF0 {
+                    0x80 | 0x90 => {
+                        let number = reader.data()?;
+                        let velocity = reader.data()?;
+                        track.channel.get_or_insert(channel);
+                        if status & 0xF0 == 0x90 && velocity > 0 {
+                            sounding.push(RawNote {
+                                number,
+                                start: *tick,
+                                end: *tick,
+                                velocity,
+                                channel,
+                            });
+                        } else if let Some(index) = sounding
+                            .iter()
+                            .position(|n| n.number == number && n.channel == channel)
+                        {
+                            let mut note = sounding.remove(index);
+                            note.end = *tick;
+                            track.notes.push(note);
+                        }
+                    }
+                    0xC0 => {
+                        let program = reader.data()?;
+                        track.program.get_or_insert(program);
+                    }
+                    0xD0 => {
+                        reader.data()?;
+                    }
+                    _ => {
+                        reader.data()?;
+                        reader.data()?;
+                    }
+                }
+            }
+            _ => return Err("invalid status byte"),
+        }
+    }
+    Ok(false)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Reader<'a> {
+        Reader { bytes, position: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.position >= self.bytes.len()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.position).copied()
+    }
+
+    fn byte(&mut self) -> Result<u8, &'static str> {
+        let byte = self.peek().ok_or("unexpected end of data")?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn data(&mut self) -> Result<u8, &'static str> {
+        match self.byte()? {
+            byte if byte < 0x80 => Ok(byte),
+            _ => Err("invalid data byte"),
+        }
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], &'static str> {
+        if self.bytes.len() - self.position < length {
+            return Err("unexpected end of data");
+        }
+        let data = &self.bytes[self.position..self.position + length];
+        self.position += length;
+        Ok(data)
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        let data = &self.bytes[self.position..];
+        self.position = self.bytes.len();
+        data
+    }
+
+    fn u16(&mut self) -> Result<u16, &'static str> {
+        let data = self.take(2)?;
+        Ok(u16::from_be_bytes([data[0], data[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, &'static str> {
+        let data = self.take(4)?;
+        Ok(u32::from_be_bytes([data[0], data[1], data[2], data[3]]))
+    }
+
+    fn varlen(&mut self) -> Result<u32, &'static str> {
+        let mut value = 0u32;
+        for _ in 0..4 {
+            let byte = self.byte()?;
+            value = (value << 7) | (byte & 0x7F) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("variable length quantity too long")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::music::pitch::*;
+    use crate::music::{C, D, F, HALF_SHARP, NATURAL};
+
+    fn file(format: u16, ppq: u16, tracks: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = b"MThd".to_vec();
+        bytes.extend(&6u32.to_be_bytes());
+        bytes.extend(&format.to_be_bytes());
+        bytes.extend(&(tracks.len() as u16).to_be_bytes());
+        bytes.extend(&ppq.to_be_bytes());
+        for track in tracks.iter() {
+            bytes.extend(b"MTrk");
+            bytes.extend(&(track.len() as u32).to_be_bytes());
+            bytes.extend(track);
+        }
+        bytes
+    }
+
+    fn conductor() -> Vec<u8> {
+        vec![
+            0x00, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20, // 120 bpm
+            0x00, 0xFF, 0x58, 0x04, 0x03, 0x02, 0x18, 0x08, // 3/4
+            0x00, 0xFF, 0x59, 0x02, 0xFF, 0x00, // F major
+            0x00, 0xFF, 0x2F, 0x00,
+        ]
+    }
+
+    fn melody() -> Vec<u8> {
+        vec![
+            0x00, 0xFF, 0x03, 0x05, b'F', b'l', b'u', b't', b'e', // name
+            0x00, 0xC0, 0x49, // program 73
+            0x00, 0x90, 0x41, 0x50, // F4 on
+            0x60, 0x41, 0x00, // F4 off via running status
+            0x00, 0x46, 0x50, // Bb4 on
+            0x81, 0x40, 0x80, 0x46, 0x40, // Bb4 off after a half
+            0x00, 0xFF, 0x2F, 0x00,
+        ]
+    }
+
+    #[test]
+    fn test_read_format_1() {
+        let midi = MidiFile::read(&file(1, 96, &[conductor(), melody()])).unwrap();
+        assert_eq!(1, midi.format());
+        assert_eq!(96, midi.ppq());
+        assert_eq!(&[(Fraction::ZERO, 500_000)], midi.tempos());
+        assert_eq!(
+            TimeSignature::new(3, 4),
+            midi.time_signature_at(Fraction::ONE)
+        );
+        assert_eq!(
+            Some(Key::new(F, NATURAL, Mode::Major)),
+            midi.key_at(Fraction::ZERO)
+        );
+
+        let track = &midi.tracks()[1];
+        assert_eq!(Some("Flute"), track.name());
+        assert_eq!(Some(73), track.program());
+        assert_eq!(Some(0), track.channel());
+        assert_eq!(2, track.notes().len());
+        assert_eq!(F4, track.notes()[0].pitch());
+        assert_eq!(Fraction::new(1, 4), track.notes()[0].length());
+        assert_eq!(Bb4, track.notes()[1].pitch());
+        assert_eq!(Fraction::new(1, 4), track.notes()[1].start());
+        assert_eq!(Fraction::HALF, track.notes()[1].length());
+        assert_eq!(80, track.notes()[1].velocity());
+    }
+
+    #[test]
+    fn test_read_format_0() {
+        let mut track = conductor();
+        track.truncate(track.len() - 4);
+        track.extend(&melody()[9..]);
+        let midi = MidiFile::read(&file(0, 96, &[track])).unwrap();
+        assert_eq!(0, midi.format());
+        assert_eq!(1, midi.tracks().len());
+        assert_eq!(2, midi.tracks()[0].notes().len());
+    }
+
+    #[test]
+    fn test_read_errors() {
+        assert!(MidiFile::read(b"RIFF").is_err());
+        let mut smpte = file(0, 0xE728, &[]);
+        assert_eq!(
+            Err("SMPTE time division is not supported"),
+            MidiFile::read(&smpte)
+        );
+        smpte.truncate(10);
+        assert!(MidiFile::read(&smpte).is_err());
+    }
+
+    #[test]
+    fn test_read_long_track() {
+        let mut track = vec![0x00, 0x90, 0x3C, 0x40];
+        for _ in 0..9 {
+            track.extend(&[0xFF, 0xFF, 0xFF, 0x7F, 0xFF, 0x01, 0x00]);
+        }
+        track.extend(&[0x00, 0x80, 0x3C, 0x00, 0x00, 0xFF, 0x2F, 0x00]);
+        let bytes = file(0, 96, &[track]);
+        assert_eq!(Err("track is too long"), MidiFile::read(&bytes));
+        assert_eq!(Err("track is too long"), MidiFile::read_tolerant(&bytes));
+    }
+
+    #[test]
+    fn test_read_tolerant() {
+        // Running status resumed after a meta event, no note off, no end of track.
+        let track = vec![
+            0x00, 0x90, 0x3C, 0x40, // C4 on
+            0x00, 0xFF, 0x01, 0x01, b'x', // text
+            0x00, 0x40, 0x40, // E4 on, running status across meta
+            0x60, 0x3C, 0x00, // C4 off
+        ];
+        let bytes = file(0, 96, &[track]);
+        assert!(MidiFile::read(&bytes).is_err());
+        let midi = MidiFile::read_tolerant(&bytes).unwrap();
+        let notes = midi.tracks()[0].notes();
+        assert_eq!(2, notes.len());
+        assert_eq!(E4, notes[1].pitch());
+        assert_eq!(Fraction::new(1, 4), notes[1].length());
+
+        // Chunk length running past the end of the file.
+        let mut bytes = file(1, 96, &[conductor(), melody()]);
+        bytes.truncate(bytes.len() - 6);
+        assert!(MidiFile::read(&bytes).is_err());
+        let midi = MidiFile::read_tolerant(&bytes).unwrap();
+        let notes = midi.tracks()[1].notes();
+        assert_eq!(2, notes.len());
+        assert_eq!(Fraction::HALF, notes[1].length());
+    }
+
+    #[test]
+    fn test_quantize() {
+        let note = MidiNote::new(C4, Fraction::new(49, 192), Fraction::new(23, 192), 64, 0);
+        let note = note.quantize(Fraction::new(1, 16));
+        assert_eq!(Fraction::new(1, 4), note.start());
+        assert_eq!(Fraction::new(1, 8), note.length());
+        let note = MidiNote::new(C4, Fraction::ZERO, Fraction::new(1, 192), 64, 0);
+        assert_eq!(
+            Fraction::new(1, 16),
+            note.quantize(Fraction::new(1, 16)).length()
+        );
+    }
+
+    #[test]
+    fn test_to_score() {
+        let midi = MidiFile::read(&file(1, 96, &[conductor(), melody()])).unwrap();
+        let score = midi.to_score(Fraction::new(1, 16)).unwrap();
+        assert_eq!(Ok(()), score.validate());
+        assert_eq!(1, score.parts().len());
+        assert_eq!("Flute", score.parts()[0].name());
+
+        let measure = &score.parts()[0].staves()[0].measures()[0];
+        assert_eq!(Some(&TimeSignature::new(3, 4)), measure.time_signature());
+        assert_eq!(Some(KeySignature::new(-1)), measure.key_signature());
+        let events = measure.voices()[0].events();
+        assert_eq!(vec![F4], events[0].pitches());
+        assert_eq!(Duration::new(2), events[1].duration());
+    }
+
+    #[test]
+    fn test_to_score_ties_and_chords() {
+        let track = vec![
+            0x00, 0x90, 0x3C, 0x40, // C4
+            0x00, 0x40, 0x40, // E4
+            0x83, 0x60, 0x3C, 0x00, // both off after 5 quarters
+            0x00, 0x40, 0x00, //
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        let midi = MidiFile::read(&file(0, 96, &[track])).unwrap();
+        let score = midi.to_score(Fraction::new(1, 16)).unwrap();
+        assert_eq!(Ok(()), score.validate());
+        let measures = score.parts()[0].staves()[0].measures();
+        assert_eq!(2, measures.len());
+        match &measures[0].voices()[0].events()[0] {
+            Event::Chord(chord) => {
+                assert_eq!(&[C4, E4], chord.pitches());
+                assert_eq!(Duration::new(1), chord.duration());
+                assert_eq!(Some(Tie::Start), chord.tie());
+            }
+            _ => panic!("expected a chord"),
+        }
+        let events = measures[1].voices()[0].events();
+        assert_eq!(Duration::new(4), events[0].duration());
+        assert!(events[1].is_rest());
+    }
+
+    #[test]
+    fn test_varlen() {
+        for value in [0, 0x40, 0x7F, 0x80, 0x2000, 0x3FFF, 0x4000, 0x0FFF_FFFF].iter() {
+            let mut bytes = Vec::new();
+            write_varlen(&mut bytes, *value);
+            assert_eq!(Ok(*value), Reader::new(&bytes).varlen());
+        }
+        let mut bytes = Vec::new();
+        write_varlen(&mut bytes, 0x80);
+        assert_eq!(vec![0x81, 0x00], bytes);
+    }
+
+    #[test]
+    fn test_write() {
+        let mut track = MidiTrack::new()
+            .with_name("Lead")
+            .with_channel(3)
+            .with_program(40);
+        track.push(MidiNote::new(
+            C4,
+            Fraction::ZERO,
+            Fraction::new(1, 4),
+            90,
+            0,
+        ));
+        track.push(MidiNote::new(
+            Eb4,
+            Fraction::new(1, 4),
+            Fraction::new(1, 12),
+            70,
+            0,
+        ));
+        track.push(MidiNote::new(
+            G4,
+            Fraction::new(1, 4),
+            Fraction::HALF,
+            70,
+            0,
+        ));
+        let mut midi = MidiFile::new(480)
+            .with_tempo(Fraction::ZERO, 600_000)
+            .with_time_signature(Fraction::ZERO, TimeSignature::new(6, 8))
+            .with_key(Fraction::ZERO, Key::new(C, NATURAL, Mode::Minor));
+        midi.push(track);
+
+        let read = MidiFile::read(&midi.write().unwrap()).unwrap();
+        assert_eq!(1, read.format());
+        assert_eq!(480, read.ppq());
+        assert_eq!(&[(Fraction::ZERO, 600_000)], read.tempos());
+        assert_eq!(
+            TimeSignature::new(6, 8),
+            read.time_signature_at(Fraction::ZERO)
+        );
+        assert_eq!(
+            Some(Key::new(C, NATURAL, Mode::Minor)),
+            read.key_at(Fraction::ZERO)
+        );
+        let track = &read.tracks()[1];
+        assert_eq!(Some("Lead"), track.name());
+        assert_eq!(Some(3), track.channel());
+        assert_eq!(Some(40), track.program());
+        let notes = track.notes();
+        assert_eq!(3, notes.len());
+        assert_eq!(Eb4, notes[1].pitch());
+        assert_eq!(Fraction::new(1, 12), notes[1].length());
+        assert_eq!(70, notes[2].velocity());
+        assert_eq!(3, notes[2].channel());
+    }
+
+    #[test]
+    fn test_write_errors() {
+        let mut track = MidiTrack::new();
+        track.push(MidiNote::new(
+            Pitch::new_with_accidental(C, HALF_SHARP, 4),
+            Fraction::ZERO,
+            Fraction::new(1, 4),
+            64,
+            0,
+        ));
+        let mut midi = MidiFile::new(96);
+        midi.push(track);
+        assert!(midi.write().is_err());
+
+        let midi = MidiFile::new(96).with_time_signature(Fraction::ZERO, TimeSignature::new(4, 3));
+        assert!(midi.write().is_err());
+    }
+
+    #[test]
+    fn test_write_program_per_channel() {
+        let mut track = MidiTrack::new().with_program(40);
+        for channel in [2, 5, 2].iter() {
+            track.push(MidiNote::new(
+                C4,
+                Fraction::ZERO,
+                Fraction::new(1, 4),
+                64,
+                *channel,
+            ));
+        }
+        let mut midi = MidiFile::new(96);
+        midi.push(track);
+        let bytes = midi.write().unwrap();
+        let programs: Vec<&[u8]> = bytes
+            .windows(2)
+            .filter(|w| w[0] & 0xF0 == 0xC0 && w[1] == 40)
+            .collect();
+        assert_eq!(vec![&[0xC2, 40][..], &[0xC5, 40][..]], programs);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_zero_velocity() {
+        let _illegal = MidiNote::new(C4, Fraction::ZERO, Fraction::new(1, 4), 0, 0);
+    }
+
+    #[test]
+    fn test_score_round_trip() {
+        let midi = MidiFile::read(&file(1, 96, &[conductor(), melody()])).unwrap();
+        let score = midi.to_score(Fraction::new(1, 16)).unwrap();
+        let written = MidiFile::from_score(&score, 96);
+        let read = MidiFile::read(&written.write().unwrap()).unwrap();
+        assert_eq!(
+            TimeSignature::new(3, 4),
+            read.time_signature_at(Fraction::ZERO)
+        );
+        assert_eq!(
+            Some(Key::new(F, NATURAL, Mode::Major)),
+            read.key_at(Fraction::ZERO)
+        );
+        assert_eq!(Some("Flute"), read.tracks()[1].name());
+        assert_eq!(midi.tracks()[1].notes(), read.tracks()[1].notes());
+    }
+
+    #[test]
+    fn test_from_score_minor_key() {
+        let measure = Measure::new(vec![Voice::new(vec![
+            Note::new(D4, Duration::new(1)).into()
+        ])])
+        .with_key_signature(KeySignature::new(-1))
+        .with_mode(Mode::Minor);
+        let score = Score::new(vec![Part::new("Piano", vec![Staff::new(vec![measure])])]);
+        let midi = MidiFile::from_score(&score, 96);
+        assert_eq!(
+            Some(Key::new(D, NATURAL, Mode::Minor)),
+            midi.key_at(Fraction::ZERO)
+        );
+        let bytes = midi.write().unwrap();
+        assert!(bytes
+            .windows(5)
+            .any(|w| w == [0xFF, 0x59, 0x02, 0xFF, 0x01]));
+        let score = MidiFile::read(&bytes)
+            .unwrap()
+            .to_score(Fraction::new(1, 16))
+            .unwrap();
+        let measure = &score.parts()[0].staves()[0].measures()[0];
+        assert_eq!(Some(Mode::Minor), measure.mode());
+    }
+
+    #[test]
+    fn test_from_score_ties() {
+        let track = vec![
+            0x00, 0x90, 0x3C, 0x40, // C4 for five quarters
+            0x83, 0x60, 0x3C, 0x00, //
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        let midi = MidiFile::read(&file(0, 96, &[track])).unwrap();
+        let score = midi.to_score(Fraction::new(1, 16)).unwrap();
+        let notes = MidiFile::from_score(&score, 96).tracks()[0]
+            .notes()
+            .to_vec();
+        assert_eq!(1, notes.len());
+        assert_eq!(Fraction::new(5, 4), notes[0].length());
+        assert_eq!(64, notes[0].velocity());
+    }
+}
@@ -0,0 +1,7 @@
+pub mod abc;
+pub mod kern;
+pub mod lilypond;
+pub mod midi;
+pub mod musicxml;
+mod xml;
+mod zip;
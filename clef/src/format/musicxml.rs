@@ -0,0 +1,1677 @@
+use super::xml::{self, Element};
+use super::zip;
+use crate::math::{self, Fraction};
+use crate::music::{
+    Accidental, Articulation, ChordNote, Clef, ClefSign, Duration, Dynamic, Event, KeySignature,
+    Measure, Mode, Note, NoteName, Part, Pitch, Rest, Score, Staff, Tie, TimeSignature, Tuplet,
+    Voice, A, B, C, D, E, F, G, NATURAL,
+};
+use std::str::FromStr;
+
+pub fn read(document: &str) -> Result<Score, &'static str> {
+    let root = xml::parse(document)?;
+    match root.name() {
+        "score-partwise" => read_partwise(&root),
+        "score-timewise" => read_partwise(&to_partwise(&root)),
+        _ => Err("not a MusicXML score"),
+    }
+}
+
+pub fn read_compressed(bytes: &[u8]) -> Result<Score, &'static str> {
+    let entries = zip::entries(bytes)?;
+    let path = match entries
+        .iter()
+        .find(|entry| entry.name() == "META-INF/container.xml")
+    {
+        Some(container) => {
+            let contents = container.contents()?;
+            let container = xml::parse(
+                std::str::from_utf8(&contents).map_err(|_| "container is not valid UTF-8")?,
+            )?;
+            container
+                .child("rootfiles")
+                .and_then(|rootfiles| rootfiles.child("rootfile"))
+                .and_then(|rootfile| rootfile.attribute("full-path"))
+                .ok_or("container has no rootfile")?
+                .to_string()
+        }
+        None => entries
+            .iter()
+            .map(|entry| entry.name())
+            .find(|name| {
+                !name.starts_with("META-INF/")
+                    && (name.ends_with(".musicxml") || name.ends_with(".xml"))
+            })
+            .ok_or("archive contains no score")?
+            .to_string(),
+    };
+    let entry = entries
+        .iter()
+        .find(|entry| entry.name() == path)
+        .ok_or("rootfile missing from archive")?;
+    let contents = entry.contents()?;
+    read(std::str::from_utf8(&contents).map_err(|_| "score is not valid UTF-8")?)
+}
+
+// Regroups a timewise score, whose measures contain parts, into a partwise one.
+fn to_partwise(root: &Element) -> Element {
+    let mut partwise = Element::new("score-partwise");
+    for child in root.elements().filter(|child| child.name() != "measure") {
+        partwise.push(child.clone());
+    }
+
+    let mut ids: Vec<&str> = Vec::new();
+    for measure in root.children("measure") {
+        for part in measure.children("part") {
+            if let Some(id) = part.attribute("id") {
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
+            }
+        }
+    }
+
+    for id in ids {
+        let mut part = Element::new("part").with_attribute("id", id);
+        for measure in root.children("measure") {
+            if let Some(contents) = measure
+                .children("part")
+                .find(|part| part.attribute("id") == Some(id))
+            {
+                let mut regrouped = Element::new("measure");
+                for (name, value) in measure.attributes() {
+                    regrouped = regrouped.with_attribute(name, value);
+                }
+                part.push(regrouped.with_children_of(contents));
+            }
+        }
+        partwise.push(part);
+    }
+    partwise
+}
+
+fn read_partwise(root: &Element) -> Result<Score, &'static str> {
+    let names: Vec<(&str, String)> = match root.child("part-list") {
+        Some(list) => list
+            .children("score-part")
+            .filter_map(|part| {
+                Some((
+                    part.attribute("id")?,
+                    part.child_text("part-name").unwrap_or_default(),
+                ))
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+
+    let mut score = Score::default();
+    let title = root
+        .child("work")
+        .and_then(|work| work.child_text("work-title"))
+        .or_else(|| root.child_text("movement-title"));
+    if let Some(title) = title {
+        score = score.with_title(&title);
+    }
+
+    for part in root.children("part") {
+        let id = part.attribute("id").unwrap_or_default();
+        let name = names
+            .iter()
+            .find(|(other, _)| *other == id)
+            .map(|(_, name)| name.as_str())
+            .unwrap_or(id);
+        score.push(Part::new(name, read_part(part)?));
+    }
+    Ok(score)
+}
+
+fn read_part(part: &Element) -> Result<Vec<Staff>, &'static str> {
+    let count = staff_count(part);
+    let mut staves = vec![Staff::default(); count];
+    let mut divisions = 1;
+    let mut meter = TimeSignature::new(4, 4);
+
+    for element in part.children("measure") {
+        let mut contents = Contents::new(count);
+        for child in element.elements() {
+            match child.name() {
+                "attributes" => {
+                    if let Some(value) = child.child_text("divisions") {
+                        divisions = value.parse().map_err(|_| "invalid divisions")?;
+                        if divisions <= 0 {
+                            return Err("divisions must be positive");
+                        }
+                    }
+                    for key in child.children("key") {
+                        let signature = read_key(key)?;
+                        let mode = key.child_text("mode").and_then(|mode| read_mode(&mode));
+                        for staff in targets(key, count) {
+                            contents.keys[staff] = Some((signature, mode));
+                        }
+                    }
+                    if let Some(time) = child.child("time") {
+                        if let Some(time) = read_time(time)? {
+                            meter = time.clone();
+                            contents.time = Some(time);
+                        }
+                    }
+                    for clef in child.children("clef") {
+                        if let Some(value) = read_clef(clef)? {
+                            for staff in targets(clef, count) {
+                                contents.clefs[staff] = Some(value);
+                            }
+                        }
+                    }
+                }
+                "note" => contents.note(child, divisions)?,
+                "backup" => {
+                    let length = read_length(child, divisions)?;
+                    contents.position = std::cmp::max(contents.position - length, Fraction::ZERO);
+                }
+                "forward" => contents.position += read_length(child, divisions)?,
+                "direction" => contents.direction(child),
+                _ => {}
+            }
+        }
+
+        let pickup = element.attribute("implicit") == Some("yes");
+        for (staff, measure) in staves.iter_mut().zip(contents.measures(&meter, pickup)?) {
+            staff.push(measure);
+        }
+    }
+    Ok(staves)
+}
+
+fn staff_count(part: &Element) -> usize {
+    let mut count = 1;
+    for measure in part.children("measure") {
+        for child in measure.elements() {
+            let value = match child.name() {
+                "attributes" => child.child_text("staves"),
+                "note" | "forward" | "direction" => child.child_text("staff"),
+                _ => None,
+            };
+            if let Some(number) = value.and_then(|value| value.parse::<usize>().ok()) {
+                count = std::cmp::max(count, number);
+            }
+        }
+    }
+    count
+}
+
+fn staff_index(element: &Element, count: usize) -> usize {
+    match element
+        .child_text("staff")
+        .and_then(|staff| staff.parse::<usize>().ok())
+    {
+        Some(number) if number >= 1 => std::cmp::min(number, count) - 1,
+        _ => 0,
+    }
+}
+
+// The staves an attribute applies to: the numbered one, or all of them.
+fn targets(element: &Element, count: usize) -> Vec<usize> {
+    match element
+        .attribute("number")
+        .and_then(|number| number.parse::<usize>().ok())
+    {
+        Some(number) if (1..=count).contains(&number) => vec![number - 1],
+        _ => (0..count).collect(),
+    }
+}
+
+// Limits that keep the arithmetic on note lengths within i32.
+const MAX_LENGTH: i32 = 64;
+const MAX_DENOMINATOR: i32 = 4096;
+
+fn read_length(element: &Element, divisions: i32) -> Result<Fraction, &'static str> {
+    let duration: i32 = element
+        .child_text("duration")
+        .ok_or("missing duration")?
+        .parse()
+        .map_err(|_| "invalid duration")?;
+    if duration < 0 {
+        return Err("duration must not be negative");
+    }
+    let whole = divisions
+        .checked_mul(4)
+        .ok_or("too many divisions per quarter note")?;
+    let length = Fraction::new(duration, whole).to_irreducible();
+    if length > Fraction::new(MAX_LENGTH, 1) || length.denominator() > MAX_DENOMINATOR {
+        return Err("duration out of range");
+    }
+    Ok(length)
+}
+
+fn read_alter(text: &str) -> Result<Accidental, &'static str> {
+    let semitones: f64 = text.trim().parse().map_err(|_| "invalid alter")?;
+    Ok(Accidental::from_cents((semitones * 100.0).round() as i32))
+}
+
+fn read_pitch(note: &Element) -> Result<Option<Pitch>, &'static str> {
+    let (step, alter, octave) = if let Some(pitch) = note.child("pitch") {
+        (
+            pitch.child_text("step").ok_or("missing step")?,
+            pitch.child_text("alter"),
+            pitch.child_text("octave").ok_or("missing octave")?,
+        )
+    } else if let Some(unpitched) = note.child("unpitched") {
+        match (
+            unpitched.child_text("display-step"),
+            unpitched.child_text("display-octave"),
+        ) {
+            (Some(step), Some(octave)) => (step, None, octave),
+            _ => return Ok(Some(Pitch::new(B, 4))),
+        }
+    } else {
+        return Ok(None);
+    };
+
+    let name = NoteName::from_str(&step)?;
+    let accidental = match alter {
+        Some(alter) => read_alter(&alter)?,
+        None => NATURAL,
+    };
+    let octave: i32 = octave.parse().map_err(|_| "invalid octave")?;
+    if !(-1..=9).contains(&octave) {
+        return Err("octave out of range");
+    }
+    Ok(Some(Pitch::new_with_accidental(name, accidental, octave)))
+}
+
+fn read_type(note: &Element) -> Option<Duration> {
+    let denominator = match note.child_text("type")?.as_str() {
+        "whole" => 1,
+        "half" => 2,
+        "quarter" => 4,
+        "eighth" => 8,
+        "16th" => 16,
+        "32nd" => 32,
+        "64th" => 64,
+        "128th" => 128,
+        "256th" => 256,
+        "512th" => 512,
+        "1024th" => 1024,
+        _ => return None,
+    };
+    let dots = std::cmp::min(note.children("dot").count(), 4) as u8;
+    let duration = Duration::new_with_dots(denominator, dots);
+    let modification = match note.child("time-modification") {
+        Some(modification) => modification,
+        None => return Some(duration),
+    };
+    let actual: u16 = modification.child_text("actual-notes")?.parse().ok()?;
+    let normal: u16 = modification.child_text("normal-notes")?.parse().ok()?;
+    if actual == 0 || normal == 0 {
+        return None;
+    }
+    Some(duration.with_tuplet(Tuplet::new(actual, normal)))
+}
+
+// The written durations of a note, trusting <duration> when <type> disagrees with it.
+fn read_durations(note: &Element, length: Fraction) -> Result<Vec<Duration>, &'static str> {
+    match read_type(note) {
+        Some(duration) if duration.to_fraction() == length => Ok(vec![duration]),
+        _ => Duration::decompose(length),
+    }
+}
+
+// Whether a note is tied from the previous note and to the next one.
+fn read_tie(note: &Element) -> (bool, bool) {
+    let types: Vec<&str> = note
+        .children("tie")
+        .filter_map(|tie| tie.attribute("type"))
+        .collect();
+    (types.contains(&"stop"), types.contains(&"start"))
+}
+
+fn read_articulations(note: &Element) -> Vec<Articulation> {
+    let mut articulations = Vec::new();
+    for notations in note.children("notations") {
+        for group in notations.children("articulations") {
+            for mark in group.elements() {
+                let articulation = match mark.name() {
+                    "staccato" => Articulation::Staccato,
+                    "staccatissimo" | "spiccato" => Articulation::Staccatissimo,
+                    "tenuto" => Articulation::Tenuto,
+                    "accent" => Articulation::Accent,
+                    "strong-accent" => Articulation::Marcato,
+                    _ => continue,
+                };
+                articulations.push(articulation);
+            }
+        }
+        if notations.has("fermata") {
+            articulations.push(Articulation::Fermata);
+        }
+    }
+    articulations
+}
+
+const DYNAMICS: [(&str, Dynamic); 8] = [
+    ("ppp", Dynamic::Ppp),
+    ("pp", Dynamic::Pp),
+    ("p", Dynamic::P),
+    ("mp", Dynamic::Mp),
+    ("mf", Dynamic::Mf),
+    ("f", Dynamic::F),
+    ("ff", Dynamic::Ff),
+    ("fff", Dynamic::Fff),
+];
+
+fn read_dynamics(dynamics: &Element) -> Option<Dynamic> {
+    dynamics
+        .elements()
+        .filter_map(|mark| {
+            DYNAMICS
+                .iter()
+                .find(|(name, _)| *name == mark.name())
+                .map(|(_, dynamic)| *dynamic)
+        })
+        .last()
+}
+
+fn read_key(key: &Element) -> Result<KeySignature, &'static str> {
+    if let Some(fifths) = key.child_text("fifths") {
+        let fifths = fifths.parse().map_err(|_| "invalid key fifths")?;
+        if !(-7..=7).contains(&fifths) {
+            return Err("key fifths out of range");
+        }
+        return Ok(KeySignature::new(fifths));
+    }
+    let mut accidentals = Vec::new();
+    for (step, alter) in key.children("key-step").zip(key.children("key-alter")) {
+        accidentals.push((
+            NoteName::from_str(&step.text())?,
+            read_alter(&alter.text())?,
+        ));
+    }
+    Ok(KeySignature::from_accidentals(&accidentals))
+}
+
+fn read_mode(text: &str) -> Option<Mode> {
+    Some(match text.trim() {
+        "major" | "ionian" => Mode::Major,
+        "minor" | "aeolian" => Mode::Minor,
+        "dorian" => Mode::Dorian,
+        "phrygian" => Mode::Phrygian,
+        "lydian" => Mode::Lydian,
+        "mixolydian" => Mode::Mixolydian,
+        "locrian" => Mode::Locrian,
+        _ => return None,
+    })
+}
+
+fn read_time(time: &Element) -> Result<Option<TimeSignature>, &'static str> {
+    if time.has("senza-misura") {
+        return Ok(None);
+    }
+    let beats = time.child_text("beats").ok_or("missing beats")?;
+    let beat_type = time.child_text("beat-type").ok_or("missing beat type")?;
+    TimeSignature::from_str(&format!("{}/{}", beats, beat_type)).map(Some)
+}
+
+fn read_clef(clef: &Element) -> Result<Option<Clef>, &'static str> {
+    let (sign, line) = match clef.child_text("sign").ok_or("missing clef sign")?.as_str() {
+        "G" => (ClefSign::G, 2),
+        "F" => (ClefSign::F, 4),
+        "C" => (ClefSign::C, 3),
+        "percussion" => (ClefSign::Percussion, 3),
+        "TAB" => (ClefSign::Tab, 5),
+        _ => return Ok(None),
+    };
+    let line = match clef.child_text("line") {
+        Some(line) => line.parse().map_err(|_| "invalid clef line")?,
+        None => line,
+    };
+    let octave_change = match clef.child_text("clef-octave-change") {
+        Some(change) => change.parse().map_err(|_| "invalid clef octave change")?,
+        None => 0,
+    };
+    Ok(Some(Clef::new(sign, line, octave_change)))
+}
+
+// A note or rest whose pitches may still grow as <chord/> notes follow it.
+struct Pending {
+    pitches: Vec<Pitch>,
+    duration: Duration,
+    tie: Option<Tie>,
+    articulations: Vec<Articulation>,
+    dynamics: Option<Dynamic>,
+}
+
+impl Pending {
+    fn event(self) -> Event {
+        let event: Event = match self.pitches.len() {
+            0 => return Rest::new(self.duration).into(),
+            1 => Note::new(self.pitches[0], self.duration).into(),
+            _ => ChordNote::new(self.pitches, self.duration).into(),
+        };
+        match event {
+            Event::Note(mut note) => {
+                if let Some(tie) = self.tie {
+                    note = note.with_tie(tie);
+                }
+                for articulation in self.articulations {
+                    note = note.with_articulation(articulation);
+                }
+                if let Some(dynamics) = self.dynamics {
+                    note = note.with_dynamics(dynamics);
+                }
+                note.into()
+            }
+            Event::Chord(mut chord) => {
+                if let Some(tie) = self.tie {
+                    chord = chord.with_tie(tie);
+                }
+                for articulation in self.articulations {
+                    chord = chord.with_articulation(articulation);
+                }
+                if let Some(dynamics) = self.dynamics {
+                    chord = chord.with_dynamics(dynamics);
+                }
+                chord.into()
+            }
+            rest => rest,
+        }
+    }
+}
+
+fn rests(length: Fraction) -> Result<Vec<Pending>, &'static str> {
+    Ok(Duration::decompose(length)?
+        .into_iter()
+        .map(|duration| Pending {
+            pitches: Vec::new(),
+            duration,
+            tie: None,
+            articulations: Vec::new(),
+            dynamics: None,
+        })
+        .collect())
+}
+
+// A voice within a measure, keyed by staff and MusicXML voice number.
+struct VoiceContents {
+    staff: usize,
+    number: String,
+    events: Vec<Pending>,
+    end: Fraction,
+}
+
+// The notes and attribute changes of one measure of a part.
+struct Contents {
+    position: Fraction,
+    voices: Vec<VoiceContents>,
+    last: Option<(usize, usize)>,
+    dynamics: Vec<Option<Dynamic>>,
+    time: Option<TimeSignature>,
+    keys: Vec<Option<(KeySignature, Option<Mode>)>>,
+    clefs: Vec<Option<Clef>>,
+}
+
+impl Contents {
+    fn new(staves: usize) -> Contents {
+        Contents {
+            position: Fraction::ZERO,
+            voices: Vec::new(),
+            last: None,
+            dynamics: vec![None; staves],
+            time: None,
+            keys: vec![None; staves],
+            clefs: vec![None; staves],
+        }
+    }
+
+    fn note(&mut self, note: &Element, divisions: i32) -> Result<(), &'static str> {
+        if note.has("grace") {
+            return Ok(());
+        }
+        let pitch = read_pitch(note)?;
+
+        if note.has("chord") {
+            if let (Some((voice, first)), Some(pitch)) = (self.last, pitch) {
+                for event in self.voices[voice].events[first..].iter_mut() {
+                    if !event.pitches.contains(&pitch) {
+                        event.pitches.push(pitch);
+                    }
+                }
+            }
+            return Ok(());
+        }
+
+        let length = read_length(note, divisions)?;
+        if length == Fraction::ZERO {
+            return Ok(());
+        }
+        let staff = staff_index(note, self.dynamics.len());
+        let number = note.child_text("voice").unwrap_or_else(|| "1".to_string());
+        let index = match self
+            .voices
+            .iter()
+            .position(|voice| voice.staff == staff && voice.number == number)
+        {
+            Some(index) => index,
+            None => {
+                self.voices.push(VoiceContents {
+                    staff,
+                    number,
+                    events: Vec::new(),
+                    end: Fraction::ZERO,
+                });
+                self.voices.len() - 1
+            }
+        };
+
+        let mut dynamics = note
+            .children("notations")
+            .filter_map(|notations| notations.child("dynamics"))
+            .filter_map(read_dynamics)
+            .last();
+        if pitch.is_some() {
+            dynamics = dynamics.or_else(|| self.dynamics[staff].take());
+        }
+        let start = self.position;
+        self.position += length;
+        let voice = &mut self.voices[index];
+        if voice.end < start {
+            voice.events.extend(rests(start - voice.end)?);
+            voice.end = start;
+        }
+        voice.end += length;
+
+        let (tied_from, tied_to) = read_tie(note);
+        let articulations = read_articulations(note);
+        let durations = read_durations(note, length)?;
+        let count = durations.len();
+
+        self.last = Some((index, voice.events.len()));
+        for (n, duration) in durations.into_iter().enumerate() {
+            let tie = match (tied_from || n > 0, tied_to || n + 1 < count) {
+                _ if pitch.is_none() => None,
+                (true, true) => Some(Tie::Continue),
+                (true, false) => Some(Tie::Stop),
+                (false, true) => Some(Tie::Start),
+                (false, false) => None,
+            };
+            voice.events.push(Pending {
+                pitches: pitch.into_iter().collect(),
+                duration,
+                tie,
+                articulations: if n == 0 {
+                    articulations.clone()
+                } else {
+                    Vec::new()
+                },
+                dynamics: if n == 0 { dynamics } else { None },
+            });
+        }
+        Ok(())
+    }
+
+    fn direction(&mut self, direction: &Element) {
+        let staff = staff_index(direction, self.dynamics.len());
+        for kind in direction.children("direction-type") {
+            if let Some(dynamics) = kind.child("dynamics").and_then(read_dynamics) {
+                self.dynamics[staff] = Some(dynamics);
+            }
+        }
+    }
+
+    // One measure per staff, with every voice padded out to the measure's length.
+    fn measures(self, meter: &TimeSignature, pickup: bool) -> Result<Vec<Measure>, &'static str> {
+        let length = if pickup {
+            self.voices
+                .iter()
+                .map(|voice| voice.end)
+                .max()
+                .unwrap_or(Fraction::ZERO)
+        } else {
+            meter.measure_length()
+        };
+
+        let mut measures: Vec<Measure> = (0..self.dynamics.len())
+            .map(|staff| {
+                let mut measure = Measure::default();
+                if let Some(time) = &self.time {
+                    measure = measure.with_time_signature(time.clone());
+                }
+                if let Some((key, mode)) = self.keys[staff] {
+                    measure = measure.with_key_signature(key);
+                    if let Some(mode) = mode {
+                        measure = measure.with_mode(mode);
+                    }
+                }
+                if let Some(clef) = self.clefs[staff] {
+                    measure = measure.with_clef(clef);
+                }
+                if pickup {
+                    measure = measure.with_pickup();
+                }
+                measure
+            })
+            .collect();
+
+        for mut voice in self.voices {
+            if voice.end < length {
+                voice.events.extend(rests(length - voice.end)?);
+            }
+            let events = voice.events.into_iter().map(Pending::event).collect();
+            measures[voice.staff].push(Voice::new(events));
+        }
+        Ok(measures)
+    }
+}
+
+const HEADER: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?>
+<!DOCTYPE score-partwise PUBLIC "-//Recordare//DTD MusicXML 4.0 Partwise//EN" "http://www.musicxml.org/dtds/partwise.dtd">
+"#;
+
+pub fn write(score: &Score) -> Result<String, &'static str> {
+    let mut root = Element::new("score-partwise").with_attribute("version", "4.0");
+    if let Some(title) = score.title() {
+        root.push(Element::new("work").with_child(text_element("work-title", title)));
+    }
+    let mut list = Element::new("part-list");
+    for (index, part) in score.parts().iter().enumerate() {
+        list.push(
+            Element::new("score-part")
+                .with_attribute("id", &format!("P{}", index + 1))
+                .with_child(text_element("part-name", part.name())),
+        );
+    }
+    root.push(list);
+    for (index, part) in score.parts().iter().enumerate() {
+        root.push(write_part(part, &format!("P{}", index + 1))?);
+    }
+
+    let mut out = HEADER.to_string();
+    root.write(&mut out, 0);
+    Ok(out)
+}
+
+fn text_element(name: &str, text: &str) -> Element {
+    Element::new(name).with_text(text)
+}
+
+// The fewest divisions per quarter note that express every length in the part.
+fn divisions(part: &Part) -> Result<i32, &'static str> {
+    let mut divisions = 1;
+    for staff in part.staves() {
+        for (index, measure) in staff.measures().iter().enumerate() {
+            let events = measure
+                .voices()
+                .iter()
+                .flat_map(|voice| voice.events().iter().map(Event::length));
+            let meter = staff.time_signature_at(index).measure_length();
+            for length in events.chain(std::iter::once(meter)) {
+                let quarters = (length * 4).to_irreducible();
+                divisions = math::lcm(divisions, quarters.denominator())
+                    .ok_or("too many divisions per quarter note")?;
+            }
+        }
+    }
+    Ok(divisions)
+}
+
+fn ticks(length: Fraction, divisions: i32) -> String {
+    (length * (divisions * 4))
+        .to_irreducible()
+        .numerator()
+        .to_string()
+}
+
+fn write_part(part: &Part, id: &str) -> Result<Element, &'static str> {
+    let divisions = divisions(part)?;
+    let staves = part.staves();
+    let count = staves
+        .iter()
+        .map(|staff| staff.measures().len())
+        .max()
+        .unwrap_or(0);
+    // Voices are numbered across the part, four per staff as notation programs expect.
+    let voices = staves
+        .iter()
+        .flat_map(|staff| staff.measures().iter().map(|m| m.voices().len()))
+        .fold(4, std::cmp::max);
+    let starts_with_pickup = staves
+        .iter()
+        .any(|staff| staff.measures().first().is_some_and(Measure::is_pickup));
+
+    let mut element = Element::new("part").with_attribute("id", id);
+    for index in 0..count {
+        let number = if starts_with_pickup { index } else { index + 1 };
+        let mut measure = Element::new("measure").with_attribute("number", &number.to_string());
+        if staves
+            .iter()
+            .any(|staff| staff.measures().get(index).is_some_and(Measure::is_pickup))
+        {
+            measure = measure.with_attribute("implicit", "yes");
+        }
+        if let Some(attributes) = write_attributes(staves, index, divisions) {
+            measure.push(attributes);
+        }
+
+        let mut position = Fraction::ZERO;
+        for (number, staff) in staves.iter().enumerate() {
+            let contents = match staff.measures().get(index) {
+                Some(contents) => contents,
+                None => continue,
+            };
+            let meter = staff.time_signature_at(index);
+            let shift = if index == 0 && contents.is_pickup() {
+                meter.measure_length() - contents.length(&meter)
+            } else {
+                Fraction::ZERO
+            };
+            let mut context = VoiceContext {
+                divisions,
+                number: (number * voices + 1).to_string(),
+                staff: if staves.len() > 1 {
+                    Some((number + 1).to_string())
+                } else {
+                    None
+                },
+                key: staff.key_signature_at(index),
+            };
+
+            if position > Fraction::ZERO {
+                measure.push(backup(position, divisions));
+            }
+            if contents.voices().is_empty() {
+                let mut rest = Element::new("note")
+                    .with_child(Element::new("rest").with_attribute("measure", "yes"))
+                    .with_child(text_element(
+                        "duration",
+                        &ticks(meter.measure_length(), divisions),
+                    ))
+                    .with_child(text_element("voice", &context.number));
+                if let Some(staff) = &context.staff {
+                    rest.push(text_element("staff", staff));
+                }
+                measure.push(rest);
+                position = meter.measure_length();
+            }
+            for (n, voice) in contents.voices().iter().enumerate() {
+                if n > 0 {
+                    measure.push(backup(position, divisions));
+                }
+                context.number = (number * voices + n + 1).to_string();
+                write_voice(&mut measure, voice, &context, &meter, shift)?;
+                position = voice.length();
+            }
+        }
+        element.push(measure);
+    }
+    Ok(element)
+}
+
+fn backup(length: Fraction, divisions: i32) -> Element {
+    Element::new("backup").with_child(text_element("duration", &ticks(length, divisions)))
+}
+
+// Attributes are written in full for the first measure and as changes afterwards.
+fn write_attributes(staves: &[Staff], index: usize, divisions: i32) -> Option<Element> {
+    let first = index == 0;
+    let measures: Vec<Option<&Measure>> = staves
+        .iter()
+        .map(|staff| staff.measures().get(index))
+        .collect();
+    let numbered = |element: Element, staff: usize| {
+        if staves.len() > 1 {
+            element.with_attribute("number", &(staff + 1).to_string())
+        } else {
+            element
+        }
+    };
+
+    let mut children = Vec::new();
+    if first {
+        children.push(text_element("divisions", &divisions.to_string()));
+    }
+
+    let keys: Vec<Option<(KeySignature, Option<Mode>)>> = staves
+        .iter()
+        .zip(measures.iter())
+        .map(|(staff, measure)| match measure {
+            _ if first => Some((
+                staff.key_signature_at(0),
+                staff.measures().first().and_then(Measure::mode),
+            )),
+            Some(measure) => measure.key_signature().map(|key| (key, measure.mode())),
+            None => None,
+        })
+        .collect();
+    if keys.iter().all(|key| key.is_some() && *key == keys[0]) {
+        let (key, mode) = keys[0].unwrap();
+        children.push(key_element(&key, mode));
+    } else {
+        for (staff, key) in keys.iter().enumerate() {
+            if let Some((key, mode)) = key {
+                children.push(numbered(key_element(key, *mode), staff));
+            }
+        }
+    }
+
+    let time = if first {
+        Some(staves[0].time_signature_at(0))
+    } else {
+        measures
+            .iter()
+            .flatten()
+            .find_map(|measure| measure.time_signature().cloned())
+    };
+    if let Some(time) = time {
+        let beats: Vec<String> = time.groups().iter().map(u16::to_string).collect();
+        children.push(
+            Element::new("time")
+                .with_child(text_element("beats", &beats.join("+")))
+                .with_child(text_element("beat-type", &time.denominator().to_string())),
+        );
+    }
+
+    if first && staves.len() > 1 {
+        children.push(text_element("staves", &staves.len().to_string()));
+    }
+
+    for (index, (staff, measure)) in staves.iter().zip(measures.iter()).enumerate() {
+        let clef = match measure {
+            _ if first => Some(staff.clef_at(0)),
+            Some(measure) => measure.clef(),
+            None => None,
+        };
+        if let Some(clef) = clef {
+            children.push(numbered(clef_element(&clef), index));
+        }
+    }
+
+    if children.is_empty() {
+        return None;
+    }
+    let mut attributes = Element::new("attributes");
+    for child in children {
+        attributes.push(child);
+    }
+    Some(attributes)
+}
+
+fn key_element(signature: &KeySignature, mode: Option<Mode>) -> Element {
+    let mut key = Element::new("key");
+    match signature.fifths() {
+        Some(fifths) => {
+            key.push(text_element("fifths", &fifths.to_string()));
+            if let Some(mode) = mode {
+                key.push(text_element("mode", &mode.to_string()));
+            }
+        }
+        None => {
+            for name in [F, C, G, D, A, E, B].iter() {
+                let accidental = signature.accidental(*name);
+                if accidental != NATURAL {
+                    key.push(text_element("key-step", &name.to_string()));
+                    key.push(text_element("key-alter", &alter(accidental)));
+                }
+            }
+        }
+    }
+    key
+}
+
+fn clef_element(clef: &Clef) -> Element {
+    let sign = match clef.sign() {
+        ClefSign::G => "G",
+        ClefSign::F => "F",
+        ClefSign::C => "C",
+        ClefSign::Percussion => "percussion",
+        ClefSign::Tab => "TAB",
+    };
+    let mut element = Element::new("clef")
+        .with_child(text_element("sign", sign))
+        .with_child(text_element("line", &clef.line().to_string()));
+    if clef.octave_change() != 0 {
+        element.push(text_element(
+            "clef-octave-change",
+            &clef.octave_change().to_string(),
+        ));
+    }
+    element
+}
+
+fn alter(accidental: Accidental) -> String {
+    (accidental.cents() as f64 / 100.0).to_string()
+}
+
+fn pitch_element(pitch: &Pitch) -> Result<Element, &'static str> {
+    if pitch.octave() < 0 {
+        return Err("MusicXML octaves start at 0");
+    }
+    let mut element =
+        Element::new("pitch").with_child(text_element("step", &pitch.name().to_string()));
+    if pitch.accidental() != NATURAL {
+        element.push(text_element("alter", &alter(pitch.accidental())));
+    }
+    Ok(element.with_child(text_element("octave", &pitch.octave().to_string())))
+}
+
+fn type_name(denominator: u16) -> Result<&'static str, &'static str> {
+    Ok(match denominator {
+        1 => "whole",
+        2 => "half",
+        4 => "quarter",
+        8 => "eighth",
+        16 => "16th",
+        32 => "32nd",
+        64 => "64th",
+        128 => "128th",
+        256 => "256th",
+        512 => "512th",
+        1024 => "1024th",
+        _ => return Err("duration too short for MusicXML"),
+    })
+}
+
+fn accidental_name(accidental: Accidental) -> Option<&'static str> {
+    Some(match accidental.cents() {
+        0 => "natural",
+        100 => "sharp",
+        -100 => "flat",
+        200 => "double-sharp",
+        -200 => "flat-flat",
+        50 => "quarter-sharp",
+        -50 => "quarter-flat",
+        150 => "three-quarters-sharp",
+        -150 => "three-quarters-flat",
+        _ => return None,
+    })
+}
+
+fn articulation_name(articulation: Articulation) -> Option<&'static str> {
+    match articulation {
+        Articulation::Staccato => Some("staccato"),
+        Articulation::Staccatissimo => Some("staccatissimo"),
+        Articulation::Tenuto => Some("tenuto"),
+        Articulation::Accent => Some("accent"),
+        Articulation::Marcato => Some("strong-accent"),
+        Articulation::Fermata => None,
+    }
+}
+
+// Beams notes of an eighth or shorter that fall within the same beat, with
+// secondary beams and hooks for the shorter values.
+fn beams(
+    events: &[Event],
+    offsets: &[Fraction],
+    meter: &TimeSignature,
+) -> Vec<Vec<(u32, &'static str)>> {
+    let mut bounds = meter.beat_offsets();
+    bounds.push(meter.measure_length());
+    let beat = |index: usize| {
+        let event = &events[index];
+        if event.is_rest() || event.duration().denominator() < 8 {
+            return None;
+        }
+        let beat = bounds.iter().rposition(|bound| *bound <= offsets[index])?;
+        match bounds.get(beat + 1) {
+            Some(end) if offsets[index] + event.length() <= *end => Some(beat),
+            _ => None,
+        }
+    };
+    let level = |index: usize| events[index].duration().denominator().trailing_zeros() - 2;
+
+    let mut beams = vec![Vec::new(); events.len()];
+    let mut start = 0;
+    while start < events.len() {
+        let group = beat(start);
+        let mut end = start + 1;
+        while group.is_some() && end < events.len() && beat(end) == group {
+            end += 1;
+        }
+        if end - start > 1 {
+            let deepest = (start..end).map(level).max().unwrap();
+            for depth in 1..=deepest {
+                let mut i = start;
+                while i < end {
+                    if level(i) < depth {
+                        i += 1;
+                        continue;
+                    }
+                    let mut j = i + 1;
+                    while j < end && level(j) >= depth {
+                        j += 1;
+                    }
+                    if j - i == 1 {
+                        let hook = if i + 1 < end {
+                            "forward hook"
+                        } else {
+                            "backward hook"
+                        };
+                        beams[i].push((depth, hook));
+                    } else {
+                        for (k, beam) in beams[i..j].iter_mut().enumerate() {
+                            let kind = match k {
+                                0 => "begin",
+                                _ if k == j - i - 1 => "end",
+                                _ => "continue",
+                            };
+                            beam.push((depth, kind));
+                        }
+                    }
+                    i = j;
+                }
+            }
+        }
+        start = end;
+    }
+    beams
+}
+
+// Brackets runs of notes sharing a tuplet, closing each bracket once it
+// spans `actual` of the run's shortest written value.
+fn tuplets(events: &[Event]) -> Vec<(bool, bool)> {
+    let mut marks = vec![(false, false); events.len()];
+    let mut open: Option<(Tuplet, Fraction, Fraction)> = None;
+    for (index, event) in events.iter().enumerate() {
+        let tuplet = event.duration().tuplet();
+        if let Some((current, _, _)) = open {
+            if Some(current) != tuplet {
+                marks[index - 1].1 = true;
+                open = None;
+            }
+        }
+        let tuplet = match tuplet {
+            Some(tuplet) => tuplet,
+            None => continue,
+        };
+
+        let (_, written, shortest) = open.get_or_insert_with(|| {
+            marks[index].0 = true;
+            (tuplet, Fraction::ZERO, Fraction::ONE)
+        });
+        *written += event.length() / tuplet.ratio();
+        *shortest = std::cmp::min(
+            *shortest,
+            Fraction::new(1, event.duration().denominator() as i32),
+        );
+        let span = *shortest * tuplet.actual() as i32;
+        if (*written / span).denominator() == 1 {
+            marks[index].1 = true;
+            open = None;
+        }
+    }
+    if open.is_some() {
+        marks[events.len() - 1].1 = true;
+    }
+    marks
+}
+
+// What every note written for one voice of a measure shares.
+struct VoiceContext {
+    divisions: i32,
+    number: String,
+    staff: Option<String>,
+    key: KeySignature,
+}
+
+fn direction(dynamics: Dynamic, context: &VoiceContext) -> Element {
+    let name = DYNAMICS
+        .iter()
+        .find(|(_, dynamic)| *dynamic == dynamics)
+        .map(|(name, _)| *name)
+        .unwrap();
+    let mut direction = Element::new("direction")
+        .with_attribute("placement", "below")
+        .with_child(
+            Element::new("direction-type")
+                .with_child(Element::new("dynamics").with_child(Element::new(name))),
+        )
+        .with_child(text_element("voice", &context.number));
+    if let Some(staff) = &context.staff {
+        direction.push(text_element("staff", staff));
+    }
+    // Sound dynamics are a percentage of the velocity of forte, taken as 90.
+    let percentage = u32::from(dynamics.velocity()) * 100 / 90;
+    direction.push(Element::new("sound").with_attribute("dynamics", &percentage.to_string()));
+    direction
+}
+
+fn write_voice(
+    measure: &mut Element,
+    voice: &Voice,
+    context: &VoiceContext,
+    meter: &TimeSignature,
+    shift: Fraction,
+) -> Result<(), &'static str> {
+    let events = voice.events();
+    let offsets: Vec<Fraction> = voice.offsets().iter().map(|o| *o + shift).collect();
+    let beams = beams(events, &offsets, meter);
+    let tuplets = tuplets(events);
+    // Accidentals already shown in this measure, which hold until the barline.
+    let mut shown: Vec<(NoteName, i32, Accidental)> = Vec::new();
+
+    for (index, event) in events.iter().enumerate() {
+        let duration = event.duration();
+        let (pitches, tie, articulations, dynamics) = match event {
+            Event::Note(note) => (
+                vec![Some(note.pitch())],
+                note.tie(),
+                note.articulations(),
+                note.dynamics(),
+            ),
+            Event::Chord(chord) => (
+                chord.pitches().iter().copied().map(Some).collect(),
+                chord.tie(),
+                chord.articulations(),
+                chord.dynamics(),
+            ),
+            Event::Rest(_) => (vec![None], None, &[][..], None),
+        };
+        if let Some(dynamics) = dynamics {
+            measure.push(direction(dynamics, context));
+        }
+        let ties: &[&str] = match tie {
+            Some(Tie::Start) => &["start"],
+            Some(Tie::Stop) => &["stop"],
+            Some(Tie::Continue) => &["stop", "start"],
+            None => &[],
+        };
+
+        for (n, pitch) in pitches.into_iter().enumerate() {
+            let mut note = Element::new("note");
+            if n > 0 {
+                note.push(Element::new("chord"));
+            }
+            note.push(match &pitch {
+                Some(pitch) => pitch_element(pitch)?,
+                None => Element::new("rest"),
+            });
+            note.push(text_element(
+                "duration",
+                &ticks(event.length(), context.divisions),
+            ));
+            for kind in ties.iter() {
+                note.push(Element::new("tie").with_attribute("type", kind));
+            }
+            note.push(text_element("voice", &context.number));
+            note.push(text_element("type", type_name(duration.denominator())?));
+            for _ in 0..duration.dots() {
+                note.push(Element::new("dot"));
+            }
+            if let Some(pitch) = pitch {
+                let current = shown
+                    .iter()
+                    .rev()
+                    .find(|(name, octave, _)| *name == pitch.name() && *octave == pitch.octave())
+                    .map(|(_, _, accidental)| *accidental)
+                    .unwrap_or_else(|| context.key.accidental(pitch.name()));
+                let tied = matches!(tie, Some(Tie::Stop) | Some(Tie::Continue));
+                if pitch.accidental() != current && !tied {
+                    if let Some(name) = accidental_name(pitch.accidental()) {
+                        note.push(text_element("accidental", name));
+                    }
+                }
+                shown.push((pitch.name(), pitch.octave(), pitch.accidental()));
+            }
+            if let Some(tuplet) = duration.tuplet() {
+                note.push(
+                    Element::new("time-modification")
+                        .with_child(text_element("actual-notes", &tuplet.actual().to_string()))
+                        .with_child(text_element("normal-notes", &tuplet.normal().to_string())),
+                );
+            }
+            if let Some(staff) = &context.staff {
+                note.push(text_element("staff", staff));
+            }
+
+            let mut notations: Vec<Element> = ties
+                .iter()
+                .map(|kind| Element::new("tied").with_attribute("type", kind))
+                .collect();
+            if n == 0 {
+                for (depth, kind) in beams[index].iter() {
+                    note.push(
+                        Element::new("beam")
+                            .with_attribute("number", &depth.to_string())
+                            .with_text(kind),
+                    );
+                }
+                let (start, stop) = tuplets[index];
+                if start {
+                    notations.push(Element::new("tuplet").with_attribute("type", "start"));
+                }
+                if stop {
+                    notations.push(Element::new("tuplet").with_attribute("type", "stop"));
+                }
+                let marks: Vec<&str> = articulations
+                    .iter()
+                    .filter_map(|articulation| articulation_name(*articulation))
+                    .collect();
+                if !marks.is_empty() {
+                    let mut group = Element::new("articulations");
+                    for mark in marks {
+                        group.push(Element::new(mark));
+                    }
+                    notations.push(group);
+                }
+                if articulations.contains(&Articulation::Fermata) {
+                    notations.push(Element::new("fermata"));
+                }
+            }
+            if !notations.is_empty() {
+                let mut element = Element::new("notations");
+                for notation in notations {
+                    element.push(notation);
+                }
+                note.push(element);
+            }
+            measure.push(note);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::music::pitch::*;
+    use crate::music::{FLAT, HALF_FLAT, SHARP};
+
+    const PARTWISE: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?>
+<!DOCTYPE score-partwise PUBLIC "-//Recordare//DTD MusicXML 4.0 Partwise//EN"
+  "http://www.musicxml.org/dtds/partwise.dtd">
+<score-partwise version="4.0">
+  <work><work-title>Study</work-title></work>
+  <part-list>
+    <score-part id="P1"><part-name>Piano</part-name></score-part>
+  </part-list>
+  <part id="P1">
+    <measure number="1">
+      <attributes>
+        <divisions>6</divisions>
+        <key><fifths>-3</fifths><mode>minor</mode></key>
+        <time><beats>3</beats><beat-type>4</beat-type></time>
+        <staves>2</staves>
+        <clef number="1"><sign>G</sign><line>2</line></clef>
+        <clef number="2"><sign>F</sign><line>4</line></clef>
+      </attributes>
+      <direction placement="below">
+        <direction-type><dynamics><mf/></dynamics></direction-type>
+        <staff>1</staff>
+      </direction>
+      <note>
+        <pitch><step>C</step><octave>4</octave></pitch>
+        <duration>6</duration><voice>1</voice><type>quarter</type><staff>1</staff>
+        <notations><articulations><staccato/></articulations></notations>
+      </note>
+      <note>
+        <pitch><step>D</step><alter>1</alter><octave>4</octave></pitch>
+        <duration>12</duration><tie type="start"/><voice>1</voice><type>half</type>
+        <staff>1</staff>
+      </note>
+      <backup><duration>18</duration></backup>
+      <note>
+        <pitch><step>C</step><octave>3</octave></pitch>
+        <duration>18</duration><voice>5</voice><type>half</type><dot/><staff>2</staff>
+      </note>
+    </measure>
+    <measure number="2">
+      <note>
+        <pitch><step>D</step><alter>1</alter><octave>4</octave></pitch>
+        <duration>6</duration><tie type="stop"/><voice>1</voice><type>quarter</type>
+        <staff>1</staff>
+      </note>
+      <note>
+        <pitch><step>E</step><alter>-0.5</alter><octave>4</octave></pitch>
+        <duration>2</duration><voice>1</voice><type>eighth</type>
+        <time-modification><actual-notes>3</actual-notes><normal-notes>2</normal-notes></time-modification>
+        <staff>1</staff>
+      </note>
+      <note>
+        <grace/><pitch><step>G</step><octave>4</octave></pitch>
+        <voice>1</voice><type>16th</type><staff>1</staff>
+      </note>
+      <note>
+        <rest/><duration>4</duration><voice>1</voice><type>quarter</type>
+        <time-modification><actual-notes>3</actual-notes><normal-notes>2</normal-notes></time-modification>
+        <staff>1</staff>
+      </note>
+      <note>
+        <pitch><step>C</step><octave>5</octave></pitch>
+        <duration>6</duration><voice>1</voice><type>quarter</type><staff>1</staff>
+      </note>
+      <note>
+        <chord/><pitch><step>E</step><octave>5</octave></pitch>
+        <duration>6</duration><voice>1</voice><type>quarter</type><staff>1</staff>
+      </note>
+      <backup><duration>18</duration></backup>
+      <forward><duration>6</duration><voice>2</voice><staff>1</staff></forward>
+      <note>
+        <pitch><step>G</step><octave>3</octave></pitch>
+        <duration>6</duration><voice>2</voice><type>quarter</type><staff>1</staff>
+      </note>
+      <backup><duration>12</duration></backup>
+      <note>
+        <rest measure="yes"/><duration>18</duration><voice>5</voice><staff>2</staff>
+      </note>
+    </measure>
+  </part>
+</score-partwise>"#;
+
+    const TIMEWISE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<score-timewise version="4.0">
+  <movement-title>Round</movement-title>
+  <part-list>
+    <score-part id="P1"><part-name>Flute</part-name></score-part>
+    <score-part id="P2"><part-name>Cello</part-name></score-part>
+  </part-list>
+  <measure number="0" implicit="yes">
+    <part id="P1">
+      <attributes>
+        <divisions>1</divisions>
+        <key><fifths>0</fifths></key>
+        <time><beats>3+3+2</beats><beat-type>8</beat-type></time>
+        <clef><sign>G</sign><line>2</line></clef>
+      </attributes>
+      <note><pitch><step>G</step><octave>5</octave></pitch><duration>1</duration><type>quarter</type></note>
+    </part>
+    <part id="P2">
+      <attributes>
+        <divisions>2</divisions>
+        <key><key-step>F</key-step><key-alter>1</key-alter><key-step>B</key-step><key-alter>-1</key-alter></key>
+        <time><beats>3+3+2</beats><beat-type>8</beat-type></time>
+        <clef><sign>F</sign><line>4</line></clef>
+      </attributes>
+      <note><rest/><duration>2</duration><type>quarter</type></note>
+    </part>
+  </measure>
+  <measure number="1">
+    <part id="P1">
+      <note><pitch><step>C</step><octave>6</octave></pitch><duration>4</duration><type>whole</type></note>
+    </part>
+    <part id="P2">
+      <note><pitch><step>C</step><octave>3</octave></pitch><duration>8</duration><type>whole</type></note>
+    </part>
+  </measure>
+</score-timewise>"#;
+
+    fn archive(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut directory = Vec::new();
+        for (name, contents) in files.iter() {
+            let offset = bytes.len() as u32;
+            let size = (contents.len() as u32).to_le_bytes();
+            let name_length = (name.len() as u16).to_le_bytes();
+            bytes.extend_from_slice(&[0x50, 0x4b, 0x03, 0x04, 20, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+            bytes.extend_from_slice(&[0, 0, 0, 0]);
+            bytes.extend_from_slice(&size);
+            bytes.extend_from_slice(&size);
+            bytes.extend_from_slice(&name_length);
+            bytes.extend_from_slice(&[0, 0]);
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.extend_from_slice(contents.as_bytes());
+
+            directory.extend_from_slice(&[0x50, 0x4b, 0x01, 0x02, 20, 0, 20, 0, 0, 0, 0, 0]);
+            directory.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0]);
+            directory.extend_from_slice(&size);
+            directory.extend_from_slice(&size);
+            directory.extend_from_slice(&name_length);
+            directory.extend_from_slice(&[0; 12]);
+            directory.extend_from_slice(&offset.to_le_bytes());
+            directory.extend_from_slice(name.as_bytes());
+        }
+        let offset = (bytes.len() as u32).to_le_bytes();
+        let count = (files.len() as u16).to_le_bytes();
+        let size = (directory.len() as u32).to_le_bytes();
+        bytes.extend(directory);
+        bytes.extend_from_slice(&[0x50, 0x4b, 0x05, 0x06, 0, 0, 0, 0]);
+        bytes.extend_from_slice(&count);
+        bytes.extend_from_slice(&count);
+        bytes.extend_from_slice(&size);
+        bytes.extend_from_slice(&offset);
+        bytes.extend_from_slice(&[0, 0]);
+        bytes
+    }
+
+    #[test]
+    fn test_read_partwise() {
+        let score = read(PARTWISE).unwrap();
+        assert_eq!(Some("Study"), score.title());
+        assert_eq!(Ok(()), score.validate());
+        let part = &score.parts()[0];
+        assert_eq!("Piano", part.name());
+        assert_eq!(2, part.staves().len());
+
+        let upper = &part.staves()[0].measures();
+        let lower = &part.staves()[1].measures();
+        assert_eq!(Some(&TimeSignature::new(3, 4)), upper[0].time_signature());
+        assert_eq!(Some(KeySignature::new(-3)), upper[0].key_signature());
+        assert_eq!(Some(Mode::Minor), upper[0].mode());
+        assert_eq!(Some(Clef::TREBLE), upper[0].clef());
+        assert_eq!(Some(Clef::BASS), lower[0].clef());
+        assert_eq!(None, upper[1].time_signature());
+
+        assert_eq!(
+            vec![
+                Event::from(
+                    Note::new(C4, Duration::new(4))
+                        .with_articulation(Articulation::Staccato)
+                        .with_dynamics(Dynamic::Mf)
+                ),
+                Note::new(Ds4, Duration::new(2)).with_tie(Tie::Start).into(),
+            ],
+            upper[0].voices()[0].events()
+        );
+        assert_eq!(
+            vec![Event::from(Note::new(C3, Duration::new_with_dots(2, 1)))],
+            lower[0].voices()[0].events()
+        );
+
+        let triplet = Tuplet::new(3, 2);
+        assert_eq!(
+            vec![
+                Event::from(Note::new(Ds4, Duration::new(4)).with_tie(Tie::Stop)),
+                Note::new(
+                    Pitch::new_with_accidental(E, HALF_FLAT, 4),
+                    Duration::new(8).with_tuplet(triplet)
+                )
+                .into(),
+                Rest::new(Duration::new(4).with_tuplet(triplet)).into(),
+                ChordNote::new(vec![C5, E5], Duration::new(4)).into(),
+            ],
+            upper[1].voices()[0].events()
+        );
+        assert_eq!(
+            vec![
+                Event::from(Rest::new(Duration::new(4))),
+                Note::new(G3, Duration::new(4)).into(),
+                Rest::new(Duration::new(4)).into(),
+            ],
+            upper[1].voices()[1].events()
+        );
+        assert_eq!(
+            vec![Event::from(Rest::new(Duration::new_with_dots(2, 1)))],
+            lower[1].voices()[0].events()
+        );
+    }
+
+    #[test]
+    fn test_read_timewise() {
+        let score = read(TIMEWISE).unwrap();
+        assert_eq!(Some("Round"), score.title());
+        assert_eq!(Ok(()), score.validate());
+        let names: Vec<&str> = score.parts().iter().map(Part::name).collect();
+        assert_eq!(vec!["Flute", "Cello"], names);
+
+        let flute = score.parts()[0].staves()[0].measures();
+        assert!(flute[0].is_pickup());
+        assert_eq!(
+            Some(&TimeSignature::additive(&[3, 3, 2], 8)),
+            flute[0].time_signature()
+        );
+        assert_eq!(
+            vec![Event::from(Note::new(G5, Duration::new(4)))],
+            flute[0].voices()[0].events()
+        );
+        assert_eq!(
+            vec![Event::from(Note::new(C6, Duration::new(1)))],
+            flute[1].voices()[0].events()
+        );
+
+        let cello = score.parts()[1].staves()[0].measures();
+        assert_eq!(Some(Clef::BASS), cello[0].clef());
+        assert_eq!(
+            Some(KeySignature::from_accidentals(&[(F, SHARP), (B, FLAT)])),
+            cello[0].key_signature()
+        );
+        assert_eq!(
+            vec![Event::from(Note::new(C3, Duration::new(1)))],
+            cello[1].voices()[0].events()
+        );
+    }
+
+    #[test]
+    fn test_read_compressed() {
+        let container = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container>
+  <rootfiles>
+    <rootfile full-path="scores/round.musicxml" media-type="application/vnd.recordare.musicxml+xml"/>
+  </rootfiles>
+</container>"#;
+        let bytes = archive(&[
+            ("mimetype", "application/vnd.recordare.musicxml"),
+            ("META-INF/container.xml", container),
+            ("other.xml", PARTWISE),
+            ("scores/round.musicxml", TIMEWISE),
+        ]);
+        assert_eq!(Some("Round"), read_compressed(&bytes).unwrap().title());
+
+        let bytes = archive(&[("study.musicxml", PARTWISE)]);
+        assert_eq!(Some("Study"), read_compressed(&bytes).unwrap().title());
+
+        let bytes = archive(&[("META-INF/container.xml", container)]);
+        assert!(read_compressed(&bytes).is_err());
+        assert!(read_compressed(PARTWISE.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_read_errors() {
+        assert!(read("<score-partwise>").is_err());
+        assert!(read("<opus/>").is_err());
+        let missing = PARTWISE.replace("<step>C</step><octave>4</octave>", "<step>C</step>");
+        assert!(read(&missing).is_err());
+        let divisions = PARTWISE.replace("<divisions>6</divisions>", "<divisions>0</divisions>");
+        assert!(read(&divisions).is_err());
+        let divisions = PARTWISE.replace(
+            "<divisions>6</divisions>",
+            "<divisions>1073741824</divisions>",
+        );
+        assert_eq!(Err("too many divisions per quarter note"), read(&divisions));
+        let duration = PARTWISE.replacen(
+            "<duration>6</duration>",
+            "<duration>2147483647</duration>",
+            1,
+        );
+        assert_eq!(Err("duration out of range"), read(&duration));
+        let fifths = PARTWISE.replace("<fifths>-3</fifths>", "<fifths>8</fifths>");
+        assert_eq!(Err("key fifths out of range"), read(&fifths));
+    }
+    #[test]
+    fn test_write_round_trip() {
+        let score = read(PARTWISE).unwrap();
+        let written = write(&score).unwrap();
+        assert_eq!(score, read(&written).unwrap());
+        assert!(written.starts_with("<?xml"));
+        assert!(written.contains("<divisions>3</divisions>"));
+        assert!(written.contains("<mode>minor</mode>"));
+        assert_eq!(1, written.matches("<accidental>sharp</accidental>").count());
+        assert!(written.contains("<accidental>quarter-flat</accidental>"));
+        assert!(written.contains("<clef number=\"2\">"));
+
+        let score = read(TIMEWISE).unwrap();
+        let written = write(&score).unwrap();
+        assert_eq!(score, read(&written).unwrap());
+        assert!(written.contains("<measure number=\"0\" implicit=\"yes\">"));
+        assert!(written.contains("<beats>3+3+2</beats>"));
+    }
+
+    #[test]
+    fn test_write_beams() {
+        let sixteenth = Note::new(G4, Duration::new(16));
+        let events: Vec<Event> = vec![
+            Note::new(C4, Duration::new(8)).into(),
+            Note::new(D4, Duration::new(8)).into(),
+            Note::new(E4, Duration::new_with_dots(8, 1)).into(),
+            Note::new(F4, Duration::new(16)).into(),
+            sixteenth.clone().into(),
+            sixteenth.clone().into(),
+            sixteenth.clone().into(),
+            sixteenth.into(),
+            Rest::new(Duration::new(4)).into(),
+        ];
+        let measure = Measure::new(vec![Voice::new(events)]);
+        let score = Score::new(vec![Part::new("Solo", vec![Staff::new(vec![measure])])]);
+
+        let root = xml::parse(&write(&score).unwrap()).unwrap();
+        let measure = root.child("part").unwrap().child("measure").unwrap();
+        let attributes = measure.child("attributes").unwrap();
+        assert_eq!(Some("4".to_string()), attributes.child_text("divisions"));
+        let beams: Vec<Vec<String>> = measure
+            .children("note")
+            .map(|note| {
+                note.children("beam")
+                    .map(|beam| format!("{} {}", beam.attribute("number").unwrap(), beam.text()))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                vec!["1 begin"],
+                vec!["1 end"],
+                vec!["1 begin"],
+                vec!["1 end", "2 backward hook"],
+                vec!["1 begin", "2 begin"],
+                vec!["1 continue", "2 continue"],
+                vec!["1 continue", "2 continue"],
+                vec!["1 end", "2 end"],
+                vec![],
+            ],
+            beams
+        );
+    }
+
+    #[test]
+    fn test_write_tuplets() {
+        let triplet = Duration::new(8).with_tuplet(Tuplet::TRIPLET);
+        let events: Vec<Event> = vec![
+            ChordNote::new(vec![C4, E4], triplet).into(),
+            Note::new(D4, triplet).into(),
+            Note::new(E4, triplet).into(),
+            Note::new(F4, Duration::new(4)).into(),
+        ];
+        let measure =
+            Measure::new(vec![Voice::new(events)]).with_time_signature(TimeSignature::new(2, 4));
+        let score = Score::new(vec![Part::new("Solo", vec![Staff::new(vec![measure])])]);
+        let written = write(&score).unwrap();
+        let read_back = read(&written).unwrap();
+        assert_eq!(
+            score.parts()[0].staves()[0].measures()[0].voices(),
+            read_back.parts()[0].staves()[0].measures()[0].voices()
+        );
+
+        let root = xml::parse(&written).unwrap();
+        let measure = root.child("part").unwrap().child("measure").unwrap();
+        let attributes = measure.child("attributes").unwrap();
+        assert_eq!(Some("3".to_string()), attributes.child_text("divisions"));
+        let notes: Vec<&Element> = measure.children("note").collect();
+        assert_eq!(5, notes.len());
+        assert!(notes[1].has("chord"));
+        assert_eq!(Some("1".to_string()), notes[0].child_text("duration"));
+        let tuplets: Vec<Option<&str>> = notes
+            .iter()
+            .map(|note| {
+                note.child("notations")
+                    .and_then(|notations| notations.child("tuplet"))
+                    .and_then(|tuplet| tuplet.attribute("type"))
+            })
+            .collect();
+        assert_eq!(vec![Some("start"), None, None, Some("stop"), None], tuplets);
+    }
+}
@@ -0,0 +1,372 @@
+// A small XML reader and writer covering what music interchange formats use:
+// elements, attributes, text, CDATA, comments, processing instructions,
+// doctype declarations and the predefined and numeric character entities.
+
+#[derive(Clone, PartialEq, Debug)]
+pub(crate) enum Node {
+    Element(Element),
+    Text(String),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub(crate) struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Node>,
+}
+
+impl Element {
+    pub(crate) fn new(name: &str) -> Element {
+        Element {
+            name: name.to_string(),
+            attributes: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    pub(crate) fn with_attribute(mut self, name: &str, value: &str) -> Element {
+        self.attributes.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub(crate) fn with_child(mut self, child: Element) -> Element {
+        self.children.push(Node::Element(child));
+        self
+    }
+
+    pub(crate) fn with_text(mut self, text: &str) -> Element {
+        self.children.push(Node::Text(text.to_string()));
+        self
+    }
+
+    pub(crate) fn with_children_of(mut self, other: &Element) -> Element {
+        self.children.extend(other.children.iter().cloned());
+        self
+    }
+
+    pub(crate) fn push(&mut self, child: Element) {
+        self.children.push(Node::Element(child));
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) fn attributes(&self) -> &[(String, String)] {
+        &self.attributes
+    }
+
+    pub(crate) fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub(crate) fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|node| match node {
+            Node::Element(element) => Some(element),
+            Node::Text(_) => None,
+        })
+    }
+
+    pub(crate) fn child(&self, name: &str) -> Option<&Element> {
+        self.elements().find(|element| element.name == name)
+    }
+
+    pub(crate) fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.elements().filter(move |element| element.name == name)
+    }
+
+    pub(crate) fn has(&self, name: &str) -> bool {
+        self.child(name).is_some()
+    }
+
+    pub(crate) fn text(&self) -> String {
+        let mut text = String::new();
+        for node in self.children.iter() {
+            match node {
+                Node::Text(value) => text += value,
+                Node::Element(element) => text += &element.text(),
+            }
+        }
+        text.trim().to_string()
+    }
+
+    pub(crate) fn child_text(&self, name: &str) -> Option<String> {
+        self.child(name).map(Element::text)
+    }
+
+    pub(crate) fn write(&self, out: &mut String, depth: usize) {
+        let indent = "  ".repeat(depth);
+        out.push_str(&indent);
+        out.push('<');
+        out.push_str(&self.name);
+        for (name, value) in self.attributes.iter() {
+            out.push_str(&format!(" {}=\"{}\"", name, escape(value)));
+        }
+        if self.children.is_empty() {
+            out.push_str("/>\n");
+            return;
+        }
+        out.push('>');
+        match self.children.as_slice() {
+            [Node::Text(text)] => out.push_str(&escape(text)),
+            children => {
+                out.push('\n');
+                for node in children.iter() {
+                    match node {
+                        Node::Element(element) => element.write(out, depth + 1),
+                        Node::Text(text) => {
+                            out.push_str(&"  ".repeat(depth + 1));
+                            out.push_str(&escape(text));
+                            out.push('\n');
+                        }
+                    }
+                }
+                out.push_str(&indent);
+            }
+        }
+        out.push_str(&format!("</{}>\n", self.name));
+    }
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(text: &str) -> Result<String, &'static str> {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        let end = rest[start..].find(';').ok_or("unterminated entity")? + start;
+        let entity = &rest[start + 1..end];
+        let c = match entity {
+            "lt" => '<',
+            "gt" => '>',
+            "amp" => '&',
+            "quot" => '"',
+            "apos" => '\'',
+            _ if entity.starts_with("#x") => u32::from_str_radix(&entity[2..], 16)
+                .ok()
+                .and_then(std::char::from_u32)
+                .ok_or("invalid character reference")?,
+            _ if entity.starts_with('#') => entity[1..]
+                .parse::<u32>()
+                .ok()
+                .and_then(std::char::from_u32)
+                .ok_or("invalid character reference")?,
+            _ => return Err("unknown entity"),
+        };
+        unescaped.push(c);
+        rest = &rest[end + 1..];
+    }
+    unescaped.push_str(rest);
+    Ok(unescaped)
+}
+
+pub(crate) fn parse(input: &str) -> Result<Element, &'static str> {
+    let mut parser = Parser {
+        rest: input.trim_start_matches('\u{feff}'),
+    };
+    parser.skip_misc()?;
+    let root = parser.element()?;
+    parser.skip_misc()?;
+    if !parser.rest.is_empty() {
+        return Err("content after root element");
+    }
+    Ok(root)
+}
+
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_whitespace(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+
+    fn skip_past(&mut self, end: &str) -> Result<&'a str, &'static str> {
+        let index = self.rest.find(end).ok_or("unterminated markup")?;
+        let skipped = &self.rest[..index];
+        self.rest = &self.rest[index + end.len()..];
+        Ok(skipped)
+    }
+
+    // Skips whitespace, comments, processing instructions and doctype declarations.
+    fn skip_misc(&mut self) -> Result<(), &'static str> {
+        loop {
+            self.skip_whitespace();
+            if self.rest.starts_with("<?") {
+                self.skip_past("?>")?;
+            } else if self.rest.starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if self.rest.starts_with("<!DOCTYPE") {
+                let close = self.rest.find('>').ok_or("unterminated doctype")?;
+                match self.rest.find('[') {
+                    Some(open) if open < close => {
+                        self.skip_past("]")?;
+                        self.skip_past(">")?;
+                    }
+                    _ => {
+                        self.skip_past(">")?;
+                    }
+                }
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<&'a str, &'static str> {
+        let end = self
+            .rest
+            .find(|c: char| c.is_whitespace() || c == '>' || c == '/' || c == '=')
+            .unwrap_or(self.rest.len());
+        if end == 0 {
+            return Err("expected a name");
+        }
+        let name = &self.rest[..end];
+        self.rest = &self.rest[end..];
+        Ok(name)
+    }
+
+    fn element(&mut self) -> Result<Element, &'static str> {
+        if !self.rest.starts_with('<') {
+            return Err("expected an element");
+        }
+        self.rest = &self.rest[1..];
+        let mut element = Element::new(self.name()?);
+
+        loop {
+            self.skip_whitespace();
+            if self.rest.starts_with("/>") {
+                self.rest = &self.rest[2..];
+                return Ok(element);
+            }
+            if self.rest.starts_with('>') {
+                self.rest = &self.rest[1..];
+                break;
+            }
+            let name = self.name()?;
+            self.skip_whitespace();
+            if !self.rest.starts_with('=') {
+                return Err("expected '=' after attribute name");
+            }
+            self.rest = self.rest[1..].trim_start();
+            let quote = match self.rest.chars().next() {
+                Some(c) if c == '"' || c == '\'' => c,
+                _ => return Err("attribute value must be quoted"),
+            };
+            self.rest = &self.rest[1..];
+            let end = self
+                .rest
+                .find(quote)
+                .ok_or("unterminated attribute value")?;
+            let value = unescape(&self.rest[..end])?;
+            self.rest = &self.rest[end + 1..];
+            element.attributes.push((name.to_string(), value));
+        }
+
+        loop {
+            if self.rest.starts_with("</") {
+                self.rest = &self.rest[2..];
+                if self.name()? != element.name {
+                    return Err("mismatched closing tag");
+                }
+                self.skip_whitespace();
+                if !self.rest.starts_with('>') {
+                    return Err("expected '>'");
+                }
+                self.rest = &self.rest[1..];
+                return Ok(element);
+            } else if self.rest.starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if self.rest.starts_with("<![CDATA[") {
+                self.rest = &self.rest[9..];
+                let text = self.skip_past("]]>")?;
+                element.children.push(Node::Text(text.to_string()));
+            } else if self.rest.starts_with("<?") {
+                self.skip_past("?>")?;
+            } else if self.rest.starts_with('<') {
+                let child = self.element()?;
+                element.children.push(Node::Element(child));
+            } else if self.rest.is_empty() {
+                return Err("unexpected end of document");
+            } else {
+                let end = self.rest.find('<').unwrap_or(self.rest.len());
+                let text = unescape(&self.rest[..end])?;
+                self.rest = &self.rest[end..];
+                if !text.trim().is_empty() {
+                    element.children.push(Node::Text(text));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let document = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE score-partwise PUBLIC "-//Recordare//DTD MusicXML 4.0 Partwise//EN"
+  "http://www.musicxml.org/dtds/partwise.dtd">
+<!-- comment -->
+<root a="1" b='two &amp; three'>
+  <empty/>
+  <text>  R&amp;B &#233;&#x41; </text>
+  <!-- inner -->
+  <data><![CDATA[<raw>]]></data>
+  <nested><empty/><empty/></nested>
+</root>"#;
+        let root = parse(document).unwrap();
+        assert_eq!("root", root.name());
+        assert_eq!(Some("1"), root.attribute("a"));
+        assert_eq!(Some("two & three"), root.attribute("b"));
+        assert_eq!(None, root.attribute("c"));
+        assert_eq!(Some("R&B \u{e9}A".to_string()), root.child_text("text"));
+        assert_eq!(Some("<raw>".to_string()), root.child_text("data"));
+        assert_eq!(2, root.child("nested").unwrap().children("empty").count());
+        assert!(root.has("empty"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("<a><b></a>").is_err());
+        assert!(parse("<a>").is_err());
+        assert!(parse("<a b=c/>").is_err());
+        assert!(parse("<a>&nbsp;</a>").is_err());
+        assert!(parse("<a/><b/>").is_err());
+    }
+
+    #[test]
+    fn test_write() {
+        let element = Element::new("note")
+            .with_attribute("default-x", "1 < 2")
+            .with_child(Element::new("rest"))
+            .with_child(Element::new("type").with_text("quarter"));
+        let mut out = String::new();
+        element.write(&mut out, 0);
+        assert_eq!(
+            "<note default-x=\"1 &lt; 2\">\n  <rest/>\n  <type>quarter</type>\n</note>\n",
+            out
+        );
+        assert_eq!(element, parse(&out).unwrap());
+    }
+}
@@ -0,0 +1,349 @@
+// Reads entries out of zip archives, such as compressed MusicXML files.
+// Only stored and deflated entries are supported.
+
+pub(crate) struct Entry<'a> {
+    name: String,
+    method: u16,
+    size: usize,
+    data: &'a [u8],
+}
+
+impl<'a> Entry<'a> {
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) fn contents(&self) -> Result<Vec<u8>, &'static str> {
+        let contents = match self.method {
+            0 => self.data.to_vec(),
+            8 => inflate(self.data, self.size)?,
+            _ => return Err("unsupported zip compression method"),
+        };
+        if contents.len() != self.size {
+            return Err("zip entry has the wrong size");
+        }
+        Ok(contents)
+    }
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> Result<u16, &'static str> {
+    let data = bytes
+        .get(offset..offset + 2)
+        .ok_or("truncated zip archive")?;
+    Ok(u16::from_le_bytes([data[0], data[1]]))
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Result<u32, &'static str> {
+    let data = bytes
+        .get(offset..offset + 4)
+        .ok_or("truncated zip archive")?;
+    Ok(u32::from_le_bytes([data[0], data[1], data[2], data[3]]))
+}
+
+pub(crate) fn entries(bytes: &[u8]) -> Result<Vec<Entry<'_>>, &'static str> {
+    // The end of central directory record is 22 bytes plus a comment of up to 64KiB.
+    let earliest = bytes.len().saturating_sub(22 + 0xFFFF);
+    let end = (earliest..=bytes.len().saturating_sub(22))
+        .rev()
+        .find(|offset| u32_at(bytes, *offset) == Ok(0x0605_4b50))
+        .ok_or("not a zip archive")?;
+    let count = u16_at(bytes, end + 10)? as usize;
+    let mut offset = u32_at(bytes, end + 16)? as usize;
+
+    let mut entries = Vec::with_capacity(count);
+    for _ in 0..count {
+        if u32_at(bytes, offset)? != 0x0201_4b50 {
+            return Err("invalid zip central directory");
+        }
+        let method = u16_at(bytes, offset + 10)?;
+        let compressed = u32_at(bytes, offset + 20)? as usize;
+        let size = u32_at(bytes, offset + 24)? as usize;
+        let name_length = u16_at(bytes, offset + 28)? as usize;
+        let extra_length = u16_at(bytes, offset + 30)? as usize;
+        let comment_length = u16_at(bytes, offset + 32)? as usize;
+        let local = u32_at(bytes, offset + 42)? as usize;
+        let name = bytes
+            .get(offset + 46..offset + 46 + name_length)
+            .ok_or("truncated zip archive")?;
+
+        if u32_at(bytes, local)? != 0x0403_4b50 {
+            return Err("invalid zip local header");
+        }
+        let start =
+            local + 30 + u16_at(bytes, local + 26)? as usize + u16_at(bytes, local + 28)? as usize;
+        let data = bytes
+            .get(start..start + compressed)
+            .ok_or("truncated zip archive")?;
+
+        entries.push(Entry {
+            name: String::from_utf8_lossy(name).into_owned(),
+            method,
+            size,
+            data,
+        });
+        offset += 46 + name_length + extra_length + comment_length;
+    }
+    Ok(entries)
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+struct Bits<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl<'a> Bits<'a> {
+    fn take(&mut self, count: u32) -> Result<u32, &'static str> {
+        while self.count < count {
+            let byte = *self
+                .data
+                .get(self.position)
+                .ok_or("truncated deflate stream")?;
+            self.position += 1;
+            self.buffer |= (byte as u32) << self.count;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1u64 << count) - 1) as u32;
+        self.buffer >>= count;
+        self.count -= count;
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+// Canonical Huffman code, decoded one bit at a time.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0u16; 16];
+        for length in lengths.iter() {
+            counts[*length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut symbols = Vec::with_capacity(lengths.len());
+        for length in 1..16 {
+            for (symbol, l) in lengths.iter().enumerate() {
+                if *l as usize == length {
+                    symbols.push(symbol as u16);
+                }
+            }
+        }
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16, &'static str> {
+        let mut code = 0i32;
+        let mut first = 0i32;
+        let mut index = 0i32;
+        for length in 1..16 {
+            code |= bits.take(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - count < first {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid Huffman code")
+    }
+}
+
+// Stops with an error once the output would grow past `size`, the length the
+// archive declares for the entry.
+pub(crate) fn inflate(data: &[u8], size: usize) -> Result<Vec<u8>, &'static str> {
+    let mut bits = Bits {
+        data,
+        position: 0,
+        buffer: 0,
+        count: 0,
+    };
+    let mut out = Vec::new();
+
+    loop {
+        let last = bits.take(1)? == 1;
+        match bits.take(2)? {
+            0 => {
+                bits.align();
+                let header = data
+                    .get(bits.position..bits.position + 4)
+                    .ok_or("truncated deflate stream")?;
+                let length = u16::from_le_bytes([header[0], header[1]]) as usize;
+                if length != !u16::from_le_bytes([header[2], header[3]]) as usize {
+                    return Err("invalid stored block length");
+                }
+                let start = bits.position + 4;
+                let block = data
+                    .get(start..start + length)
+                    .ok_or("truncated deflate stream")?;
+                if out.len() + length > size {
+                    return Err("zip entry has the wrong size");
+                }
+                out.extend_from_slice(block);
+                bits.position = start + length;
+            }
+            1 => {
+                let mut lengths = [0u8; 288];
+                for (symbol, length) in lengths.iter_mut().enumerate() {
+                    *length = match symbol {
+                        0..=143 => 8,
+                        144..=255 => 9,
+                        256..=279 => 7,
+                        _ => 8,
+                    };
+                }
+                let literals = Huffman::new(&lengths);
+                let distances = Huffman::new(&[5; 30]);
+                inflate_block(&mut bits, &mut out, size, &literals, &distances)?;
+            }
+            2 => {
+                let literal_count = bits.take(5)? as usize + 257;
+                let distance_count = bits.take(5)? as usize + 1;
+                let code_count = bits.take(4)? as usize + 4;
+
+                let mut code_lengths = [0u8; 19];
+                for index in CODE_LENGTH_ORDER.iter().take(code_count) {
+                    code_lengths[*index] = bits.take(3)? as u8;
+                }
+                let codes = Huffman::new(&code_lengths);
+
+                let mut lengths = Vec::with_capacity(literal_count + distance_count);
+                while lengths.len() < literal_count + distance_count {
+                    let symbol = codes.decode(&mut bits)?;
+                    let (value, repeat) = match symbol {
+                        0..=15 => (symbol as u8, 1),
+                        16 => (
+                            *lengths.last().ok_or("repeat without previous length")?,
+                            3 + bits.take(2)?,
+                        ),
+                        17 => (0, 3 + bits.take(3)?),
+                        _ => (0, 11 + bits.take(7)?),
+                    };
+                    for _ in 0..repeat {
+                        lengths.push(value);
+                    }
+                }
+                if lengths.len() > literal_count + distance_count {
+                    return Err("too many code lengths");
+                }
+                let literals = Huffman::new(&lengths[..literal_count]);
+                let distances = Huffman::new(&lengths[literal_count..]);
+                inflate_block(&mut bits, &mut out, size, &literals, &distances)?;
+            }
+            _ => return Err("invalid deflate block type"),
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+fn inflate_block(
+    bits: &mut Bits,
+    out: &mut Vec<u8>,
+    size: usize,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), &'static str> {
+    loop {
+        let symbol = literals.decode(bits)? as usize;
+        match symbol {
+            0..=255 if out.len() >= size => return Err("zip entry has the wrong size"),
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let index = symbol - 257;
+                if index >= LENGTH_BASE.len() {
+                    return Err("invalid length code");
+                }
+                let length =
+                    LENGTH_BASE[index] as usize + bits.take(LENGTH_EXTRA[index] as u32)? as usize;
+                let index = distances.decode(bits)? as usize;
+                if index >= DISTANCE_BASE.len() {
+                    return Err("invalid distance code");
+                }
+                let distance = DISTANCE_BASE[index] as usize
+                    + bits.take(DISTANCE_EXTRA[index] as u32)? as usize;
+                if distance > out.len() {
+                    return Err("distance too far back");
+                }
+                if out.len() + length > size {
+                    return Err("zip entry has the wrong size");
+                }
+                let start = out.len() - distance;
+                for i in 0..length {
+                    out.push(out[start + i]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inflate_stored() {
+        let data = [0x01, 0x03, 0x00, 0xFC, 0xFF, b'a', b'b', b'c'];
+        assert_eq!(Ok(b"abc".to_vec()), inflate(&data, 3));
+        assert!(inflate(&data, 2).is_err());
+        assert!(inflate(&[0x01, 0x03, 0x00, 0x00, 0x00], 3).is_err());
+    }
+
+    #[test]
+    fn test_inflate_fixed() {
+        let data = [0x4B, 0xCE, 0x49, 0x4D, 0x53, 0x48, 0x46, 0x21, 0x00];
+        assert_eq!(Ok(b"clef clef clef clef".to_vec()), inflate(&data, 19));
+        assert!(inflate(&data, 18).is_err());
+        assert!(inflate(&data[..4], 19).is_err());
+    }
+
+    #[test]
+    fn test_inflate_dynamic() {
+        let data = [
+            0xED, 0x90, 0xDB, 0x8D, 0xC5, 0x20, 0x0C, 0x05, 0x5B, 0x99, 0x12, 0x62, 0x1B, 0x0C,
+            0xF4, 0xDF, 0xD8, 0x0E, 0x94, 0xB0, 0xDF, 0x57, 0x8A, 0x50, 0x12, 0xCE, 0xFB, 0x23,
+            0x18, 0x1C, 0xA2, 0xC9, 0x49, 0x35, 0xE3, 0xD0, 0x83, 0x1D, 0x14, 0x39, 0x18, 0x8B,
+            0x95, 0x24, 0x15, 0x74, 0x72, 0x44, 0x14, 0xEB, 0x23, 0x92, 0x59, 0x1C, 0xD1, 0x72,
+            0x15, 0xF0, 0x7D, 0x30, 0x3F, 0x36, 0x3D, 0xC9, 0xC5, 0xDE, 0xCC, 0x41, 0x26, 0x5B,
+            0x35, 0xA5, 0x26, 0x11, 0xEC, 0xA6, 0x65, 0x6C, 0x2A, 0x09, 0x91, 0x9C, 0x62, 0x4F,
+            0xD6, 0x61, 0x79, 0xD6, 0x7B, 0xDE, 0xA7, 0x3F, 0xBD, 0xEA, 0x0B, 0x12, 0x2A, 0x41,
+            0x9A, 0xE4, 0x78, 0x42, 0xCA, 0x29, 0xAA, 0xB4, 0x06, 0xDA, 0x68, 0xA6, 0xE5, 0xBE,
+            0xE6, 0x46, 0xB8, 0x41, 0xE2, 0x85, 0xEA, 0x1B, 0xD0, 0x98, 0x86, 0x35, 0xB2, 0xC1,
+            0xFB, 0x95, 0xC8, 0x5B, 0xC7, 0x52, 0x56, 0xAB, 0x5B, 0xD2, 0xAA, 0x16, 0xAE, 0x57,
+            0xDE, 0x09, 0x8E, 0x5B, 0x04, 0xDF, 0x6F, 0x93, 0xDF, 0x26, 0xFF, 0xDF, 0xE4, 0x0F,
+        ];
+        let text: String = (0..300).map(|i| format!("{} ", i * i % 97)).collect();
+        assert_eq!(Ok(text.clone().into_bytes()), inflate(&data, text.len()));
+        assert!(inflate(&data, text.len() - 1).is_err());
+    }
+}
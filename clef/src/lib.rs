@@ -1,14 +1,23 @@
+pub mod format;
 pub mod math;
 
 mod music;
 pub use music::pitch::*;
 pub use music::tune_sys;
 pub use music::Accidental;
-pub use music::Chord;
-pub use music::Duration;
 pub use music::NoteName;
+pub use music::TimeSignature;
+pub use music::{Alteration, Chord, ChordCandidate, ChordQuality, ChordStyle, Extension};
+pub use music::{Articulation, ChordNote, Dynamic, Event, Note, Rest, Tie};
+pub use music::{Clef, ClefSign};
+pub use music::{Duration, Tuplet};
+pub use music::{Interval, Quality};
+pub use music::{Key, KeySignature, Mode};
+pub use music::{Measure, Part, Score, Staff, Voice};
+pub use music::{Scale, ScaleKind};
 pub use music::{A, B, C, D, E, F, G};
-pub use music::{FLAT, NATURAL, SHARP};
+pub use music::{DOUBLE_FLAT, DOUBLE_SHARP, FLAT, NATURAL, SHARP};
+pub use music::{HALF_FLAT, HALF_SHARP, SESQUI_FLAT, SESQUI_SHARP};
 
 #[cfg(test)]
 mod tests {
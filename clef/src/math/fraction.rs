@@ -4,7 +4,7 @@ use std::cmp::Ordering;
 use std::fmt;
 use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
 
-#[derive(Clone, Copy, Debug, Eq, Ord)]
+#[derive(Clone, Copy, Debug, Eq)]
 pub struct Fraction {
     numerator: i32,
     denominator: i32,
@@ -76,9 +76,18 @@ impl PartialEq for Fraction {
     }
 }
 
+impl Ord for Fraction {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let l = self.to_irreducible();
+        let r = other.to_irreducible();
+        (l.numerator as i64 * r.denominator as i64)
+            .cmp(&(r.numerator as i64 * l.denominator as i64))
+    }
+}
+
 impl PartialOrd for Fraction {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.to_float().partial_cmp(&other.to_float())
+        Some(self.cmp(other))
     }
 }
 
@@ -184,7 +193,7 @@ impl Neg for Fraction {
     type Output = Self;
 
     fn neg(self) -> Self::Output {
-        Self::new(self.numerator * -1, self.denominator).to_irreducible()
+        Self::new(-self.numerator, self.denominator).to_irreducible()
     }
 }
 
@@ -264,6 +273,18 @@ mod tests {
     fn test_op_ord() {
         assert!(Fraction::new(3, 5) < Fraction::new(4, 5));
         assert!(Fraction::new(3, 5) <= Fraction::new(15, 25));
+        assert_eq!(
+            Fraction::new(1, 2).cmp(&Fraction::new(2, 4)),
+            Ordering::Equal
+        );
+        assert_eq!(
+            Fraction::new(1, 2).max(Fraction::new(1, 3)),
+            Fraction::new(1, 2)
+        );
+        assert_eq!(
+            Fraction::new(-1, 2).cmp(&Fraction::new(1, -3)),
+            Ordering::Less
+        );
     }
 
     #[test]
@@ -18,6 +18,14 @@ pub fn gcd(a: i32, b: i32) -> i32 {
     cmp::max(a, b)
 }
 
+// Returns None when the result does not fit in an i32.
+pub fn lcm(a: i32, b: i32) -> Option<i32> {
+    if a == 0 || b == 0 {
+        return Some(0);
+    }
+    (a / gcd(a, b)).checked_mul(b).map(i32::abs)
+}
+
 pub fn is_power_of_2(x: u32) -> bool {
     (x != 0) && ((x & (x - 1)) == 0)
 }
@@ -42,6 +50,15 @@ mod tests {
         assert_eq!(gcd(0, -14), 14);
     }
 
+    #[test]
+    fn test_lcm() {
+        assert_eq!(lcm(4, 6), Some(12));
+        assert_eq!(lcm(-4, 6), Some(12));
+        assert_eq!(lcm(7, 1), Some(7));
+        assert_eq!(lcm(0, 5), Some(0));
+        assert_eq!(lcm(i32::MAX, 2), None);
+    }
+
     #[test]
     fn test_is_power_of_2() {
         assert!(is_power_of_2(1));
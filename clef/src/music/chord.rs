@@ -0,0 +1,1198 @@
+use super::*;
+use contracts::requires;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChordQuality {
+    Major,
+    Minor,
+    Diminished,
+    Augmented,
+    Sus2,
+    Sus4,
+    Power,
+    Major6,
+    Minor6,
+    Dominant7,
+    Major7,
+    Minor7,
+    MinorMajor7,
+    Diminished7,
+    HalfDiminished7,
+    Augmented7,
+    AugmentedMajor7,
+    Dominant7Sus4,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Extension {
+    Ninth,
+    Eleventh,
+    Thirteenth,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Alteration {
+    Flat5,
+    Sharp5,
+    Flat9,
+    Sharp9,
+    Sharp11,
+    Flat13,
+    Add(Interval),
+    Omit(i32),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChordStyle {
+    Jazz,
+    Figured,
+    Nashville(Pitch),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Chord {
+    root: Pitch,
+    quality: ChordQuality,
+    extension: Option<Extension>,
+    alterations: Vec<Alteration>,
+    bass: Option<Pitch>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ChordCandidate {
+    chord: Chord,
+    score: i32,
+    missing: Vec<Interval>,
+    added: Vec<Interval>,
+}
+
+impl ChordQuality {
+    const ALL: [ChordQuality; 18] = [
+        ChordQuality::Major,
+        ChordQuality::Minor,
+        ChordQuality::Diminished,
+        ChordQuality::Augmented,
+        ChordQuality::Sus2,
+        ChordQuality::Sus4,
+        ChordQuality::Power,
+        ChordQuality::Major6,
+        ChordQuality::Minor6,
+        ChordQuality::Dominant7,
+        ChordQuality::Major7,
+        ChordQuality::Minor7,
+        ChordQuality::MinorMajor7,
+        ChordQuality::Diminished7,
+        ChordQuality::HalfDiminished7,
+        ChordQuality::Augmented7,
+        ChordQuality::AugmentedMajor7,
+        ChordQuality::Dominant7Sus4,
+    ];
+
+    pub fn intervals(&self) -> Vec<Interval> {
+        let minor_seventh = Interval::MINOR_SEVENTH;
+        let diminished_seventh = Interval::new(Quality::Diminished(1), 7);
+        let augmented_fifth = Interval::new(Quality::Augmented(1), 5);
+
+        let intervals = match self {
+            ChordQuality::Major => vec![Interval::MAJOR_THIRD, Interval::PERFECT_FIFTH],
+            ChordQuality::Minor => vec![Interval::MINOR_THIRD, Interval::PERFECT_FIFTH],
+            ChordQuality::Diminished => vec![Interval::MINOR_THIRD, Interval::DIMINISHED_FIFTH],
+            ChordQuality::Augmented => vec![Interval::MAJOR_THIRD, augmented_fifth],
+            ChordQuality::Sus2 => vec![Interval::MAJOR_SECOND, Interval::PERFECT_FIFTH],
+            ChordQuality::Sus4 => vec![Interval::PERFECT_FOURTH, Interval::PERFECT_FIFTH],
+            ChordQuality::Power => vec![Interval::PERFECT_FIFTH],
+            ChordQuality::Major6 => vec![
+                Interval::MAJOR_THIRD,
+                Interval::PERFECT_FIFTH,
+                Interval::MAJOR_SIXTH,
+            ],
+            ChordQuality::Minor6 => vec![
+                Interval::MINOR_THIRD,
+                Interval::PERFECT_FIFTH,
+                Interval::MAJOR_SIXTH,
+            ],
+            ChordQuality::Dominant7 => vec![
+                Interval::MAJOR_THIRD,
+                Interval::PERFECT_FIFTH,
+                minor_seventh,
+            ],
+            ChordQuality::Major7 => vec![
+                Interval::MAJOR_THIRD,
+                Interval::PERFECT_FIFTH,
+                Interval::MAJOR_SEVENTH,
+            ],
+            ChordQuality::Minor7 => vec![
+                Interval::MINOR_THIRD,
+                Interval::PERFECT_FIFTH,
+                minor_seventh,
+            ],
+            ChordQuality::MinorMajor7 => vec![
+                Interval::MINOR_THIRD,
+                Interval::PERFECT_FIFTH,
+                Interval::MAJOR_SEVENTH,
+            ],
+            ChordQuality::Diminished7 => vec![
+                Interval::MINOR_THIRD,
+                Interval::DIMINISHED_FIFTH,
+                diminished_seventh,
+            ],
+            ChordQuality::HalfDiminished7 => vec![
+                Interval::MINOR_THIRD,
+                Interval::DIMINISHED_FIFTH,
+                minor_seventh,
+            ],
+            ChordQuality::Augmented7 => {
+                vec![Interval::MAJOR_THIRD, augmented_fifth, minor_seventh]
+            }
+            ChordQuality::AugmentedMajor7 => vec![
+                Interval::MAJOR_THIRD,
+                augmented_fifth,
+                Interval::MAJOR_SEVENTH,
+            ],
+            ChordQuality::Dominant7Sus4 => vec![
+                Interval::PERFECT_FOURTH,
+                Interval::PERFECT_FIFTH,
+                minor_seventh,
+            ],
+        };
+
+        std::iter::once(Interval::PERFECT_UNISON)
+            .chain(intervals)
+            .collect()
+    }
+
+    pub fn has_seventh(&self) -> bool {
+        self.intervals().iter().any(|i| i.number() == 7)
+    }
+}
+
+impl Extension {
+    // The natural eleventh clashes with a major third, so it is left out of
+    // thirteenth chords built on one.
+    fn intervals(&self, major_third: bool) -> Vec<Interval> {
+        let ninth = Interval::new(Quality::Major, 9);
+        let eleventh = Interval::new(Quality::Perfect, 11);
+        let thirteenth = Interval::new(Quality::Major, 13);
+
+        match self {
+            Extension::Ninth => vec![ninth],
+            Extension::Eleventh => vec![ninth, eleventh],
+            Extension::Thirteenth if major_third => vec![ninth, thirteenth],
+            Extension::Thirteenth => vec![ninth, eleventh, thirteenth],
+        }
+    }
+}
+
+impl Alteration {
+    pub fn interval(&self) -> Option<Interval> {
+        match self {
+            Alteration::Flat5 => Some(Interval::DIMINISHED_FIFTH),
+            Alteration::Sharp5 => Some(Interval::new(Quality::Augmented(1), 5)),
+            Alteration::Flat9 => Some(Interval::new(Quality::Minor, 9)),
+            Alteration::Sharp9 => Some(Interval::new(Quality::Augmented(1), 9)),
+            Alteration::Sharp11 => Some(Interval::new(Quality::Augmented(1), 11)),
+            Alteration::Flat13 => Some(Interval::new(Quality::Minor, 13)),
+            Alteration::Add(interval) => Some(*interval),
+            Alteration::Omit(_) => None,
+        }
+    }
+}
+
+impl Chord {
+    pub fn new(root: Pitch, quality: ChordQuality) -> Chord {
+        Chord {
+            root,
+            quality,
+            extension: None,
+            alterations: Vec::new(),
+            bass: None,
+        }
+    }
+
+    #[requires(self.quality.has_seventh(), "extensions require a seventh chord")]
+    pub fn with_extension(self, extension: Extension) -> Chord {
+        Chord {
+            extension: Some(extension),
+            ..self
+        }
+    }
+
+    pub fn with_alteration(mut self, alteration: Alteration) -> Chord {
+        self.alterations.push(alteration);
+        self
+    }
+
+    pub fn with_bass(self, bass: Pitch) -> Chord {
+        Chord {
+            bass: Some(bass),
+            ..self
+        }
+    }
+
+    #[requires(inversion < self.intervals().len(), "inversion must select a chord tone")]
+    pub fn with_inversion(self, inversion: usize) -> Result<Chord, &'static str> {
+        match inversion {
+            0 => Ok(Chord { bass: None, ..self }),
+            _ => {
+                let bass = self
+                    .root
+                    .transpose(self.intervals()[inversion] - Interval::PERFECT_OCTAVE)?;
+                Ok(self.with_bass(bass))
+            }
+        }
+    }
+
+    pub fn root(&self) -> Pitch {
+        self.root
+    }
+
+    pub fn quality(&self) -> ChordQuality {
+        self.quality
+    }
+
+    pub fn extension(&self) -> Option<Extension> {
+        self.extension
+    }
+
+    pub fn alterations(&self) -> &[Alteration] {
+        &self.alterations
+    }
+
+    pub fn bass(&self) -> Option<Pitch> {
+        self.bass
+    }
+
+    pub fn intervals(&self) -> Vec<Interval> {
+        let mut intervals = self.quality.intervals();
+        if let Some(extension) = self.extension {
+            let major_third = intervals.contains(&Interval::MAJOR_THIRD);
+            intervals.extend(extension.intervals(major_third));
+        }
+
+        let altered: Vec<Interval> = self
+            .alterations
+            .iter()
+            .filter_map(Alteration::interval)
+            .collect();
+        intervals.retain(|i| !altered.iter().any(|a| a.number() == i.number()));
+        intervals.extend(altered);
+
+        for alteration in self.alterations.iter() {
+            if let Alteration::Omit(number) = alteration {
+                intervals.retain(|i| i.number() != *number);
+            }
+        }
+
+        intervals.sort_by_key(|i| (i.steps(), i.cents()));
+        intervals.dedup();
+        intervals
+    }
+
+    pub fn inversion(&self) -> Option<usize> {
+        match self.bass {
+            None => Some(0),
+            Some(bass) => self
+                .intervals()
+                .iter()
+                .position(|i| ((bass - self.root).cents() - i.cents()).rem_euclid(1200) == 0),
+        }
+    }
+
+    pub fn pitches(&self) -> Result<Vec<Pitch>, &'static str> {
+        let tones = self
+            .intervals()
+            .into_iter()
+            .map(|i| self.root.transpose(i))
+            .collect::<Result<Vec<Pitch>, _>>()?;
+
+        Ok(match self.bass {
+            None => tones,
+            Some(bass) => std::iter::once(bass)
+                .chain(
+                    tones
+                        .into_iter()
+                        .filter(|p| p.pitch_class() != bass.pitch_class()),
+                )
+                .collect(),
+        })
+    }
+}
+
+impl Chord {
+    pub fn format(&self, style: ChordStyle) -> String {
+        match style {
+            ChordStyle::Jazz => format!(
+                "{}{}{}{}",
+                Self::format_pitch(self.root),
+                self.suffix(),
+                self.format_alterations(),
+                self.format_bass(Self::format_pitch)
+            ),
+            ChordStyle::Figured => self.format_figured(),
+            ChordStyle::Nashville(tonic) => {
+                let degree = |pitch: Pitch| {
+                    let interval = Self::simple_above(pitch - tonic);
+                    format!("{}{}", Self::degree_accidental(interval), interval.number())
+                };
+                format!(
+                    "{}{}{}{}",
+                    degree(self.root),
+                    self.suffix(),
+                    self.format_alterations(),
+                    self.format_bass(degree)
+                )
+            }
+        }
+    }
+
+    fn format_pitch(pitch: Pitch) -> String {
+        format!("{}{}", pitch.name(), pitch.accidental())
+    }
+
+    fn format_bass(&self, format: impl Fn(Pitch) -> String) -> String {
+        match self.bass {
+            Some(bass) => format!("/{}", format(bass)),
+            None => String::new(),
+        }
+    }
+
+    fn format_figured(&self) -> String {
+        let marker = match self.quality {
+            ChordQuality::Minor
+            | ChordQuality::Minor6
+            | ChordQuality::Minor7
+            | ChordQuality::MinorMajor7 => "m",
+            ChordQuality::Diminished | ChordQuality::Diminished7 => "\u{b0}",
+            ChordQuality::HalfDiminished7 => "\u{f8}",
+            ChordQuality::Augmented | ChordQuality::Augmented7 | ChordQuality::AugmentedMajor7 => {
+                "+"
+            }
+            _ => "",
+        };
+        let figures: &[&str] = if self.quality.has_seventh() {
+            &["7", "65", "43", "42"]
+        } else {
+            &["", "6", "64"]
+        };
+        let added = match self.quality {
+            ChordQuality::Major6 | ChordQuality::Minor6 => "add6",
+            _ => "",
+        };
+
+        match self.inversion() {
+            Some(inversion) if inversion < figures.len() => format!(
+                "{}{}{}{}",
+                Self::format_pitch(self.root),
+                marker,
+                figures[inversion],
+                added
+            ),
+            _ => format!(
+                "{}{}{}{}{}",
+                Self::format_pitch(self.root),
+                marker,
+                figures[0],
+                added,
+                self.format_bass(Self::format_pitch)
+            ),
+        }
+    }
+
+    fn suffix(&self) -> String {
+        let number = match self.extension {
+            None => "7",
+            Some(Extension::Ninth) => "9",
+            Some(Extension::Eleventh) => "11",
+            Some(Extension::Thirteenth) => "13",
+        };
+        let sixth_ninth = if self.has_sixth_ninth() { "/9" } else { "" };
+
+        match self.quality {
+            ChordQuality::Major => String::new(),
+            ChordQuality::Minor => "m".to_string(),
+            ChordQuality::Diminished => "dim".to_string(),
+            ChordQuality::Augmented => "aug".to_string(),
+            ChordQuality::Sus2 => "sus2".to_string(),
+            ChordQuality::Sus4 => "sus4".to_string(),
+            ChordQuality::Power => "5".to_string(),
+            ChordQuality::Major6 => format!("6{}", sixth_ninth),
+            ChordQuality::Minor6 => format!("m6{}", sixth_ninth),
+            ChordQuality::Dominant7 => number.to_string(),
+            ChordQuality::Major7 => format!("maj{}", number),
+            ChordQuality::Minor7 => format!("m{}", number),
+            ChordQuality::MinorMajor7 => format!("m(maj{})", number),
+            ChordQuality::Diminished7 => format!("dim{}", number),
+            ChordQuality::HalfDiminished7 => format!("m{}b5", number),
+            ChordQuality::Augmented7 => format!("aug{}", number),
+            ChordQuality::AugmentedMajor7 => format!("augmaj{}", number),
+            ChordQuality::Dominant7Sus4 => format!("{}sus4", number),
+        }
+    }
+
+    fn format_alterations(&self) -> String {
+        let mut altered = Vec::new();
+        let mut added = String::new();
+
+        for alteration in self.alterations.iter() {
+            match alteration {
+                Alteration::Add(interval) => {
+                    if !(self.has_sixth_ninth() && *interval == Self::major_ninth()) {
+                        added += &format!(
+                            "add{}{}",
+                            Self::degree_accidental(*interval),
+                            interval.number()
+                        );
+                    }
+                }
+                Alteration::Omit(number) => added += &format!("no{}", number),
+                alteration => {
+                    let interval = alteration.interval().unwrap();
+                    altered.push(format!(
+                        "{}{}",
+                        Self::degree_accidental(interval),
+                        interval.number()
+                    ));
+                }
+            }
+        }
+
+        if altered.is_empty() {
+            added
+        } else {
+            format!("({}){}", altered.join(","), added)
+        }
+    }
+
+    fn has_sixth_ninth(&self) -> bool {
+        matches!(self.quality, ChordQuality::Major6 | ChordQuality::Minor6)
+            && self
+                .alterations
+                .contains(&Alteration::Add(Self::major_ninth()))
+    }
+
+    fn major_ninth() -> Interval {
+        Interval::new(Quality::Major, 9)
+    }
+
+    fn simple_above(interval: Interval) -> Interval {
+        let interval = interval.simple();
+        if interval.is_descending() {
+            interval + Interval::PERFECT_OCTAVE
+        } else {
+            interval
+        }
+    }
+
+    fn degree_accidental(interval: Interval) -> String {
+        let perfect = matches!(interval.number().rem_euclid(7), 1 | 4 | 5);
+        match interval.quality() {
+            Quality::Perfect | Quality::Major => String::new(),
+            Quality::Minor => "b".to_string(),
+            Quality::Augmented(n) => "#".repeat(n as usize),
+            Quality::Diminished(n) if perfect => "b".repeat(n as usize),
+            Quality::Diminished(n) => "b".repeat(n as usize + 1),
+        }
+    }
+}
+
+impl Chord {
+    pub fn identify(pitches: &[Pitch]) -> Vec<ChordCandidate> {
+        let mut sorted = pitches.to_vec();
+        sorted.sort_by_key(|p| (*p - C_1).cents());
+
+        let mut roots: Vec<Pitch> = Vec::new();
+        for pitch in sorted {
+            if !roots.iter().any(|r| r.pitch_class() == pitch.pitch_class()) {
+                roots.push(pitch);
+            }
+        }
+        let bass = match roots.first() {
+            Some(bass) => *bass,
+            None => return Vec::new(),
+        };
+        let classes: Vec<i32> = roots.iter().map(Pitch::pitch_class).collect();
+
+        let mut candidates = Vec::new();
+        for root in roots.iter() {
+            for chord in Self::templates(*root) {
+                let intervals = chord.intervals();
+                let tones: Vec<i32> = intervals
+                    .iter()
+                    .map(|i| (root.pitch_class() + i.semitones()).rem_euclid(12))
+                    .collect();
+                let missing: Vec<Interval> = intervals
+                    .iter()
+                    .zip(tones.iter())
+                    .filter(|(_, tone)| !classes.contains(tone))
+                    .map(|(interval, _)| *interval)
+                    .collect();
+                let missing_seventh = missing.iter().any(|i| i.number() == 7);
+                if missing.len() * 2 >= intervals.len()
+                    || (chord.extension.is_some() && missing_seventh)
+                {
+                    continue;
+                }
+                let added: Vec<Interval> = roots
+                    .iter()
+                    .filter(|p| !tones.contains(&p.pitch_class()))
+                    .map(|p| Self::simple_above(*p - *root))
+                    .collect();
+
+                // Omitting the fifth is common in voicings, so it costs less
+                // than any other missing tone.
+                let mut score = (intervals.len() - missing.len()) as i32 * 4;
+                score -= added.len() as i32 * 4;
+                score -= missing
+                    .iter()
+                    .map(|i| if i.number() == 5 { 1 } else { 6 })
+                    .sum::<i32>();
+
+                let chord = if root.pitch_class() == bass.pitch_class() {
+                    score += 2;
+                    chord
+                } else {
+                    chord.with_bass(bass)
+                };
+
+                candidates.push(ChordCandidate {
+                    chord,
+                    score,
+                    missing,
+                    added,
+                });
+            }
+        }
+
+        candidates.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then(a.chord.intervals().len().cmp(&b.chord.intervals().len()))
+        });
+        candidates
+    }
+
+    fn templates(root: Pitch) -> Vec<Chord> {
+        let mut templates: Vec<Chord> = ChordQuality::ALL
+            .iter()
+            .map(|quality| Chord::new(root, *quality))
+            .collect();
+
+        for quality in [
+            ChordQuality::Dominant7,
+            ChordQuality::Major7,
+            ChordQuality::Minor7,
+        ]
+        .iter()
+        {
+            for extension in [Extension::Ninth, Extension::Eleventh, Extension::Thirteenth].iter() {
+                templates.push(Chord::new(root, *quality).with_extension(*extension));
+            }
+        }
+
+        templates
+    }
+}
+
+impl ChordCandidate {
+    pub fn chord(&self) -> &Chord {
+        &self.chord
+    }
+
+    pub fn score(&self) -> i32 {
+        self.score
+    }
+
+    pub fn inversion(&self) -> Option<usize> {
+        self.chord.inversion()
+    }
+
+    pub fn missing(&self) -> &[Interval] {
+        &self.missing
+    }
+
+    pub fn added(&self) -> &[Interval] {
+        &self.added
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format(ChordStyle::Jazz))
+    }
+}
+
+struct Scanner<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Scanner<'a> {
+    fn eat(&mut self, token: &str) -> bool {
+        if self.rest.starts_with(token) {
+            self.rest = &self.rest[token.len()..];
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_any(&mut self, tokens: &[&str]) -> bool {
+        tokens.iter().any(|token| self.eat(token))
+    }
+
+    fn pitch(&mut self, octave: i32) -> Result<Pitch, &'static str> {
+        let letter = self.rest.chars().next().ok_or("missing chord root")?;
+        if !letter.is_ascii_uppercase() {
+            return Err("chord root must be an upper case letter");
+        }
+        let name = NoteName::from_str(&self.rest[..1])?;
+        self.rest = &self.rest[1..];
+
+        let mut accidental = 0;
+        loop {
+            if self.eat_any(&["#", "\u{266f}"]) {
+                accidental += 1;
+            } else if self.eat_any(&["b", "\u{266d}"]) {
+                accidental -= 1;
+            } else {
+                break;
+            }
+        }
+
+        Ok(Pitch::new_with_accidental(
+            name,
+            Accidental::new(accidental),
+            octave,
+        ))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Triad {
+    Major,
+    Minor,
+    Diminished,
+    Augmented,
+    HalfDiminished,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Seventh {
+    None,
+    Minor,
+    Major,
+    Sixth,
+    Power,
+}
+
+impl FromStr for Chord {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut scanner = Scanner { rest: s.trim() };
+        let root = scanner.pitch(4)?;
+
+        let triad = if scanner.eat("\u{f8}") {
+            Triad::HalfDiminished
+        } else if !scanner.rest.starts_with("omit") && scanner.eat_any(&["dim", "o", "\u{b0}"]) {
+            Triad::Diminished
+        } else if scanner.eat_any(&["aug", "+"]) {
+            Triad::Augmented
+        } else if !scanner.rest.starts_with("ma") && scanner.eat_any(&["min", "mi", "m", "-"]) {
+            Triad::Minor
+        } else {
+            Triad::Major
+        };
+
+        let parenthesized = ["(maj", "(Maj", "(M", "(\u{394}"]
+            .iter()
+            .any(|p| scanner.rest.starts_with(p));
+        if parenthesized {
+            scanner.eat("(");
+        }
+        let delta = scanner.eat_any(&["\u{394}", "^"]);
+        let major = delta || scanner.eat_any(&["maj", "Maj", "ma", "M"]);
+        let number = ["13", "11", "9", "7", "6", "5"]
+            .iter()
+            .find(|n| scanner.eat(n))
+            .map(|n| n.parse::<i32>().unwrap());
+        if parenthesized && !scanner.eat(")") {
+            return Err("unclosed parenthesis");
+        }
+
+        let seventh = match number {
+            None if delta => Seventh::Major,
+            None => Seventh::None,
+            Some(5) if !major => Seventh::Power,
+            Some(6) if !major => Seventh::Sixth,
+            Some(_) if major => Seventh::Major,
+            Some(7) | Some(9) | Some(11) | Some(13) => Seventh::Minor,
+            _ => return Err("invalid chord symbol"),
+        };
+        let extension = match number {
+            Some(9) => Some(Extension::Ninth),
+            Some(11) => Some(Extension::Eleventh),
+            Some(13) => Some(Extension::Thirteenth),
+            _ => None,
+        };
+        let sixth_ninth = seventh == Seventh::Sixth && scanner.eat_any(&["/9", "9"]);
+
+        let sus = if scanner.eat("sus2") {
+            Some(2)
+        } else if scanner.eat_any(&["sus4", "sus"]) {
+            Some(4)
+        } else {
+            None
+        };
+
+        let quality = match (triad, seventh, sus) {
+            (Triad::Major, Seventh::None, None) => ChordQuality::Major,
+            (Triad::Major, Seventh::Sixth, None) => ChordQuality::Major6,
+            (Triad::Major, Seventh::Minor, None) => ChordQuality::Dominant7,
+            (Triad::Major, Seventh::Major, None) => ChordQuality::Major7,
+            (Triad::Major, Seventh::Power, None) => ChordQuality::Power,
+            (Triad::Major, Seventh::None, Some(2)) => ChordQuality::Sus2,
+            (Triad::Major, Seventh::None, Some(4)) => ChordQuality::Sus4,
+            (Triad::Major, Seventh::Minor, Some(4)) => ChordQuality::Dominant7Sus4,
+            (Triad::Minor, Seventh::None, None) => ChordQuality::Minor,
+            (Triad::Minor, Seventh::Sixth, None) => ChordQuality::Minor6,
+            (Triad::Minor, Seventh::Minor, None) => ChordQuality::Minor7,
+            (Triad::Minor, Seventh::Major, None) => ChordQuality::MinorMajor7,
+            (Triad::Diminished, Seventh::None, None) => ChordQuality::Diminished,
+            (Triad::Diminished, Seventh::Minor, None) => ChordQuality::Diminished7,
+            (Triad::Augmented, Seventh::None, None) => ChordQuality::Augmented,
+            (Triad::Augmented, Seventh::Minor, None) => ChordQuality::Augmented7,
+            (Triad::Augmented, Seventh::Major, None) => ChordQuality::AugmentedMajor7,
+            (Triad::HalfDiminished, Seventh::None, None) => ChordQuality::HalfDiminished7,
+            (Triad::HalfDiminished, Seventh::Minor, None) => ChordQuality::HalfDiminished7,
+            _ => return Err("unsupported chord symbol"),
+        };
+
+        let mut alterations = Vec::new();
+        if sixth_ninth {
+            alterations.push(Alteration::Add(Interval::new(Quality::Major, 9)));
+        }
+        loop {
+            if scanner.eat_any(&["(", ")", ",", " "]) {
+                continue;
+            }
+            let alteration = if scanner.eat("b5") {
+                Alteration::Flat5
+            } else if scanner.eat_any(&["#5", "+5"]) {
+                Alteration::Sharp5
+            } else if scanner.eat("b9") {
+                Alteration::Flat9
+            } else if scanner.eat("#9") {
+                Alteration::Sharp9
+            } else if scanner.eat("#11") {
+                Alteration::Sharp11
+            } else if scanner.eat("b13") {
+                Alteration::Flat13
+            } else if scanner.eat("add2") {
+                Alteration::Add(Interval::MAJOR_SECOND)
+            } else if scanner.eat("add4") {
+                Alteration::Add(Interval::PERFECT_FOURTH)
+            } else if scanner.eat("add6") {
+                Alteration::Add(Interval::MAJOR_SIXTH)
+            } else if scanner.eat("add9") {
+                Alteration::Add(Interval::new(Quality::Major, 9))
+            } else if scanner.eat("add11") {
+                Alteration::Add(Interval::new(Quality::Perfect, 11))
+            } else if scanner.eat("add13") {
+                Alteration::Add(Interval::new(Quality::Major, 13))
+            } else if scanner.eat_any(&["no3", "omit3"]) {
+                Alteration::Omit(3)
+            } else if scanner.eat_any(&["no5", "omit5"]) {
+                Alteration::Omit(5)
+            } else {
+                break;
+            };
+            alterations.push(alteration);
+        }
+
+        let mut chord = Chord::new(root, quality);
+        if chord.quality == ChordQuality::Minor7 && alterations.contains(&Alteration::Flat5) {
+            chord.quality = ChordQuality::HalfDiminished7;
+            alterations.retain(|a| *a != Alteration::Flat5);
+        }
+        chord.alterations = alterations;
+        if let Some(extension) = extension {
+            if !chord.quality.has_seventh() {
+                return Err("extensions require a seventh chord");
+            }
+            chord = chord.with_extension(extension);
+        }
+
+        if scanner.eat("/") {
+            let bass = scanner.pitch(root.octave())?;
+            chord.bass = Some(if (bass - root).cents() >= 0 {
+                bass.transpose(-Interval::PERFECT_OCTAVE)?
+            } else {
+                bass
+            });
+        }
+
+        if !scanner.rest.is_empty() {
+            return Err("invalid chord symbol");
+        }
+
+        Ok(chord)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_triads() {
+        assert_eq!(
+            Ok(vec![C4, E4, G4]),
+            Chord::new(C4, ChordQuality::Major).pitches()
+        );
+        assert_eq!(
+            Ok(vec![A3, C4, E4]),
+            Chord::new(A3, ChordQuality::Minor).pitches()
+        );
+        assert_eq!(
+            Ok(vec![B3, D4, F4]),
+            Chord::new(B3, ChordQuality::Diminished).pitches()
+        );
+        assert_eq!(
+            Ok(vec![Ab3, C4, E4]),
+            Chord::new(Ab3, ChordQuality::Augmented).pitches()
+        );
+        assert_eq!(
+            Ok(vec![D4, E4, A4]),
+            Chord::new(D4, ChordQuality::Sus2).pitches()
+        );
+        assert_eq!(
+            Ok(vec![D4, G4, A4]),
+            Chord::new(D4, ChordQuality::Sus4).pitches()
+        );
+    }
+
+    #[test]
+    fn test_sevenths() {
+        assert_eq!(
+            Ok(vec![G3, B3, D4, F4]),
+            Chord::new(G3, ChordQuality::Dominant7).pitches()
+        );
+        assert_eq!(
+            Ok(vec![Bb3, D4, F4, A4]),
+            Chord::new(Bb3, ChordQuality::Major7).pitches()
+        );
+        assert_eq!(
+            Ok(vec![Fs4, A4, C5, E5]),
+            Chord::new(Fs4, ChordQuality::HalfDiminished7).pitches()
+        );
+        assert_eq!(
+            Ok(vec![Gs3, B3, D4, F4]),
+            Chord::new(Gs3, ChordQuality::Diminished7).pitches()
+        );
+        assert_eq!(
+            Ok(vec![
+                Eb4,
+                Gb4,
+                Pitch::new_with_accidental(B, DOUBLE_FLAT, 4),
+                Pitch::new_with_accidental(D, DOUBLE_FLAT, 5),
+            ]),
+            Chord::new(Eb4, ChordQuality::Diminished7).pitches()
+        );
+    }
+
+    #[test]
+    fn test_extensions() {
+        assert_eq!(
+            Ok(vec![C4, E4, G4, Bb4, D5]),
+            Chord::new(C4, ChordQuality::Dominant7)
+                .with_extension(Extension::Ninth)
+                .pitches()
+        );
+        assert_eq!(
+            Ok(vec![D4, F4, A4, C5, E5, G5]),
+            Chord::new(D4, ChordQuality::Minor7)
+                .with_extension(Extension::Eleventh)
+                .pitches()
+        );
+        assert_eq!(
+            Ok(vec![G3, B3, D4, F4, A4, E5]),
+            Chord::new(G3, ChordQuality::Dominant7)
+                .with_extension(Extension::Thirteenth)
+                .pitches()
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_extension_without_seventh() {
+        let _illegal = Chord::new(C4, ChordQuality::Major).with_extension(Extension::Ninth);
+    }
+
+    #[test]
+    fn test_alterations() {
+        assert_eq!(
+            Ok(vec![C4, E4, Gb4, Bb4]),
+            Chord::new(C4, ChordQuality::Dominant7)
+                .with_alteration(Alteration::Flat5)
+                .pitches()
+        );
+        assert_eq!(
+            Ok(vec![G3, B3, D4, F4, Ab4, As4]),
+            Chord::new(G3, ChordQuality::Dominant7)
+                .with_alteration(Alteration::Flat9)
+                .with_alteration(Alteration::Sharp9)
+                .pitches()
+        );
+        assert_eq!(
+            Ok(vec![Bb3, D4, F4, Ab4, C5, E5, G5]),
+            Chord::new(Bb3, ChordQuality::Dominant7)
+                .with_extension(Extension::Thirteenth)
+                .with_alteration(Alteration::Sharp11)
+                .pitches()
+        );
+        assert_eq!(
+            Ok(vec![C4, E4, G4, A4, D5]),
+            Chord::new(C4, ChordQuality::Major6)
+                .with_alteration(Alteration::Add(Interval::new(Quality::Major, 9)))
+                .pitches()
+        );
+        assert_eq!(
+            Ok(vec![C4, G4, Bb4]),
+            Chord::new(C4, ChordQuality::Dominant7)
+                .with_alteration(Alteration::Omit(3))
+                .pitches()
+        );
+    }
+
+    #[test]
+    fn test_inversions() {
+        let chord = Chord::new(C4, ChordQuality::Major)
+            .with_inversion(1)
+            .unwrap();
+        assert_eq!(Some(E3), chord.bass());
+        assert_eq!(Some(1), chord.inversion());
+        assert_eq!(Ok(vec![E3, C4, G4]), chord.pitches());
+
+        let chord = Chord::new(D4, ChordQuality::Major).with_bass(Fs3);
+        assert_eq!(Some(1), chord.inversion());
+        assert_eq!(Ok(vec![Fs3, D4, A4]), chord.pitches());
+
+        let chord = Chord::new(C4, ChordQuality::Major).with_bass(D3);
+        assert_eq!(None, chord.inversion());
+        assert_eq!(Ok(vec![D3, C4, E4, G4]), chord.pitches());
+
+        assert_eq!(Some(0), Chord::new(C4, ChordQuality::Major).inversion());
+
+        assert!(Chord::new(C_1, ChordQuality::Major)
+            .with_inversion(1)
+            .is_err());
+        let chord = Chord::new(G9, ChordQuality::Major).with_bass(B8);
+        assert_eq!(Some(1), chord.inversion());
+        assert!(chord.pitches().is_err());
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!(Ok(Chord::new(C4, ChordQuality::Major7)), "Cmaj7".parse());
+        assert_eq!(
+            Ok(Chord::new(Fs4, ChordQuality::HalfDiminished7)),
+            "F#m7b5".parse()
+        );
+        assert_eq!(
+            Ok(Chord::new(Bb4, ChordQuality::Dominant7)
+                .with_extension(Extension::Thirteenth)
+                .with_alteration(Alteration::Sharp11)),
+            "Bb13(#11)".parse()
+        );
+        assert_eq!(
+            Ok(Chord::new(G4, ChordQuality::Dominant7Sus4)),
+            "G7sus4".parse()
+        );
+        assert_eq!(
+            Ok(Chord::new(D4, ChordQuality::Major).with_bass(Fs3)),
+            "D/F#".parse()
+        );
+        assert_eq!(
+            Ok(Chord::new(C4, ChordQuality::Major6)
+                .with_alteration(Alteration::Add(Interval::new(Quality::Major, 9)))),
+            "C6/9".parse()
+        );
+        assert_eq!(
+            Ok(Chord::new(Eb4, ChordQuality::Diminished7)),
+            "Ebo7".parse()
+        );
+        assert_eq!(
+            Ok(Chord::new(C4, ChordQuality::MinorMajor7)),
+            "Cm(maj7)".parse()
+        );
+        assert_eq!(
+            Ok(Chord::new(A4, ChordQuality::Minor7).with_extension(Extension::Ninth)),
+            "Ami9".parse()
+        );
+        assert_eq!(Ok(Chord::new(C4, ChordQuality::Major7)), "C\u{394}".parse());
+        assert_eq!(Ok(Chord::new(E4, ChordQuality::Power)), "E5".parse());
+        assert_eq!(
+            Ok(Chord::new(G4, ChordQuality::Dominant7)
+                .with_alteration(Alteration::Flat9)
+                .with_alteration(Alteration::Sharp9)),
+            "G7b9#9".parse()
+        );
+        assert_eq!(
+            Ok(Chord::new(C4, ChordQuality::Major)
+                .with_alteration(Alteration::Add(Interval::new(Quality::Major, 9)))),
+            "Cadd9".parse()
+        );
+        assert!("".parse::<Chord>().is_err());
+        assert!("H7".parse::<Chord>().is_err());
+        assert!("Cmaj7x".parse::<Chord>().is_err());
+        assert!("Cdim6".parse::<Chord>().is_err());
+    }
+
+    #[test]
+    fn test_format_jazz() {
+        let symbols = [
+            "Cmaj7",
+            "F#m7b5",
+            "Bb13(#11)",
+            "G7sus4",
+            "D/F#",
+            "C6/9",
+            "Ebdim7",
+            "Cm(maj7)",
+            "Am9",
+            "G7(b9,#9)",
+            "Cadd9",
+            "C7no3",
+            "Abaugmaj7",
+            "E5",
+        ];
+        for symbol in symbols.iter() {
+            assert_eq!(*symbol, symbol.parse::<Chord>().unwrap().to_string());
+        }
+        assert_eq!("Ebdim7", "Ebo7".parse::<Chord>().unwrap().to_string());
+        assert_eq!("Cmaj7", "CM7".parse::<Chord>().unwrap().to_string());
+    }
+
+    #[test]
+    fn test_format_figured() {
+        let chord = Chord::new(C4, ChordQuality::Major);
+        assert_eq!("C", chord.format(ChordStyle::Figured));
+        assert_eq!(
+            "C6",
+            chord
+                .clone()
+                .with_inversion(1)
+                .unwrap()
+                .format(ChordStyle::Figured)
+        );
+        assert_eq!(
+            "C64",
+            chord.with_inversion(2).unwrap().format(ChordStyle::Figured)
+        );
+
+        let chord = Chord::new(G3, ChordQuality::Dominant7);
+        assert_eq!("G7", chord.format(ChordStyle::Figured));
+        assert_eq!(
+            "G65",
+            chord
+                .clone()
+                .with_inversion(1)
+                .unwrap()
+                .format(ChordStyle::Figured)
+        );
+        assert_eq!(
+            "G42",
+            chord.with_inversion(3).unwrap().format(ChordStyle::Figured)
+        );
+
+        assert_eq!(
+            "B\u{f8}7",
+            Chord::new(B3, ChordQuality::HalfDiminished7).format(ChordStyle::Figured)
+        );
+        assert_eq!(
+            "Am/D",
+            Chord::new(A3, ChordQuality::Minor)
+                .with_bass(D3)
+                .format(ChordStyle::Figured)
+        );
+    }
+
+    #[test]
+    fn test_format_nashville() {
+        let key = ChordStyle::Nashville(C4);
+        assert_eq!("1", "C".parse::<Chord>().unwrap().format(key));
+        assert_eq!("57", "G7".parse::<Chord>().unwrap().format(key));
+        assert_eq!("6m", "Am".parse::<Chord>().unwrap().format(key));
+        assert_eq!("b7", "Bb".parse::<Chord>().unwrap().format(key));
+        assert_eq!("2/#4", "D/F#".parse::<Chord>().unwrap().format(key));
+        assert_eq!(
+            "4maj7",
+            "Bbmaj7"
+                .parse::<Chord>()
+                .unwrap()
+                .format(ChordStyle::Nashville(F4))
+        );
+        assert_eq!(
+            "1",
+            "Eb".parse::<Chord>()
+                .unwrap()
+                .format(ChordStyle::Nashville(Eb2))
+        );
+    }
+
+    #[test]
+    fn test_identify() {
+        assert!(Chord::identify(&[]).is_empty());
+
+        let candidates = Chord::identify(&[C4, E4, G4]);
+        assert_eq!(&Chord::new(C4, ChordQuality::Major), candidates[0].chord());
+        assert_eq!(Some(0), candidates[0].inversion());
+        assert!(candidates[0].missing().is_empty());
+        assert!(candidates[0].added().is_empty());
+
+        let candidates = Chord::identify(&[G2, B3, F4, D5, G5]);
+        assert_eq!(
+            &Chord::new(G2, ChordQuality::Dominant7),
+            candidates[0].chord()
+        );
+
+        let candidates = Chord::identify(&[E3, G4, C5, E5]);
+        assert_eq!(
+            &Chord::new(C5, ChordQuality::Major).with_bass(E3),
+            candidates[0].chord()
+        );
+        assert_eq!(Some(1), candidates[0].inversion());
+
+        let candidates = Chord::identify(&[A3, C4, E4, G4]);
+        assert_eq!(&Chord::new(A3, ChordQuality::Minor7), candidates[0].chord());
+        assert_eq!(
+            &Chord::new(C4, ChordQuality::Major6).with_bass(A3),
+            candidates[1].chord()
+        );
+
+        let candidates = Chord::identify(&[C3, E4, Bb4]);
+        assert_eq!(
+            &Chord::new(C3, ChordQuality::Dominant7),
+            candidates[0].chord()
+        );
+        assert_eq!(&[Interval::PERFECT_FIFTH], candidates[0].missing());
+
+        let candidates = Chord::identify(&[C4, E4, G4, D5]);
+        assert_eq!(&Chord::new(C4, ChordQuality::Major), candidates[0].chord());
+        assert_eq!(&[Interval::MAJOR_SECOND], candidates[0].added());
+
+        let candidates = Chord::identify(&[C4, E4, G4, Bb4, D5]);
+        assert_eq!(
+            &Chord::new(C4, ChordQuality::Dominant7).with_extension(Extension::Ninth),
+            candidates[0].chord()
+        );
+
+        let candidates = Chord::identify(&[C4, F4, G4]);
+        assert_eq!(&Chord::new(C4, ChordQuality::Sus4), candidates[0].chord());
+
+        let candidates = Chord::identify(&[D3, A3, D4]);
+        assert_eq!(&Chord::new(D3, ChordQuality::Power), candidates[0].chord());
+    }
+}
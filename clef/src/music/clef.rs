@@ -0,0 +1,158 @@
+use super::pitch::{B4, C4, F3, G4};
+use super::{NoteName, Pitch};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ClefSign {
+    G,
+    F,
+    C,
+    Percussion,
+    Tab,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Clef {
+    sign: ClefSign,
+    line: i32,
+    octave_change: i32,
+}
+
+const STAFF_LINES: i32 = 5;
+
+fn steps(pitch: &Pitch) -> i32 {
+    pitch.octave() * 7 + pitch.name().index()
+}
+
+impl Clef {
+    pub const TREBLE: Clef = Clef::new(ClefSign::G, 2, 0);
+    pub const TREBLE_8VB: Clef = Clef::new(ClefSign::G, 2, -1);
+    pub const TREBLE_8VA: Clef = Clef::new(ClefSign::G, 2, 1);
+    pub const BASS: Clef = Clef::new(ClefSign::F, 4, 0);
+    pub const BASS_8VB: Clef = Clef::new(ClefSign::F, 4, -1);
+    pub const BASS_8VA: Clef = Clef::new(ClefSign::F, 4, 1);
+    pub const SOPRANO: Clef = Clef::new(ClefSign::C, 1, 0);
+    pub const ALTO: Clef = Clef::new(ClefSign::C, 3, 0);
+    pub const TENOR: Clef = Clef::new(ClefSign::C, 4, 0);
+    pub const PERCUSSION: Clef = Clef::new(ClefSign::Percussion, 3, 0);
+    pub const TAB: Clef = Clef::new(ClefSign::Tab, 5, 0);
+
+    pub const fn new(sign: ClefSign, line: i32, octave_change: i32) -> Clef {
+        Clef {
+            sign,
+            line,
+            octave_change,
+        }
+    }
+
+    pub fn sign(&self) -> ClefSign {
+        self.sign
+    }
+
+    pub fn line(&self) -> i32 {
+        self.line
+    }
+
+    pub fn octave_change(&self) -> i32 {
+        self.octave_change
+    }
+
+    // The pitch sitting on the clef's line, before any octave change.
+    fn reference(&self) -> Option<Pitch> {
+        match self.sign {
+            ClefSign::G => Some(G4),
+            ClefSign::F => Some(F3),
+            ClefSign::C => Some(C4),
+            // Unpitched notes are placed as on a treble staff.
+            ClefSign::Percussion => Some(B4),
+            ClefSign::Tab => None,
+        }
+    }
+
+    pub fn position(&self, pitch: &Pitch) -> Option<i32> {
+        let reference = self.reference()?;
+        let steps = steps(pitch) - self.octave_change * 7 - steps(&reference);
+        Some(steps + (self.line - 1) * 2)
+    }
+
+    pub fn pitch_at(&self, position: i32) -> Result<Pitch, &'static str> {
+        let reference = self.reference().ok_or("tablature has no pitch positions")?;
+        let steps = position - (self.line - 1) * 2 + steps(&reference) + self.octave_change * 7;
+        let octave = steps.div_euclid(7);
+        if !(-1..=9).contains(&octave) {
+            return Err("octave out of range");
+        }
+        Ok(Pitch::new(NoteName::from_index(steps), octave))
+    }
+
+    pub fn ledger_lines(&self, pitch: &Pitch) -> Option<i32> {
+        let position = self.position(pitch)?;
+        let top = (STAFF_LINES - 1) * 2;
+        Some(if position < 0 {
+            -position / 2
+        } else if position > top {
+            (position - top) / 2
+        } else {
+            0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::music::pitch::*;
+
+    #[test]
+    fn test_position() {
+        assert_eq!(Some(0), Clef::TREBLE.position(&E4));
+        assert_eq!(Some(2), Clef::TREBLE.position(&G4));
+        assert_eq!(Some(-2), Clef::TREBLE.position(&C4));
+        assert_eq!(Some(8), Clef::TREBLE.position(&F5));
+        assert_eq!(Some(8), Clef::BASS.position(&A3));
+        assert_eq!(Some(0), Clef::BASS.position(&G2));
+        assert_eq!(Some(4), Clef::ALTO.position(&C4));
+        assert_eq!(Some(6), Clef::TENOR.position(&C4));
+        assert_eq!(Some(0), Clef::SOPRANO.position(&C4));
+        assert_eq!(Some(4), Clef::PERCUSSION.position(&B4));
+        assert_eq!(None, Clef::TAB.position(&B4));
+        assert_eq!(Some(0), Clef::TREBLE.position(&Eb4));
+    }
+
+    #[test]
+    fn test_octave_clefs() {
+        assert_eq!(Some(2), Clef::TREBLE_8VB.position(&G3));
+        assert_eq!(Some(2), Clef::TREBLE_8VA.position(&G5));
+        assert_eq!(Some(6), Clef::BASS_8VB.position(&F2));
+        assert_eq!(Ok(G3), Clef::TREBLE_8VB.pitch_at(2));
+    }
+
+    #[test]
+    fn test_pitch_at() {
+        assert_eq!(Ok(E4), Clef::TREBLE.pitch_at(0));
+        assert_eq!(Ok(C4), Clef::TREBLE.pitch_at(-2));
+        assert_eq!(Ok(B3), Clef::TREBLE.pitch_at(-3));
+        assert_eq!(Ok(D3), Clef::BASS.pitch_at(4));
+        assert_eq!(Ok(C4), Clef::ALTO.pitch_at(4));
+        assert!(Clef::TAB.pitch_at(0).is_err());
+        assert!(Clef::TREBLE.pitch_at(100).is_err());
+
+        for clef in [Clef::TREBLE, Clef::BASS, Clef::TENOR, Clef::BASS_8VA].iter() {
+            for position in -6..=14 {
+                let pitch = clef.pitch_at(position).unwrap();
+                assert_eq!(Some(position), clef.position(&pitch));
+            }
+        }
+    }
+
+    #[test]
+    fn test_ledger_lines() {
+        assert_eq!(Some(1), Clef::TREBLE.ledger_lines(&C4));
+        assert_eq!(Some(1), Clef::TREBLE.ledger_lines(&B3));
+        assert_eq!(Some(0), Clef::TREBLE.ledger_lines(&D4));
+        assert_eq!(Some(0), Clef::TREBLE.ledger_lines(&G5));
+        assert_eq!(Some(1), Clef::TREBLE.ledger_lines(&A5));
+        assert_eq!(Some(2), Clef::TREBLE.ledger_lines(&C6));
+        assert_eq!(Some(1), Clef::BASS.ledger_lines(&C4));
+        assert_eq!(None, Clef::TAB.ledger_lines(&C4));
+    }
+}
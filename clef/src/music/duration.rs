@@ -1,3 +1,4 @@
+use super::TimeSignature;
 use crate::math;
 use crate::math::Fraction;
 use contracts::requires;
@@ -7,6 +8,52 @@ use std::convert::TryFrom;
 pub struct Duration {
     denominator: u16,
     dots: u8,
+    tuplet: Option<Tuplet>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tuplet {
+    actual: u16,
+    normal: u16,
+}
+
+impl Tuplet {
+    pub const TRIPLET: Tuplet = Tuplet {
+        actual: 3,
+        normal: 2,
+    };
+
+    #[requires(actual > 0 && normal > 0, "tuplet ratio must be positive")]
+    pub fn new(actual: u16, normal: u16) -> Tuplet {
+        Tuplet { actual, normal }
+    }
+
+    pub fn actual(&self) -> u16 {
+        self.actual
+    }
+
+    pub fn normal(&self) -> u16 {
+        self.normal
+    }
+
+    pub fn ratio(&self) -> Fraction {
+        Fraction::new(self.normal as i32, self.actual as i32)
+    }
+
+    pub fn nested(&self, inner: Tuplet) -> Tuplet {
+        Tuplet::new(self.actual * inner.actual, self.normal * inner.normal)
+    }
+
+    // Picks the conventional ratio for an odd denominator factor, e.g. 3:2 or 5:4.
+    fn for_factor(factor: i32) -> Result<Tuplet, &'static str> {
+        let actual = u16::try_from(factor).map_err(|_| "tuplet too large")?;
+        let mut normal = 1;
+        while normal * 2 < factor {
+            normal *= 2;
+        }
+        let normal = u16::try_from(normal).map_err(|_| "tuplet too large")?;
+        Ok(Tuplet::new(actual, normal))
+    }
 }
 
 impl Duration {
@@ -15,13 +62,26 @@ impl Duration {
         Duration {
             denominator,
             dots: 0,
+            tuplet: None,
         }
     }
 
     #[requires(math::is_power_of_2(denominator as u32), "denominator must be a power of 2")]
     #[requires(dots <= 4, "dots must be no more than 4")]
     pub fn new_with_dots(denominator: u16, dots: u8) -> Duration {
-        Duration { denominator, dots }
+        Duration {
+            denominator,
+            dots,
+            tuplet: None,
+        }
+    }
+
+    pub fn with_tuplet(mut self, tuplet: Tuplet) -> Duration {
+        self.tuplet = match self.tuplet {
+            Some(outer) => Some(outer.nested(tuplet)),
+            None => Some(tuplet),
+        };
+        self
     }
 
     pub fn denominator(&self) -> u16 {
@@ -32,6 +92,10 @@ impl Duration {
         self.dots
     }
 
+    pub fn tuplet(&self) -> Option<Tuplet> {
+        self.tuplet
+    }
+
     pub fn to_fraction(&self) -> Fraction {
         let denominator = self.denominator as i32;
         let mut fraction = Fraction::new(1, denominator);
@@ -44,7 +108,108 @@ impl Duration {
             }
         }
 
-        fraction
+        match self.tuplet {
+            Some(tuplet) => fraction * tuplet.ratio(),
+            None => fraction,
+        }
+    }
+
+    pub fn decompose(fraction: Fraction) -> Result<Vec<Duration>, &'static str> {
+        if fraction.signum() <= 0 {
+            return Err("fraction must be positive");
+        }
+
+        let (mut rest, tuplet) = Self::split_tuplet(fraction)?;
+        let mut durations = Vec::new();
+        while rest > Fraction::ZERO {
+            let duration = Self::largest_within(rest).ok_or("denominator too large")?;
+            rest -= duration.to_fraction();
+            durations.push(match tuplet {
+                Some(tuplet) => duration.with_tuplet(tuplet),
+                None => duration,
+            });
+        }
+        Ok(durations)
+    }
+
+    pub fn decompose_at(
+        fraction: Fraction,
+        meter: &TimeSignature,
+        offset: Fraction,
+    ) -> Result<Vec<Duration>, &'static str> {
+        if fraction.signum() <= 0 {
+            return Err("fraction must be positive");
+        }
+
+        let measure = meter.measure_length();
+        let mut beats = meter.beat_offsets();
+        beats.push(measure);
+
+        let end = offset + fraction;
+        let mut start = offset;
+        let mut durations = Vec::new();
+        while start < end {
+            let bars = start / measure;
+            let bar = measure * bars.numerator().div_euclid(bars.denominator());
+            let position = start - bar;
+            let limit = std::cmp::min(end - bar, measure);
+
+            // Notes starting on a beat may span whole beats; others stop at the next beat.
+            let stop = if beats.contains(&position) {
+                let last = beats.iter().filter(|b| **b <= limit).max().unwrap();
+                if *last > position {
+                    *last
+                } else {
+                    limit
+                }
+            } else {
+                let next = beats.iter().find(|b| **b > position).unwrap();
+                std::cmp::min(*next, limit)
+            };
+
+            durations.extend(Self::decompose(bar + stop - start)?);
+            start = bar + stop;
+        }
+        Ok(durations)
+    }
+
+    pub fn decompose_all(fractions: &[Fraction]) -> Result<Vec<Vec<Duration>>, &'static str> {
+        fractions.iter().map(|f| Self::decompose(*f)).collect()
+    }
+
+    // Separates the odd part of the denominator into a tuplet, leaving a
+    // fraction with a power of 2 denominator.
+    fn split_tuplet(fraction: Fraction) -> Result<(Fraction, Option<Tuplet>), &'static str> {
+        let fraction = fraction.to_irreducible();
+        let mut factor = fraction.denominator();
+        while factor % 2 == 0 {
+            factor /= 2;
+        }
+        if factor == 1 {
+            return Ok((fraction, None));
+        }
+        let tuplet = Tuplet::for_factor(factor)?;
+        Ok((fraction / tuplet.ratio(), Some(tuplet)))
+    }
+
+    fn largest_within(fraction: Fraction) -> Option<Duration> {
+        let mut denominator = 1u16;
+        while denominator <= 128 {
+            let base = Duration::new(denominator);
+            if base.to_fraction() <= fraction {
+                let mut duration = base;
+                while duration.dots < 4 {
+                    let dotted = Duration::new_with_dots(denominator, duration.dots + 1);
+                    if dotted.to_fraction() > fraction {
+                        break;
+                    }
+                    duration = dotted;
+                }
+                return Some(duration);
+            }
+            denominator *= 2;
+        }
+        None
     }
 }
 
@@ -56,7 +221,7 @@ impl TryFrom<Fraction> for Duration {
             return Err("fraction must be positive");
         }
 
-        let mut fraction = fraction;
+        let (mut fraction, tuplet) = Duration::split_tuplet(fraction)?;
         let mut denominator = 0u16;
         let mut dots = 0;
 
@@ -90,7 +255,11 @@ impl TryFrom<Fraction> for Duration {
             return Err("not a durational fraction")
         }
 
-        Ok(Duration::new_with_dots(denominator, dots))
+        let duration = Duration::new_with_dots(denominator, dots);
+        Ok(match tuplet {
+            Some(tuplet) => duration.with_tuplet(tuplet),
+            None => duration,
+        })
     }
 }
 
@@ -135,4 +304,141 @@ mod tests {
         assert!(Duration::try_from(Fraction::new(9, 16)).is_err());
         assert!(Duration::try_from(Fraction::new(1, 256)).is_err());
     }
+
+    #[test]
+    #[should_panic]
+    fn test_zero_tuplet() {
+        let _illegal = Tuplet::new(0, 2);
+    }
+
+    #[test]
+    fn test_tuplet_to_fraction() {
+        let triplet = Duration::new(8).with_tuplet(Tuplet::TRIPLET);
+        assert_eq!(Fraction::new(1, 12), triplet.to_fraction());
+        let quintuplet = Duration::new(16).with_tuplet(Tuplet::new(5, 4));
+        assert_eq!(Fraction::new(1, 20), quintuplet.to_fraction());
+        let nested = quintuplet.with_tuplet(Tuplet::TRIPLET);
+        assert_eq!(Some(Tuplet::new(15, 8)), nested.tuplet());
+        assert_eq!(Fraction::new(1, 30), nested.to_fraction());
+        let dotted = Duration::new_with_dots(4, 1).with_tuplet(Tuplet::TRIPLET);
+        assert_eq!(Fraction::new(1, 4), dotted.to_fraction());
+    }
+
+    #[test]
+    fn test_tuplet_from_fraction() {
+        assert_eq!(
+            Ok(Duration::new(8).with_tuplet(Tuplet::TRIPLET)),
+            Duration::try_from(Fraction::new(1, 12))
+        );
+        assert_eq!(
+            Ok(Duration::new(2).with_tuplet(Tuplet::TRIPLET)),
+            Duration::try_from(Fraction::new(1, 3))
+        );
+        assert_eq!(
+            Ok(Duration::new(16).with_tuplet(Tuplet::new(5, 4))),
+            Duration::try_from(Fraction::new(1, 20))
+        );
+        assert!(Duration::try_from(Fraction::new(5, 12)).is_err());
+        assert_eq!(
+            Err("tuplet too large"),
+            Duration::try_from(Fraction::new(1, 65537))
+        );
+
+        for tuplet in [Tuplet::TRIPLET, Tuplet::new(5, 4), Tuplet::new(15, 8)].iter() {
+            for denominator in [1, 4, 16].iter() {
+                for dots in 0..=2 {
+                    let duration = Duration::new_with_dots(*denominator, dots).with_tuplet(*tuplet);
+                    let fraction = duration.to_fraction();
+                    let parsed = Duration::try_from(fraction).unwrap();
+                    assert_eq!(fraction, parsed.to_fraction());
+                    if dots == 0 {
+                        assert_eq!(duration, parsed);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_decompose() {
+        assert_eq!(
+            Ok(vec![Duration::new(2), Duration::new(8)]),
+            Duration::decompose(Fraction::new(5, 8))
+        );
+        assert_eq!(
+            Ok(vec![Duration::new_with_dots(2, 2)]),
+            Duration::decompose(Fraction::new(7, 8))
+        );
+        assert_eq!(
+            Ok(vec![
+                Duration::new(4).with_tuplet(Tuplet::TRIPLET),
+                Duration::new(16).with_tuplet(Tuplet::TRIPLET)
+            ]),
+            Duration::decompose(Fraction::new(5, 24))
+        );
+        assert_eq!(
+            Ok(vec![Duration::new(1), Duration::new(4)]),
+            Duration::decompose(Fraction::new(5, 4))
+        );
+        assert!(Duration::decompose(Fraction::ZERO).is_err());
+        assert!(Duration::decompose(Fraction::new(1, 512)).is_err());
+        assert_eq!(
+            Err("tuplet too large"),
+            Duration::decompose(Fraction::new(3, 65537))
+        );
+
+        let durations = Duration::decompose_all(&[Fraction::new(1, 12), Fraction::new(9, 16)]);
+        assert_eq!(
+            Ok(vec![
+                vec![Duration::new(8).with_tuplet(Tuplet::TRIPLET)],
+                vec![Duration::new(2), Duration::new(16)]
+            ]),
+            durations
+        );
+    }
+
+    #[test]
+    fn test_decompose_at() {
+        let meter = TimeSignature::new(4, 4);
+        assert_eq!(
+            Ok(vec![Duration::new(2), Duration::new(8)]),
+            Duration::decompose_at(Fraction::new(5, 8), &meter, Fraction::ZERO)
+        );
+        assert_eq!(
+            Ok(vec![Duration::new(8), Duration::new(4)]),
+            Duration::decompose_at(Fraction::new(3, 8), &meter, Fraction::new(1, 8))
+        );
+        assert_eq!(
+            Ok(vec![Duration::new(4), Duration::new(4)]),
+            Duration::decompose_at(Fraction::new(1, 2), &meter, Fraction::new(3, 4))
+        );
+        assert_eq!(
+            Ok(vec![Duration::new(1), Duration::new(1)]),
+            Duration::decompose_at(Fraction::new(2, 1), &meter, Fraction::new(4, 1))
+        );
+        assert_eq!(
+            Ok(vec![
+                Duration::new(8).with_tuplet(Tuplet::TRIPLET),
+                Duration::new(4)
+            ]),
+            Duration::decompose_at(Fraction::new(1, 3), &meter, Fraction::new(1, 6))
+        );
+
+        let meter = TimeSignature::new(6, 8);
+        assert_eq!(
+            Ok(vec![Duration::new_with_dots(4, 1), Duration::new(8)]),
+            Duration::decompose_at(Fraction::new(1, 2), &meter, Fraction::ZERO)
+        );
+        assert_eq!(
+            Ok(vec![Duration::new(4), Duration::new_with_dots(4, 1)]),
+            Duration::decompose_at(Fraction::new(5, 8), &meter, Fraction::new(1, 8))
+        );
+
+        let meter = TimeSignature::additive(&[3, 3, 2], 8);
+        assert_eq!(
+            Ok(vec![Duration::new(8), Duration::new(4)]),
+            Duration::decompose_at(Fraction::new(3, 8), &meter, Fraction::new(5, 8))
+        );
+        assert!(Duration::decompose_at(Fraction::ZERO, &meter, Fraction::ZERO).is_err());
+    }
 }
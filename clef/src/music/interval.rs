@@ -0,0 +1,352 @@
+use contracts::requires;
+use std::fmt;
+use std::ops::{Add, Neg, Sub};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Quality {
+    Perfect,
+    Major,
+    Minor,
+    Augmented(u8),
+    Diminished(u8),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Interval {
+    steps: i32,
+    cents: i32,
+}
+
+impl Interval {
+    pub const PERFECT_UNISON: Interval = Interval { steps: 0, cents: 0 };
+    pub const MINOR_SECOND: Interval = Interval {
+        steps: 1,
+        cents: 100,
+    };
+    pub const MAJOR_SECOND: Interval = Interval {
+        steps: 1,
+        cents: 200,
+    };
+    pub const MINOR_THIRD: Interval = Interval {
+        steps: 2,
+        cents: 300,
+    };
+    pub const MAJOR_THIRD: Interval = Interval {
+        steps: 2,
+        cents: 400,
+    };
+    pub const PERFECT_FOURTH: Interval = Interval {
+        steps: 3,
+        cents: 500,
+    };
+    pub const AUGMENTED_FOURTH: Interval = Interval {
+        steps: 3,
+        cents: 600,
+    };
+    pub const DIMINISHED_FIFTH: Interval = Interval {
+        steps: 4,
+        cents: 600,
+    };
+    pub const PERFECT_FIFTH: Interval = Interval {
+        steps: 4,
+        cents: 700,
+    };
+    pub const MINOR_SIXTH: Interval = Interval {
+        steps: 5,
+        cents: 800,
+    };
+    pub const MAJOR_SIXTH: Interval = Interval {
+        steps: 5,
+        cents: 900,
+    };
+    pub const MINOR_SEVENTH: Interval = Interval {
+        steps: 6,
+        cents: 1000,
+    };
+    pub const MAJOR_SEVENTH: Interval = Interval {
+        steps: 6,
+        cents: 1100,
+    };
+    pub const PERFECT_OCTAVE: Interval = Interval {
+        steps: 7,
+        cents: 1200,
+    };
+
+    const MAJOR_SCALE_SEMITONES: [i32; 7] = [0, 2, 4, 5, 7, 9, 11];
+}
+
+impl Interval {
+    #[requires(number != 0, "number must not be zero")]
+    #[requires(Self::is_valid(quality, number), "quality does not match the number")]
+    pub fn new(quality: Quality, number: i32) -> Interval {
+        let steps = number.abs() - 1;
+        let deviation = match quality {
+            Quality::Perfect | Quality::Major => 0,
+            Quality::Minor => -1,
+            Quality::Augmented(n) => n as i32,
+            Quality::Diminished(n) if Self::is_perfect_class(steps) => -(n as i32),
+            Quality::Diminished(n) => -(n as i32) - 1,
+        };
+        let cents = (Self::reference_semitones(steps) + deviation) * 100;
+
+        if number < 0 {
+            Interval {
+                steps: -steps,
+                cents: -cents,
+            }
+        } else {
+            Interval { steps, cents }
+        }
+    }
+
+    pub(crate) fn from_cents(steps: i32, cents: i32) -> Interval {
+        Interval { steps, cents }
+    }
+
+    pub fn steps(&self) -> i32 {
+        self.steps
+    }
+
+    pub fn semitones(&self) -> i32 {
+        self.cents / 100
+    }
+
+    pub fn cents(&self) -> i32 {
+        self.cents
+    }
+
+    pub fn deviation(&self) -> i32 {
+        self.cents % 100
+    }
+
+    pub fn number(&self) -> i32 {
+        if self.steps < 0 {
+            self.steps - 1
+        } else {
+            self.steps + 1
+        }
+    }
+
+    pub fn quality(&self) -> Quality {
+        let (steps, semitones) = if self.steps < 0 {
+            (-self.steps, -self.semitones())
+        } else {
+            (self.steps, self.semitones())
+        };
+        let deviation = semitones - Self::reference_semitones(steps);
+
+        if Self::is_perfect_class(steps) {
+            match deviation {
+                0 => Quality::Perfect,
+                d if d > 0 => Quality::Augmented(d as u8),
+                d => Quality::Diminished(-d as u8),
+            }
+        } else {
+            match deviation {
+                0 => Quality::Major,
+                -1 => Quality::Minor,
+                d if d > 0 => Quality::Augmented(d as u8),
+                d => Quality::Diminished((-d - 1) as u8),
+            }
+        }
+    }
+
+    pub fn is_descending(&self) -> bool {
+        self.steps < 0
+    }
+
+    pub fn is_compound(&self) -> bool {
+        self.steps.abs() > 7
+    }
+
+    pub fn octaves(&self) -> i32 {
+        self.steps.abs() / 7
+    }
+
+    pub fn simple(&self) -> Interval {
+        let octaves = self.octaves() * self.steps.signum();
+        Interval {
+            steps: self.steps - octaves * 7,
+            cents: self.cents - octaves * 1200,
+        }
+    }
+
+    fn is_valid(quality: Quality, number: i32) -> bool {
+        let perfect = Self::is_perfect_class(number.abs() - 1);
+        match quality {
+            Quality::Perfect => perfect,
+            Quality::Major | Quality::Minor => !perfect,
+            Quality::Augmented(n) | Quality::Diminished(n) => n > 0,
+        }
+    }
+
+    fn is_perfect_class(steps: i32) -> bool {
+        matches!(steps.rem_euclid(7), 0 | 3 | 4)
+    }
+
+    fn reference_semitones(steps: i32) -> i32 {
+        steps.div_euclid(7) * 12 + Self::MAJOR_SCALE_SEMITONES[steps.rem_euclid(7) as usize]
+    }
+}
+
+impl fmt::Display for Quality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Quality::Perfect => write!(f, "P"),
+            Quality::Major => write!(f, "M"),
+            Quality::Minor => write!(f, "m"),
+            Quality::Augmented(n) => write!(f, "{}", "A".repeat(*n as usize)),
+            Quality::Diminished(n) => write!(f, "{}", "d".repeat(*n as usize)),
+        }
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_descending() {
+            write!(f, "-")?;
+        }
+        write!(f, "{}{}", self.quality(), self.number().abs())?;
+        match self.deviation() {
+            0 => Ok(()),
+            cents if self.is_descending() => write!(f, "{:+}c", -cents),
+            cents => write!(f, "{:+}c", cents),
+        }
+    }
+}
+
+impl Add for Interval {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self::Output {
+        Interval {
+            steps: self.steps + other.steps,
+            cents: self.cents + other.cents,
+        }
+    }
+}
+
+impl Neg for Interval {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Interval {
+            steps: -self.steps,
+            cents: -self.cents,
+        }
+    }
+}
+
+impl Sub for Interval {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self::Output {
+        self + -other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[should_panic]
+    fn test_major_fifth() {
+        let _illegal = Interval::new(Quality::Major, 5);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_perfect_third() {
+        let _illegal = Interval::new(Quality::Perfect, 3);
+    }
+
+    #[test]
+    fn test_new() {
+        assert_eq!(Interval::MAJOR_THIRD, Interval::new(Quality::Major, 3));
+        assert_eq!(Interval::MINOR_SEVENTH, Interval::new(Quality::Minor, 7));
+        assert_eq!(
+            Interval::DIMINISHED_FIFTH,
+            Interval::new(Quality::Diminished(1), 5)
+        );
+        assert_eq!(
+            Interval::AUGMENTED_FOURTH,
+            Interval::new(Quality::Augmented(1), 4)
+        );
+        assert_eq!(2, Interval::new(Quality::Diminished(1), 3).semitones());
+        assert_eq!(9, Interval::new(Quality::Augmented(2), 5).semitones());
+        assert_eq!(14, Interval::new(Quality::Major, 9).semitones());
+        assert_eq!(-7, Interval::new(Quality::Perfect, -5).semitones());
+    }
+
+    #[test]
+    fn test_quality() {
+        assert_eq!(Quality::Perfect, Interval::PERFECT_UNISON.quality());
+        assert_eq!(Quality::Minor, Interval::MINOR_SIXTH.quality());
+        assert_eq!(Quality::Diminished(1), Interval::DIMINISHED_FIFTH.quality());
+        assert_eq!(Quality::Augmented(1), Interval::AUGMENTED_FOURTH.quality());
+        assert_eq!(
+            Quality::Diminished(2),
+            Interval::from_cents(2, 100).quality()
+        );
+        assert_eq!(
+            Quality::Diminished(1),
+            Interval::from_cents(0, -100).quality()
+        );
+        assert_eq!(Quality::Major, (-Interval::MAJOR_THIRD).quality());
+    }
+
+    #[test]
+    fn test_microtonal() {
+        let neutral_third = Interval::from_cents(2, 350);
+        assert_eq!(3, neutral_third.semitones());
+        assert_eq!(50, neutral_third.deviation());
+        assert_eq!(Quality::Minor, neutral_third.quality());
+        assert_eq!(-50, (-neutral_third).deviation());
+        assert_eq!(Quality::Minor, (-neutral_third).quality());
+    }
+
+    #[test]
+    fn test_number() {
+        assert_eq!(1, Interval::PERFECT_UNISON.number());
+        assert_eq!(8, Interval::PERFECT_OCTAVE.number());
+        assert_eq!(-3, (-Interval::MINOR_THIRD).number());
+        assert_eq!(
+            10,
+            (Interval::PERFECT_OCTAVE + Interval::MAJOR_THIRD).number()
+        );
+    }
+
+    #[test]
+    fn test_compound() {
+        let tenth = Interval::PERFECT_OCTAVE + Interval::MAJOR_THIRD;
+        assert!(tenth.is_compound());
+        assert!(!Interval::PERFECT_OCTAVE.is_compound());
+        assert_eq!(1, tenth.octaves());
+        assert_eq!(Interval::MAJOR_THIRD, tenth.simple());
+        assert_eq!(-Interval::MAJOR_THIRD, (-tenth).simple());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!("P1", Interval::PERFECT_UNISON.to_string());
+        assert_eq!("M3", Interval::MAJOR_THIRD.to_string());
+        assert_eq!("d5", Interval::DIMINISHED_FIFTH.to_string());
+        assert_eq!("AA4", Interval::new(Quality::Augmented(2), 4).to_string());
+        assert_eq!("-m7", (-Interval::MINOR_SEVENTH).to_string());
+        assert_eq!("m2+50c", Interval::from_cents(1, 150).to_string());
+        assert_eq!("-m2+50c", Interval::from_cents(-1, -150).to_string());
+    }
+
+    #[test]
+    fn test_op_add() {
+        assert_eq!(
+            Interval::PERFECT_FIFTH,
+            Interval::MAJOR_THIRD + Interval::MINOR_THIRD
+        );
+        assert_eq!(
+            Interval::MINOR_SECOND,
+            Interval::PERFECT_FOURTH - Interval::MAJOR_THIRD
+        );
+    }
+}
@@ -0,0 +1,343 @@
+use super::*;
+use std::fmt;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Mode {
+    Major,
+    Minor,
+    Dorian,
+    Phrygian,
+    Lydian,
+    Mixolydian,
+    Locrian,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct KeySignature {
+    accidentals: [Accidental; 7],
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Key {
+    name: NoteName,
+    accidental: Accidental,
+    mode: Mode,
+}
+
+// Note names in the order sharps are added to a signature.
+const LINE_OF_FIFTHS: [NoteName; 7] = [F, C, G, D, A, E, B];
+
+fn fifths_of(name: NoteName, accidental: Accidental) -> i32 {
+    let position = LINE_OF_FIFTHS.iter().position(|n| *n == name).unwrap() as i32;
+    position - 1 + accidental.semitones() * 7
+}
+
+fn from_fifths(fifths: i32) -> (NoteName, Accidental) {
+    let name = LINE_OF_FIFTHS[(fifths + 1).rem_euclid(7) as usize];
+    (name, Accidental::new((fifths + 1).div_euclid(7)))
+}
+
+impl Mode {
+    fn fifths_offset(&self) -> i32 {
+        match self {
+            Mode::Lydian => 1,
+            Mode::Major => 0,
+            Mode::Mixolydian => -1,
+            Mode::Dorian => -2,
+            Mode::Minor => -3,
+            Mode::Phrygian => -4,
+            Mode::Locrian => -5,
+        }
+    }
+
+    pub fn scale_kind(&self) -> ScaleKind {
+        match self {
+            Mode::Major => ScaleKind::Ionian,
+            Mode::Minor => ScaleKind::Aeolian,
+            Mode::Dorian => ScaleKind::Dorian,
+            Mode::Phrygian => ScaleKind::Phrygian,
+            Mode::Lydian => ScaleKind::Lydian,
+            Mode::Mixolydian => ScaleKind::Mixolydian,
+            Mode::Locrian => ScaleKind::Locrian,
+        }
+    }
+}
+
+impl KeySignature {
+    pub fn new(fifths: i32) -> KeySignature {
+        let mut accidentals = [NATURAL; 7];
+        for i in 0..fifths.abs() {
+            let index = if fifths > 0 { i % 7 } else { 6 - i % 7 };
+            let name = LINE_OF_FIFTHS[index as usize];
+            let accidental = &mut accidentals[name.index() as usize];
+            *accidental = Accidental::from_cents(accidental.cents() + fifths.signum() * 100);
+        }
+        KeySignature { accidentals }
+    }
+
+    pub fn from_accidentals(accidentals: &[(NoteName, Accidental)]) -> KeySignature {
+        let mut signature = KeySignature {
+            accidentals: [NATURAL; 7],
+        };
+        for (name, accidental) in accidentals.iter() {
+            signature.accidentals[name.index() as usize] = *accidental;
+        }
+        signature
+    }
+
+    pub fn accidental(&self, name: NoteName) -> Accidental {
+        self.accidentals[name.index() as usize]
+    }
+
+    pub fn fifths(&self) -> Option<i32> {
+        let fifths = self.accidentals.iter().map(|a| a.cents()).sum::<i32>() / 100;
+        if !self.accidentals.iter().any(Accidental::is_microtonal)
+            && KeySignature::new(fifths) == *self
+        {
+            Some(fifths)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_standard(&self) -> bool {
+        self.fifths().is_some()
+    }
+
+    pub fn sharps(&self) -> i32 {
+        self.accidentals.iter().map(|a| a.semitones().max(0)).sum()
+    }
+
+    pub fn flats(&self) -> i32 {
+        self.accidentals
+            .iter()
+            .map(|a| (-a.semitones()).max(0))
+            .sum()
+    }
+
+    pub fn key(&self, mode: Mode) -> Option<Key> {
+        let (name, accidental) = from_fifths(self.fifths()? - mode.fifths_offset());
+        Some(Key::new(name, accidental, mode))
+    }
+}
+
+impl Key {
+    pub fn new(name: NoteName, accidental: Accidental, mode: Mode) -> Key {
+        Key {
+            name,
+            accidental,
+            mode,
+        }
+    }
+
+    pub fn name(&self) -> NoteName {
+        self.name
+    }
+
+    pub fn accidental(&self) -> Accidental {
+        self.accidental
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn fifths(&self) -> i32 {
+        fifths_of(self.name, self.accidental) + self.mode.fifths_offset()
+    }
+
+    pub fn signature(&self) -> KeySignature {
+        KeySignature::new(self.fifths())
+    }
+
+    pub fn tonic(&self, octave: i32) -> Pitch {
+        Pitch::new_with_accidental(self.name, self.accidental, octave)
+    }
+
+    pub fn scale(&self, octave: i32) -> Scale {
+        Scale::new(self.tonic(octave), self.mode.scale_kind())
+    }
+
+    pub fn relative(&self) -> Key {
+        let mode = match self.mode {
+            Mode::Major => Mode::Minor,
+            _ => Mode::Major,
+        };
+        self.signature().key(mode).unwrap()
+    }
+
+    pub fn parallel(&self) -> Key {
+        let mode = match self.mode {
+            Mode::Major => Mode::Minor,
+            _ => Mode::Major,
+        };
+        Key::new(self.name, self.accidental, mode)
+    }
+
+    pub fn circle_of_fifths(&self, steps: i32) -> Key {
+        let (name, accidental) = from_fifths(fifths_of(self.name, self.accidental) + steps);
+        Key::new(name, accidental, self.mode)
+    }
+
+    pub fn spell(&self, pitch_class: i32) -> (NoteName, Accidental) {
+        let signature = self.signature();
+        let pitch_class = pitch_class.rem_euclid(12);
+        let candidates: Vec<(NoteName, Accidental)> = (0..7)
+            .map(|index| {
+                let name = NoteName::from_index(index);
+                let semitones = (pitch_class - name.semitones() + 6).rem_euclid(12) - 6;
+                (name, Accidental::new(semitones))
+            })
+            .collect();
+
+        let distance = |(name, accidental): &(NoteName, Accidental)| {
+            (accidental.semitones() - signature.accidental(*name).semitones()).abs()
+        };
+        let best = candidates.iter().map(distance).min().unwrap();
+        let mut nearest = candidates.iter().filter(|c| distance(c) == best);
+        let first = *nearest.next().unwrap();
+        let second = match nearest.next() {
+            Some(second) => *second,
+            None => return first,
+        };
+
+        let leading_tone = self.tonic(4) - Interval::MINOR_SECOND;
+        let raised = |(name, accidental): &(NoteName, Accidental)| {
+            accidental.semitones() > signature.accidental(*name).semitones()
+        };
+        let leading_tone = (leading_tone.name(), leading_tone.accidental());
+        if leading_tone == second
+            || (leading_tone != first && raised(&first) != (self.fifths() >= 0))
+        {
+            second
+        } else {
+            first
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Mode::Major => "major",
+            Mode::Minor => "minor",
+            Mode::Dorian => "dorian",
+            Mode::Phrygian => "phrygian",
+            Mode::Lydian => "lydian",
+            Mode::Mixolydian => "mixolydian",
+            Mode::Locrian => "locrian",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{} {}", self.name, self.accidental, self.mode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature() {
+        let signature = KeySignature::new(3);
+        assert_eq!(SHARP, signature.accidental(F));
+        assert_eq!(SHARP, signature.accidental(G));
+        assert_eq!(NATURAL, signature.accidental(D));
+        assert_eq!(3, signature.sharps());
+
+        let signature = KeySignature::new(-2);
+        assert_eq!(FLAT, signature.accidental(B));
+        assert_eq!(FLAT, signature.accidental(E));
+        assert_eq!(NATURAL, signature.accidental(A));
+        assert_eq!(2, signature.flats());
+
+        let signature = KeySignature::new(8);
+        assert_eq!(DOUBLE_SHARP, signature.accidental(F));
+        assert_eq!(SHARP, signature.accidental(C));
+    }
+
+    #[test]
+    fn test_non_standard_signature() {
+        let signature = KeySignature::from_accidentals(&[(B, FLAT), (F, SHARP)]);
+        assert_eq!(FLAT, signature.accidental(B));
+        assert_eq!(SHARP, signature.accidental(F));
+        assert_eq!(None, signature.fifths());
+        assert!(!signature.is_standard());
+        assert_eq!(None, signature.key(Mode::Major));
+
+        let signature = KeySignature::from_accidentals(&[(B, FLAT), (E, FLAT)]);
+        assert_eq!(Some(-2), signature.fifths());
+        assert_eq!(KeySignature::new(-2), signature);
+    }
+
+    #[test]
+    fn test_key_signature_conversion() {
+        assert_eq!(0, Key::new(C, NATURAL, Mode::Major).fifths());
+        assert_eq!(0, Key::new(A, NATURAL, Mode::Minor).fifths());
+        assert_eq!(0, Key::new(D, NATURAL, Mode::Dorian).fifths());
+        assert_eq!(-3, Key::new(E, FLAT, Mode::Major).fifths());
+        assert_eq!(6, Key::new(D, SHARP, Mode::Minor).fifths());
+        assert_eq!(-7, Key::new(C, FLAT, Mode::Major).fifths());
+        assert_eq!(
+            Some(Key::new(C, SHARP, Mode::Minor)),
+            KeySignature::new(4).key(Mode::Minor)
+        );
+        assert_eq!(
+            Some(Key::new(G, FLAT, Mode::Major)),
+            KeySignature::new(-6).key(Mode::Major)
+        );
+        assert_eq!(
+            Some(Key::new(E, NATURAL, Mode::Phrygian)),
+            KeySignature::new(0).key(Mode::Phrygian)
+        );
+    }
+
+    #[test]
+    fn test_related_keys() {
+        let key = Key::new(E, FLAT, Mode::Major);
+        assert_eq!(Key::new(C, NATURAL, Mode::Minor), key.relative());
+        assert_eq!(Key::new(E, FLAT, Mode::Minor), key.parallel());
+        assert_eq!(key, key.relative().relative());
+        assert_eq!(Key::new(B, FLAT, Mode::Major), key.circle_of_fifths(1));
+        assert_eq!(Key::new(A, FLAT, Mode::Major), key.circle_of_fifths(-1));
+        assert_eq!(Key::new(A, NATURAL, Mode::Major), key.circle_of_fifths(6));
+        assert_eq!(
+            Key::new(F, SHARP, Mode::Minor),
+            Key::new(A, NATURAL, Mode::Minor).circle_of_fifths(3)
+        );
+    }
+
+    #[test]
+    fn test_spell() {
+        let key = Key::new(E, FLAT, Mode::Major);
+        assert_eq!((E, FLAT), key.spell(3));
+        assert_eq!((A, FLAT), key.spell(8));
+        assert_eq!((G, FLAT), key.spell(6));
+
+        let key = Key::new(D, NATURAL, Mode::Minor);
+        assert_eq!((B, FLAT), key.spell(10));
+        assert_eq!((C, SHARP), key.spell(1));
+
+        let key = Key::new(F, SHARP, Mode::Major);
+        assert_eq!((E, SHARP), key.spell(5));
+        assert_eq!((A, SHARP), key.spell(10));
+        assert_eq!((B, NATURAL), key.spell(11));
+    }
+
+    #[test]
+    fn test_scale() {
+        let scale = Key::new(G, NATURAL, Mode::Minor).scale(4);
+        assert_eq!(Ok(Bb4), scale.degree(3));
+        assert_eq!(Ok(F5), scale.degree(7));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!("F# minor", Key::new(F, SHARP, Mode::Minor).to_string());
+        assert_eq!("Bb major", Key::new(B, FLAT, Mode::Major).to_string());
+    }
+}
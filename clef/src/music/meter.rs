@@ -0,0 +1,279 @@
+use super::Duration;
+use crate::math;
+use crate::math::Fraction;
+use contracts::requires;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TimeSignature {
+    groups: Vec<u16>,
+    denominator: u16,
+}
+
+impl TimeSignature {
+    #[requires(numerator > 0, "numerator must be positive")]
+    #[requires(denominator > 0, "denominator must be positive")]
+    pub fn new(numerator: u16, denominator: u16) -> TimeSignature {
+        TimeSignature {
+            groups: vec![numerator],
+            denominator,
+        }
+    }
+
+    #[requires(!groups.is_empty(), "groups must not be empty")]
+    #[requires(groups.iter().all(|g| *g > 0), "groups must be positive")]
+    #[requires(
+        groups.iter().try_fold(0u16, |sum, g| sum.checked_add(*g)).is_some(),
+        "groups must add up to at most u16::MAX"
+    )]
+    #[requires(denominator > 0, "denominator must be positive")]
+    pub fn additive(groups: &[u16], denominator: u16) -> TimeSignature {
+        TimeSignature {
+            groups: groups.to_vec(),
+            denominator,
+        }
+    }
+
+    pub fn numerator(&self) -> u16 {
+        self.groups.iter().sum()
+    }
+
+    pub fn denominator(&self) -> u16 {
+        self.denominator
+    }
+
+    pub fn groups(&self) -> &[u16] {
+        &self.groups
+    }
+
+    pub fn is_additive(&self) -> bool {
+        self.groups.len() > 1
+    }
+
+    pub fn is_compound(&self) -> bool {
+        let numerator = self.numerator();
+        !self.is_additive() && !self.is_irrational() && numerator > 3 && numerator % 3 == 0
+    }
+
+    pub fn is_irrational(&self) -> bool {
+        !math::is_power_of_2(self.denominator as u32)
+    }
+
+    pub fn measure_length(&self) -> Fraction {
+        Fraction::new(self.numerator() as i32, self.denominator as i32)
+    }
+
+    pub fn beat_unit(&self) -> Option<Duration> {
+        if self.is_irrational() {
+            Duration::try_from(Fraction::new(1, self.denominator as i32)).ok()
+        } else if self.is_compound() {
+            // A dotted breve has no Duration, so 6/1 and the like have no beat unit.
+            Duration::try_from(Fraction::new(3, self.denominator as i32)).ok()
+        } else {
+            Some(Duration::new(self.denominator))
+        }
+    }
+
+    pub fn grouping(&self) -> Vec<u16> {
+        if self.is_additive() {
+            self.groups.clone()
+        } else if self.is_compound() {
+            vec![3; (self.numerator() / 3) as usize]
+        } else {
+            vec![1; self.numerator() as usize]
+        }
+    }
+
+    pub fn beats(&self) -> usize {
+        self.grouping().len()
+    }
+
+    pub fn beat_offsets(&self) -> Vec<Fraction> {
+        let mut offset = Fraction::ZERO;
+        self.grouping()
+            .iter()
+            .map(|group| {
+                let beat = offset;
+                offset += Fraction::new(*group as i32, self.denominator as i32);
+                beat
+            })
+            .collect()
+    }
+
+    pub fn metric_weights(&self) -> Vec<u8> {
+        let grouping = self.grouping();
+        let middle = if grouping.len() >= 4 && grouping.len() % 2 == 0 {
+            Some(grouping.len() / 2)
+        } else {
+            None
+        };
+
+        let mut weights = Vec::with_capacity(self.numerator() as usize);
+        for (beat, group) in grouping.iter().enumerate() {
+            weights.push(match beat {
+                0 => 3,
+                _ if Some(beat) == middle => 2,
+                _ => 1,
+            });
+            weights.resize(weights.len() + *group as usize - 1, 0);
+        }
+        weights
+    }
+
+    pub fn metric_weight(&self, offset: Fraction) -> u8 {
+        let position = offset * self.denominator as i32;
+        let position = position.to_irreducible();
+        if position.denominator() != 1 {
+            return 0;
+        }
+        let index = position.numerator().rem_euclid(self.numerator() as i32);
+        self.metric_weights()[index as usize]
+    }
+}
+
+impl fmt::Display for TimeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let groups: Vec<String> = self.groups.iter().map(|g| g.to_string()).collect();
+        write!(f, "{}/{}", groups.join("+"), self.denominator)
+    }
+}
+
+impl FromStr for TimeSignature {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (numerator, denominator) = s.trim().split_once('/').ok_or("missing '/'")?;
+        let denominator = denominator
+            .trim()
+            .parse::<u16>()
+            .map_err(|_| "invalid denominator")?;
+        let groups = numerator
+            .split('+')
+            .map(|g| g.trim().parse::<u16>().map_err(|_| "invalid numerator"))
+            .collect::<Result<Vec<u16>, _>>()?;
+        if denominator == 0 || groups.contains(&0) {
+            return Err("time signature must be positive");
+        }
+        if groups
+            .iter()
+            .try_fold(0u16, |sum, g| sum.checked_add(*g))
+            .is_none()
+        {
+            return Err("numerator out of range");
+        }
+        Ok(TimeSignature::additive(&groups, denominator))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::music::Tuplet;
+
+    #[test]
+    #[should_panic]
+    fn test_zero_denominator() {
+        let _illegal = TimeSignature::new(4, 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_additive_overflow() {
+        let _illegal = TimeSignature::additive(&[40000, 40000], 4);
+    }
+
+    #[test]
+    fn test_simple() {
+        let meter = TimeSignature::new(3, 4);
+        assert_eq!(Fraction::new(3, 4), meter.measure_length());
+        assert_eq!(Some(Duration::new(4)), meter.beat_unit());
+        assert_eq!(vec![1, 1, 1], meter.grouping());
+        assert_eq!(vec![3, 1, 1], meter.metric_weights());
+        assert!(!meter.is_compound());
+    }
+
+    #[test]
+    fn test_compound() {
+        let meter = TimeSignature::new(6, 8);
+        assert!(meter.is_compound());
+        assert_eq!(Fraction::new(3, 4), meter.measure_length());
+        assert_eq!(Some(Duration::new_with_dots(4, 1)), meter.beat_unit());
+        assert_eq!(2, meter.beats());
+        assert_eq!(
+            vec![Fraction::ZERO, Fraction::new(3, 8)],
+            meter.beat_offsets()
+        );
+
+        assert_eq!(None, TimeSignature::new(6, 1).beat_unit());
+
+        let meter = TimeSignature::new(12, 8);
+        assert_eq!(
+            vec![3, 0, 0, 1, 0, 0, 2, 0, 0, 1, 0, 0],
+            meter.metric_weights()
+        );
+    }
+
+    #[test]
+    fn test_additive() {
+        let meter = TimeSignature::additive(&[3, 3, 2], 8);
+        assert!(meter.is_additive());
+        assert!(!meter.is_compound());
+        assert_eq!(8, meter.numerator());
+        assert_eq!(Fraction::new(1, 1), meter.measure_length());
+        assert_eq!(Some(Duration::new(8)), meter.beat_unit());
+        assert_eq!(vec![3, 3, 2], meter.grouping());
+        assert_eq!(
+            vec![Fraction::ZERO, Fraction::new(3, 8), Fraction::new(3, 4)],
+            meter.beat_offsets()
+        );
+    }
+
+    #[test]
+    fn test_irrational() {
+        let meter = TimeSignature::new(4, 3);
+        assert!(meter.is_irrational());
+        assert_eq!(Fraction::new(4, 3), meter.measure_length());
+        assert_eq!(
+            Some(Duration::new(2).with_tuplet(Tuplet::TRIPLET)),
+            meter.beat_unit()
+        );
+        assert_eq!(None, TimeSignature::new(4, 384).beat_unit());
+        assert_eq!(Fraction::new(2, 3), meter.beat_offsets()[2]);
+    }
+
+    #[test]
+    fn test_metric_weight() {
+        let meter = TimeSignature::new(4, 4);
+        assert_eq!(3, meter.metric_weight(Fraction::ZERO));
+        assert_eq!(1, meter.metric_weight(Fraction::new(1, 4)));
+        assert_eq!(2, meter.metric_weight(Fraction::new(1, 2)));
+        assert_eq!(0, meter.metric_weight(Fraction::new(1, 8)));
+        assert_eq!(3, meter.metric_weight(Fraction::new(1, 1)));
+
+        let meter = TimeSignature::new(6, 8);
+        assert_eq!(1, meter.metric_weight(Fraction::new(3, 8)));
+        assert_eq!(0, meter.metric_weight(Fraction::new(1, 4)));
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!(Ok(TimeSignature::new(4, 4)), "4/4".parse());
+        assert_eq!(
+            Ok(TimeSignature::additive(&[3, 3, 2], 8)),
+            "3+3+2/8".parse()
+        );
+        assert!("4".parse::<TimeSignature>().is_err());
+        assert!("0/4".parse::<TimeSignature>().is_err());
+        assert_eq!(
+            Err("numerator out of range"),
+            "40000+40000/4".parse::<TimeSignature>()
+        );
+        assert_eq!(
+            "3+3+2/8",
+            TimeSignature::additive(&[3, 3, 2], 8).to_string()
+        );
+        assert_eq!("6/8", TimeSignature::new(6, 8).to_string());
+    }
+}
@@ -1,8 +1,27 @@
+use std::fmt;
+use std::str::FromStr;
+
+mod chord;
+pub use chord::{Alteration, Chord, ChordCandidate, ChordQuality, ChordStyle, Extension};
+mod clef;
+pub use clef::{Clef, ClefSign};
 mod duration;
 
-pub use duration::Duration;
+pub use duration::{Duration, Tuplet};
+mod interval;
+pub use interval::{Interval, Quality};
+mod key;
+pub use key::{Key, KeySignature, Mode};
+mod meter;
+pub use meter::TimeSignature;
+mod note;
+pub use note::{Articulation, ChordNote, Dynamic, Event, Note, Rest, Tie};
 pub mod pitch;
 pub use pitch::*;
+mod scale;
+pub use scale::{Scale, ScaleKind};
+mod score;
+pub use score::{Measure, Part, Score, Staff, Voice};
 
 pub mod tune_sys;
 
@@ -16,13 +35,242 @@ pub const G: NoteName = NoteName(7);
 pub const A: NoteName = NoteName(9);
 pub const B: NoteName = NoteName(11);
 
+impl NoteName {
+    const ALL: [NoteName; 7] = [C, D, E, F, G, A, B];
+
+    pub fn from_index(index: i32) -> NoteName {
+        Self::ALL[index.rem_euclid(7) as usize]
+    }
+
+    pub fn index(&self) -> i32 {
+        match self.0 {
+            0 => 0,
+            2 => 1,
+            4 => 2,
+            5 => 3,
+            7 => 4,
+            9 => 5,
+            11 => 6,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn semitones(&self) -> i32 {
+        self.0
+    }
+
+    pub fn to_char(&self) -> char {
+        ['C', 'D', 'E', 'F', 'G', 'A', 'B'][self.index() as usize]
+    }
+
+    fn from_char(c: char) -> Result<NoteName, &'static str> {
+        match c.to_ascii_uppercase() {
+            'C' => Ok(C),
+            'D' => Ok(D),
+            'E' => Ok(E),
+            'F' => Ok(F),
+            'G' => Ok(G),
+            'A' => Ok(A),
+            'B' => Ok(B),
+            _ => Err("note name must be one of A to G"),
+        }
+    }
+}
+
+impl fmt::Display for NoteName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_char())
+    }
+}
+
+impl FromStr for NoteName {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => NoteName::from_char(c),
+            _ => Err("note name must be a single letter"),
+        }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Accidental(i32);
 pub const NATURAL: Accidental = Accidental(0);
-pub const SHARP: Accidental = Accidental(1);
-pub const FLAT: Accidental = Accidental(-1);
+pub const SHARP: Accidental = Accidental(100);
+pub const FLAT: Accidental = Accidental(-100);
+pub const DOUBLE_SHARP: Accidental = Accidental(200);
+pub const DOUBLE_FLAT: Accidental = Accidental(-200);
+pub const HALF_SHARP: Accidental = Accidental(50);
+pub const HALF_FLAT: Accidental = Accidental(-50);
+pub const SESQUI_SHARP: Accidental = Accidental(150);
+pub const SESQUI_FLAT: Accidental = Accidental(-150);
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
-pub struct Chord {
-    root: Pitch
-}
\ No newline at end of file
+impl Accidental {
+    pub fn new(semitones: i32) -> Accidental {
+        Accidental(semitones * 100)
+    }
+
+    pub fn from_cents(cents: i32) -> Accidental {
+        Accidental(cents)
+    }
+
+    pub fn semitones(&self) -> i32 {
+        self.0 / 100
+    }
+
+    pub fn cents(&self) -> i32 {
+        self.0
+    }
+
+    pub fn is_microtonal(&self) -> bool {
+        self.0 % 100 != 0
+    }
+
+    pub fn to_unicode(&self) -> String {
+        self.symbols(true)
+    }
+
+    pub(crate) fn parse_prefix(s: &str) -> Result<(Accidental, &str), &'static str> {
+        let mut cents = 0;
+        let mut rest = s;
+
+        while let Some(c) = rest.chars().next() {
+            if c == '(' {
+                let end = rest.find(')').ok_or("unclosed cents accidental")?;
+                let value = rest[1..end].trim_end_matches(['c', '\u{a2}']);
+                cents += value
+                    .parse::<i32>()
+                    .map_err(|_| "invalid cents accidental")?;
+                rest = &rest[end + 1..];
+                continue;
+            }
+
+            cents += match c {
+                '#' | '\u{266f}' => 100,
+                'x' | '\u{1d12a}' => 200,
+                'b' | '\u{266d}' => -100,
+                '\u{1d12b}' => -200,
+                '~' | '\u{1d132}' => 50,
+                '`' | '\u{1d133}' => -50,
+                'n' | '\u{266e}' => 0,
+                _ => break,
+            };
+            rest = &rest[c.len_utf8()..];
+        }
+
+        Ok((Accidental(cents), rest))
+    }
+
+    fn symbols(&self, unicode: bool) -> String {
+        let (sharp, double_sharp, flat, double_flat) = if unicode {
+            ("\u{266f}", "\u{1d12a}", "\u{266d}", "\u{1d12b}")
+        } else {
+            ("#", "x", "b", "bb")
+        };
+
+        let semitones = self.semitones();
+        let mut symbols = String::new();
+        if semitones % 2 != 0 {
+            symbols += if semitones > 0 { sharp } else { flat };
+        }
+        let doubles = (semitones / 2).unsigned_abs() as usize;
+        symbols += &if semitones > 0 {
+            double_sharp
+        } else {
+            double_flat
+        }
+        .repeat(doubles);
+
+        match self.0 % 100 {
+            0 => {}
+            50 => symbols += if unicode { "\u{1d132}" } else { "~" },
+            -50 => symbols += if unicode { "\u{1d133}" } else { "`" },
+            cents if unicode => symbols += &format!("({:+}\u{a2})", cents),
+            cents => symbols += &format!("({:+}c)", cents),
+        }
+
+        symbols
+    }
+}
+
+impl fmt::Display for Accidental {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.symbols(false))
+    }
+}
+
+impl FromStr for Accidental {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match Accidental::parse_prefix(s)? {
+            (accidental, "") => Ok(accidental),
+            _ => Err("invalid accidental"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accidental_semitones() {
+        assert_eq!(2, DOUBLE_SHARP.semitones());
+        assert_eq!(-1, SESQUI_FLAT.semitones());
+        assert_eq!(0, HALF_SHARP.semitones());
+        assert_eq!(SHARP, Accidental::new(1));
+        assert_eq!(HALF_FLAT, Accidental::from_cents(-50));
+        assert!(SESQUI_SHARP.is_microtonal());
+        assert!(!DOUBLE_FLAT.is_microtonal());
+    }
+
+    #[test]
+    fn test_accidental_display() {
+        assert_eq!("", NATURAL.to_string());
+        assert_eq!("#", SHARP.to_string());
+        assert_eq!("x", DOUBLE_SHARP.to_string());
+        assert_eq!("#x", Accidental::new(3).to_string());
+        assert_eq!("b", FLAT.to_string());
+        assert_eq!("bb", DOUBLE_FLAT.to_string());
+        assert_eq!("~", HALF_SHARP.to_string());
+        assert_eq!("b`", SESQUI_FLAT.to_string());
+        assert_eq!("#(+14c)", Accidental::from_cents(114).to_string());
+        assert_eq!("(-31c)", Accidental::from_cents(-31).to_string());
+    }
+
+    #[test]
+    fn test_accidental_to_unicode() {
+        assert_eq!("", NATURAL.to_unicode());
+        assert_eq!("\u{266f}", SHARP.to_unicode());
+        assert_eq!("\u{1d12a}", DOUBLE_SHARP.to_unicode());
+        assert_eq!("\u{266d}\u{1d12b}", Accidental::new(-3).to_unicode());
+        assert_eq!("\u{266d}\u{1d133}", SESQUI_FLAT.to_unicode());
+        assert_eq!("(+14\u{a2})", Accidental::from_cents(14).to_unicode());
+    }
+
+    #[test]
+    fn test_accidental_from_str() {
+        assert_eq!(Ok(NATURAL), "".parse());
+        assert_eq!(Ok(NATURAL), "n".parse());
+        assert_eq!(Ok(SHARP), "#".parse());
+        assert_eq!(Ok(DOUBLE_SHARP), "x".parse());
+        assert_eq!(Ok(DOUBLE_SHARP), "##".parse());
+        assert_eq!(Ok(DOUBLE_FLAT), "\u{266d}\u{266d}".parse());
+        assert_eq!(Ok(SESQUI_SHARP), "#~".parse());
+        assert_eq!(Ok(Accidental::from_cents(-131)), "b(-31c)".parse());
+        assert!("#?".parse::<Accidental>().is_err());
+        assert!("(12".parse::<Accidental>().is_err());
+    }
+
+    #[test]
+    fn test_note_name_from_str() {
+        assert_eq!(Ok(C), "C".parse());
+        assert_eq!(Ok(A), "a".parse());
+        assert!("H".parse::<NoteName>().is_err());
+        assert!("Cb".parse::<NoteName>().is_err());
+        assert_eq!("G", G.to_string());
+    }
+}
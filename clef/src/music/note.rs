@@ -0,0 +1,378 @@
+use super::{Chord, Duration, Pitch};
+use crate::math::Fraction;
+use contracts::requires;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Tie {
+    Start,
+    Stop,
+    Continue,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Articulation {
+    Staccato,
+    Staccatissimo,
+    Tenuto,
+    Accent,
+    Marcato,
+    Fermata,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Dynamic {
+    Ppp,
+    Pp,
+    P,
+    Mp,
+    Mf,
+    F,
+    Ff,
+    Fff,
+}
+
+impl Dynamic {
+    pub fn velocity(&self) -> u8 {
+        match self {
+            Dynamic::Ppp => 16,
+            Dynamic::Pp => 33,
+            Dynamic::P => 49,
+            Dynamic::Mp => 64,
+            Dynamic::Mf => 80,
+            Dynamic::F => 96,
+            Dynamic::Ff => 112,
+            Dynamic::Fff => 127,
+        }
+    }
+
+    pub fn from_velocity(velocity: u8) -> Dynamic {
+        match velocity {
+            0..=24 => Dynamic::Ppp,
+            25..=40 => Dynamic::Pp,
+            41..=56 => Dynamic::P,
+            57..=72 => Dynamic::Mp,
+            73..=88 => Dynamic::Mf,
+            89..=104 => Dynamic::F,
+            105..=119 => Dynamic::Ff,
+            _ => Dynamic::Fff,
+        }
+    }
+}
+
+const DEFAULT_VELOCITY: u8 = 64;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Note {
+    pitch: Pitch,
+    duration: Duration,
+    tie: Option<Tie>,
+    articulations: Vec<Articulation>,
+    dynamics: Option<Dynamic>,
+    velocity: Option<u8>,
+}
+
+impl Note {
+    pub fn new(pitch: Pitch, duration: Duration) -> Note {
+        Note {
+            pitch,
+            duration,
+            tie: None,
+            articulations: Vec::new(),
+            dynamics: None,
+            velocity: None,
+        }
+    }
+
+    pub fn with_tie(self, tie: Tie) -> Note {
+        Note {
+            tie: Some(tie),
+            ..self
+        }
+    }
+
+    pub fn with_articulation(mut self, articulation: Articulation) -> Note {
+        self.articulations.push(articulation);
+        self
+    }
+
+    pub fn with_dynamics(self, dynamics: Dynamic) -> Note {
+        Note {
+            dynamics: Some(dynamics),
+            ..self
+        }
+    }
+
+    #[requires(velocity <= 127, "velocity must be no more than 127")]
+    pub fn with_velocity(self, velocity: u8) -> Note {
+        Note {
+            velocity: Some(velocity),
+            ..self
+        }
+    }
+
+    pub fn pitch(&self) -> Pitch {
+        self.pitch
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    pub fn tie(&self) -> Option<Tie> {
+        self.tie
+    }
+
+    pub fn articulations(&self) -> &[Articulation] {
+        &self.articulations
+    }
+
+    pub fn dynamics(&self) -> Option<Dynamic> {
+        self.dynamics
+    }
+
+    pub fn velocity(&self) -> u8 {
+        self.velocity
+            .or_else(|| self.dynamics.map(|d| d.velocity()))
+            .unwrap_or(DEFAULT_VELOCITY)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Rest {
+    duration: Duration,
+}
+
+impl Rest {
+    pub fn new(duration: Duration) -> Rest {
+        Rest { duration }
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ChordNote {
+    pitches: Vec<Pitch>,
+    duration: Duration,
+    tie: Option<Tie>,
+    articulations: Vec<Articulation>,
+    dynamics: Option<Dynamic>,
+    velocity: Option<u8>,
+}
+
+impl ChordNote {
+    #[requires(!pitches.is_empty(), "chord must have at least one pitch")]
+    pub fn new(pitches: Vec<Pitch>, duration: Duration) -> ChordNote {
+        ChordNote {
+            pitches,
+            duration,
+            tie: None,
+            articulations: Vec::new(),
+            dynamics: None,
+            velocity: None,
+        }
+    }
+
+    pub fn from_chord(chord: &Chord, duration: Duration) -> Result<ChordNote, &'static str> {
+        Ok(ChordNote::new(chord.pitches()?, duration))
+    }
+
+    pub fn with_tie(self, tie: Tie) -> ChordNote {
+        ChordNote {
+            tie: Some(tie),
+            ..self
+        }
+    }
+
+    pub fn with_articulation(mut self, articulation: Articulation) -> ChordNote {
+        self.articulations.push(articulation);
+        self
+    }
+
+    pub fn with_dynamics(self, dynamics: Dynamic) -> ChordNote {
+        ChordNote {
+            dynamics: Some(dynamics),
+            ..self
+        }
+    }
+
+    #[requires(velocity <= 127, "velocity must be no more than 127")]
+    pub fn with_velocity(self, velocity: u8) -> ChordNote {
+        ChordNote {
+            velocity: Some(velocity),
+            ..self
+        }
+    }
+
+    pub fn pitches(&self) -> &[Pitch] {
+        &self.pitches
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    pub fn tie(&self) -> Option<Tie> {
+        self.tie
+    }
+
+    pub fn articulations(&self) -> &[Articulation] {
+        &self.articulations
+    }
+
+    pub fn dynamics(&self) -> Option<Dynamic> {
+        self.dynamics
+    }
+
+    pub fn velocity(&self) -> u8 {
+        self.velocity
+            .or_else(|| self.dynamics.map(|d| d.velocity()))
+            .unwrap_or(DEFAULT_VELOCITY)
+    }
+
+    pub fn notes(&self) -> Vec<Note> {
+        self.pitches
+            .iter()
+            .map(|pitch| Note {
+                pitch: *pitch,
+                duration: self.duration,
+                tie: self.tie,
+                articulations: self.articulations.clone(),
+                dynamics: self.dynamics,
+                velocity: self.velocity,
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Event {
+    Note(Note),
+    Rest(Rest),
+    Chord(ChordNote),
+}
+
+impl Event {
+    pub fn duration(&self) -> Duration {
+        match self {
+            Event::Note(note) => note.duration(),
+            Event::Rest(rest) => rest.duration(),
+            Event::Chord(chord) => chord.duration(),
+        }
+    }
+
+    pub fn length(&self) -> Fraction {
+        self.duration().to_fraction()
+    }
+
+    pub fn pitches(&self) -> Vec<Pitch> {
+        match self {
+            Event::Note(note) => vec![note.pitch()],
+            Event::Rest(_) => Vec::new(),
+            Event::Chord(chord) => chord.pitches().to_vec(),
+        }
+    }
+
+    pub fn is_rest(&self) -> bool {
+        matches!(self, Event::Rest(_))
+    }
+}
+
+impl From<Note> for Event {
+    fn from(note: Note) -> Self {
+        Event::Note(note)
+    }
+}
+
+impl From<Rest> for Event {
+    fn from(rest: Rest) -> Self {
+        Event::Rest(rest)
+    }
+}
+
+impl From<ChordNote> for Event {
+    fn from(chord: ChordNote) -> Self {
+        Event::Chord(chord)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::music::pitch::*;
+    use crate::music::{ChordQuality, Tuplet};
+
+    #[test]
+    fn test_note() {
+        let note = Note::new(C4, Duration::new(4))
+            .with_tie(Tie::Start)
+            .with_articulation(Articulation::Staccato)
+            .with_articulation(Articulation::Accent);
+        assert_eq!(C4, note.pitch());
+        assert_eq!(Some(Tie::Start), note.tie());
+        assert_eq!(
+            &[Articulation::Staccato, Articulation::Accent],
+            note.articulations()
+        );
+        assert_eq!(None, note.dynamics());
+        assert_eq!(64, note.velocity());
+    }
+
+    #[test]
+    fn test_velocity() {
+        let note = Note::new(A4, Duration::new(8)).with_dynamics(Dynamic::F);
+        assert_eq!(96, note.velocity());
+        assert_eq!(100, note.with_velocity(100).velocity());
+        assert_eq!(Dynamic::Mf, Dynamic::from_velocity(80));
+        assert_eq!(Dynamic::Ppp, Dynamic::from_velocity(0));
+        assert_eq!(Dynamic::Fff, Dynamic::from_velocity(127));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_illegal_velocity() {
+        let _illegal = Note::new(A4, Duration::new(8)).with_velocity(128);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_empty_chord_note() {
+        let _illegal = ChordNote::new(Vec::new(), Duration::new(4));
+    }
+
+    #[test]
+    fn test_chord_note() {
+        let chord = Chord::new(C4, ChordQuality::Major);
+        let chord = ChordNote::from_chord(&chord, Duration::new(2))
+            .unwrap()
+            .with_dynamics(Dynamic::P);
+        assert!(
+            ChordNote::from_chord(&Chord::new(G9, ChordQuality::Major), Duration::new(2)).is_err()
+        );
+        assert_eq!(&[C4, E4, G4], chord.pitches());
+        let notes = chord.notes();
+        assert_eq!(3, notes.len());
+        assert_eq!(E4, notes[1].pitch());
+        assert_eq!(Duration::new(2), notes[1].duration());
+        assert_eq!(Some(Dynamic::P), notes[1].dynamics());
+    }
+
+    #[test]
+    fn test_event() {
+        let triplet = Duration::new(8).with_tuplet(Tuplet::TRIPLET);
+        let events: Vec<Event> = vec![
+            Note::new(C4, triplet).into(),
+            Rest::new(triplet).into(),
+            ChordNote::new(vec![D4, F4], triplet).into(),
+        ];
+        let total = events
+            .iter()
+            .fold(Fraction::ZERO, |total, event| total + event.length());
+        assert_eq!(Fraction::new(1, 4), total);
+        assert!(events[1].is_rest());
+        assert!(events[1].pitches().is_empty());
+        assert_eq!(vec![D4, F4], events[2].pitches());
+    }
+}
@@ -3,7 +3,24 @@
 use super::*;
 use super::{FLAT, NATURAL, SHARP};
 use contracts::requires;
-use std::ops::Sub;
+use std::fmt;
+use std::ops::{Add, Sub};
+use std::str::FromStr;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Spelling {
+    Sharps,
+    Flats,
+    Key(Key),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Notation {
+    Scientific,
+    Helmholtz,
+    Unicode,
+    Midi,
+}
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Pitch {
@@ -13,7 +30,7 @@ pub struct Pitch {
 }
 
 impl Pitch {
-    #[requires(octave >= -1 && octave <= 9, "octave must be in the range -1 to 9")]
+    #[requires((-1..=9).contains(&octave), "octave must be in the range -1 to 9")]
     pub fn new(name: NoteName, octave: i32) -> Pitch {
         Pitch {
             name,
@@ -22,7 +39,7 @@ impl Pitch {
         }
     }
 
-    #[requires(octave >= -1 && octave <= 9, "octave must be in the range -1 to 9")]
+    #[requires((-1..=9).contains(&octave), "octave must be in the range -1 to 9")]
     pub fn new_with_accidental(name: NoteName, accidental: Accidental, octave: i32) -> Pitch {
         Pitch {
             name,
@@ -42,26 +59,190 @@ impl Pitch {
     pub fn accidental(&self) -> Accidental {
         self.accidental
     }
+
+    pub fn pitch_class(&self) -> i32 {
+        self.cents().div_euclid(100).rem_euclid(12)
+    }
+
+    #[requires(number <= 127, "MIDI note number must be in the range 0 to 127")]
+    pub fn from_midi(number: u8, spelling: Spelling) -> Pitch {
+        let semitones = number as i32 - 12;
+        Self::spell(semitones, spelling)
+            .or_else(|_| Self::spell(semitones, Spelling::Sharps))
+            .unwrap()
+    }
+
+    pub fn to_midi(&self) -> Option<u8> {
+        let number = self.cents() / 100 + 12;
+        if self.accidental.is_microtonal() || !(0..=127).contains(&number) {
+            None
+        } else {
+            Some(number as u8)
+        }
+    }
+
+    pub fn transpose(&self, interval: Interval) -> Result<Pitch, &'static str> {
+        let steps = self.steps() + interval.steps();
+        let name = NoteName::from_index(steps);
+        let octave = steps.div_euclid(7);
+        let accidental = self.cents() + interval.cents() - octave * 1200 - name.0 * 100;
+        Self::checked(name, Accidental(accidental), octave)
+    }
+
+    pub fn transpose_chromatic(
+        &self,
+        semitones: i32,
+        spelling: Spelling,
+    ) -> Result<Pitch, &'static str> {
+        let deviation = self.accidental.0 % 100;
+        let mut pitch = Self::spell((self.cents() - deviation) / 100 + semitones, spelling)?;
+        pitch.accidental.0 += deviation;
+        Ok(pitch)
+    }
+
+    pub fn format(&self, notation: Notation) -> String {
+        match notation {
+            Notation::Scientific => self.to_string(),
+            Notation::Helmholtz if self.octave >= 3 => format!(
+                "{}{}{}",
+                self.name.to_char().to_ascii_lowercase(),
+                self.accidental,
+                "'".repeat((self.octave - 3) as usize)
+            ),
+            Notation::Helmholtz => format!(
+                "{}{}{}",
+                self.name,
+                self.accidental,
+                ",".repeat((2 - self.octave) as usize)
+            ),
+            Notation::Unicode => format!(
+                "{}{}{}",
+                self.name,
+                self.accidental.to_unicode(),
+                self.octave
+            ),
+            Notation::Midi => match self.to_midi() {
+                Some(number) => number.to_string(),
+                None => self.to_string(),
+            },
+        }
+    }
+}
+
+impl Pitch {
+    const SHARP_SPELLINGS: [(NoteName, Accidental); 12] = [
+        (C, NATURAL),
+        (C, SHARP),
+        (D, NATURAL),
+        (D, SHARP),
+        (E, NATURAL),
+        (F, NATURAL),
+        (F, SHARP),
+        (G, NATURAL),
+        (G, SHARP),
+        (A, NATURAL),
+        (A, SHARP),
+        (B, NATURAL),
+    ];
+
+    const FLAT_SPELLINGS: [(NoteName, Accidental); 12] = [
+        (C, NATURAL),
+        (D, FLAT),
+        (D, NATURAL),
+        (E, FLAT),
+        (E, NATURAL),
+        (F, NATURAL),
+        (G, FLAT),
+        (G, NATURAL),
+        (A, FLAT),
+        (A, NATURAL),
+        (B, FLAT),
+        (B, NATURAL),
+    ];
+
+    fn checked(name: NoteName, accidental: Accidental, octave: i32) -> Result<Pitch, &'static str> {
+        if !(-1..=9).contains(&octave) {
+            return Err("octave must be in the range -1 to 9");
+        }
+
+        Ok(Pitch {
+            name,
+            octave,
+            accidental,
+        })
+    }
+
+    fn spell(semitones: i32, spelling: Spelling) -> Result<Pitch, &'static str> {
+        let (name, accidental) = match spelling {
+            Spelling::Sharps => Self::SHARP_SPELLINGS[semitones.rem_euclid(12) as usize],
+            Spelling::Flats => Self::FLAT_SPELLINGS[semitones.rem_euclid(12) as usize],
+            Spelling::Key(key) => key.spell(semitones),
+        };
+        let octave = (semitones - name.0 - accidental.semitones()).div_euclid(12);
+        Self::checked(name, accidental, octave)
+    }
+
+    fn steps(&self) -> i32 {
+        self.octave * 7 + self.name.index()
+    }
+
+    fn cents(&self) -> i32 {
+        (self.octave * 12 + self.name.0) * 100 + self.accidental.0
+    }
+}
+
+impl fmt::Display for Pitch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}", self.name, self.accidental, self.octave)
+    }
+}
+
+impl FromStr for Pitch {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let letter = s.chars().next().ok_or("pitch must not be empty")?;
+        let name = NoteName::from_str(&s[..letter.len_utf8()])?;
+        let (accidental, rest) = Accidental::parse_prefix(&s[letter.len_utf8()..])?;
+
+        let octave = if rest.starts_with(|c: char| c.is_ascii_digit() || c == '-') {
+            rest.parse::<i32>().map_err(|_| "invalid octave")?
+        } else if letter.is_ascii_lowercase() && rest.chars().all(|c| c == '\'') {
+            3 + rest.len() as i32
+        } else if letter.is_ascii_uppercase() && rest.chars().all(|c| c == ',') {
+            2 - rest.len() as i32
+        } else {
+            return Err("invalid octave");
+        };
+
+        Self::checked(name, accidental, octave)
+    }
+}
+
+impl Add<Interval> for Pitch {
+    type Output = Self;
+
+    #[requires(self.transpose(interval).is_ok(), "octave must be in the range -1 to 9")]
+    fn add(self, interval: Interval) -> Self::Output {
+        self.transpose(interval).unwrap()
+    }
 }
 
 impl Sub for Pitch {
-    type Output = i32;
+    type Output = Interval;
 
     fn sub(self, other: Self) -> Self::Output {
-        let from: i32 = self.name.0 + self.octave * 12 + self.accidental.0;
-        let to: i32 = other.name.0 + other.octave * 12 + other.accidental.0;
-        from - to
+        Interval::from_cents(self.steps() - other.steps(), self.cents() - other.cents())
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+impl Sub<Interval> for Pitch {
+    type Output = Self;
 
-    #[test]
-    fn test_op_sub() {
-        assert_eq!(0, C4 - C4);
-        assert_eq!(-12, C4 - C5);
+    #[requires(self.transpose(-interval).is_ok(), "octave must be in the range -1 to 9")]
+    fn sub(self, interval: Interval) -> Self::Output {
+        self + -interval
     }
 }
 
@@ -1010,3 +1191,254 @@ pub const B9: Pitch = Pitch {
     octave: 9,
     accidental: NATURAL,
 };
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_op_add() {
+        assert_eq!(D4, Bb3 + Interval::MAJOR_THIRD);
+        assert_eq!(F4, E4 + Interval::MINOR_SECOND);
+        assert_eq!(
+            Pitch::new_with_accidental(E, SHARP, 4),
+            E4 + Interval::new(Quality::Augmented(1), 1)
+        );
+        assert_eq!(Gb4, C4 + Interval::DIMINISHED_FIFTH);
+        assert_eq!(Fs4, C4 + Interval::AUGMENTED_FOURTH);
+        assert_eq!(E5, C4 + Interval::new(Quality::Major, 10));
+        assert_eq!(Eb3, C4 + Interval::new(Quality::Major, -6));
+        assert_eq!(
+            Pitch::new_with_accidental(C, FLAT, 4),
+            Bb3 + Interval::new(Quality::Minor, 2)
+        );
+    }
+
+    #[test]
+    fn test_op_sub() {
+        assert_eq!(0, (C4 - C4).semitones());
+        assert_eq!(-12, (C4 - C5).semitones());
+        assert_eq!(Interval::PERFECT_OCTAVE, C5 - C4);
+        assert_eq!(Interval::DIMINISHED_FIFTH, Gb4 - C4);
+        assert_eq!(Interval::AUGMENTED_FOURTH, Fs4 - C4);
+        assert_eq!(Interval::MINOR_THIRD, C5 - A4);
+        assert_eq!(-Interval::MAJOR_SIXTH, C4 - A4);
+        assert_eq!(
+            Quality::Diminished(1),
+            (Pitch::new_with_accidental(C, FLAT, 4) - C4).quality()
+        );
+        assert_eq!(Bb3, D4 - Interval::MAJOR_THIRD);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_op_add_out_of_range() {
+        let _illegal = B9 + Interval::MINOR_SECOND;
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_op_sub_out_of_range() {
+        let _illegal = C_1 - Interval::MINOR_SECOND;
+    }
+
+    #[test]
+    fn test_transpose() {
+        assert_eq!(Ok(D4), Bb3.transpose(Interval::MAJOR_THIRD));
+        assert_eq!(Ok(F4), E4.transpose(Interval::MINOR_SECOND));
+        assert_eq!(Ok(Ab3), C4.transpose(-Interval::MAJOR_THIRD));
+        assert_eq!(Ok(C9), C_1.transpose(Interval::new(Quality::Perfect, 71)));
+        assert!(B9.transpose(Interval::MINOR_SECOND).is_err());
+        assert!(C_1.transpose(-Interval::MINOR_SECOND).is_err());
+    }
+
+    #[test]
+    fn test_transpose_chromatic() {
+        assert_eq!(Ok(F4), E4.transpose_chromatic(1, Spelling::Sharps));
+        assert_eq!(Ok(F4), E4.transpose_chromatic(1, Spelling::Flats));
+        assert_eq!(Ok(Cs5), B4.transpose_chromatic(2, Spelling::Sharps));
+        assert_eq!(Ok(Db5), B4.transpose_chromatic(2, Spelling::Flats));
+        assert_eq!(Ok(Bb3), C4.transpose_chromatic(-2, Spelling::Flats));
+        assert_eq!(Ok(C4), Bb3.transpose_chromatic(2, Spelling::Flats));
+        assert!(B9.transpose_chromatic(1, Spelling::Sharps).is_err());
+        assert!(C_1.transpose_chromatic(-1, Spelling::Flats).is_err());
+        assert_eq!(
+            Ok(Pitch::new_with_accidental(D, SESQUI_SHARP, 4)),
+            Pitch::new_with_accidental(C, SESQUI_SHARP, 4).transpose_chromatic(2, Spelling::Sharps)
+        );
+    }
+
+    #[test]
+    fn test_double_accidentals() {
+        let fx4 = Pitch::new_with_accidental(F, DOUBLE_SHARP, 4);
+        let abb4 = Pitch::new_with_accidental(A, DOUBLE_FLAT, 4);
+        assert_eq!(0, (fx4 - G4).semitones());
+        assert_eq!(Quality::Diminished(3), (abb4 - fx4).quality());
+        assert_eq!(3, (abb4 - fx4).number());
+        assert_eq!(Ok(fx4), Ds4.transpose(Interval::MAJOR_THIRD));
+        assert_eq!(Ok(abb4), Db4.transpose(Interval::DIMINISHED_FIFTH));
+    }
+
+    #[test]
+    fn test_microtonal_accidentals() {
+        let d_half_flat = Pitch::new_with_accidental(D, HALF_FLAT, 4);
+        let interval = d_half_flat - C4;
+        assert_eq!(150, interval.cents());
+        assert_eq!(2, interval.number());
+        assert_eq!(Ok(d_half_flat), C4.transpose(interval));
+        let e_sesqui_flat = Pitch::new_with_accidental(E, SESQUI_FLAT, 4);
+        assert_eq!(-100, (d_half_flat - e_sesqui_flat).cents());
+        assert_eq!(Ok(e_sesqui_flat), D4.transpose(e_sesqui_flat - D4));
+    }
+
+    #[test]
+    fn test_pitch_class() {
+        assert_eq!(0, C4.pitch_class());
+        assert_eq!(0, Pitch::new_with_accidental(B, SHARP, 3).pitch_class());
+        assert_eq!(11, Pitch::new_with_accidental(C, FLAT, -1).pitch_class());
+        assert_eq!(10, Bb_1.pitch_class());
+        assert_eq!(
+            Pitch::new_with_accidental(C, HALF_FLAT, 4).pitch_class(),
+            Pitch::new_with_accidental(C, HALF_FLAT, -1).pitch_class()
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!("C4", C4.to_string());
+        assert_eq!("C#4", Cs4.to_string());
+        assert_eq!("Bb-1", Bb_1.to_string());
+        assert_eq!(
+            "Fx3",
+            Pitch::new_with_accidental(F, DOUBLE_SHARP, 3).to_string()
+        );
+        assert_eq!(
+            "Ebb5",
+            Pitch::new_with_accidental(E, DOUBLE_FLAT, 5).to_string()
+        );
+        assert_eq!(
+            "D`4",
+            Pitch::new_with_accidental(D, HALF_FLAT, 4).to_string()
+        );
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!("c'", C4.format(Notation::Helmholtz));
+        assert_eq!("c", C3.format(Notation::Helmholtz));
+        assert_eq!("C", C2.format(Notation::Helmholtz));
+        assert_eq!("C,,", C0.format(Notation::Helmholtz));
+        assert_eq!("f#''", Fs5.format(Notation::Helmholtz));
+        assert_eq!("B\u{266d}3", Bb3.format(Notation::Unicode));
+        assert_eq!("C\u{266f}4", Cs4.format(Notation::Unicode));
+        assert_eq!("60", C4.format(Notation::Midi));
+        assert_eq!("0", C_1.format(Notation::Midi));
+        assert_eq!("127", G9.format(Notation::Midi));
+        assert_eq!("G#9", Gs9.format(Notation::Midi));
+        assert_eq!("C4", C4.format(Notation::Scientific));
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!(Ok(Cs4), "C#4".parse());
+        assert_eq!(Ok(Bb_1), "Bb-1".parse());
+        assert_eq!(
+            Ok(Pitch::new_with_accidental(F, DOUBLE_SHARP, 3)),
+            "Fx3".parse()
+        );
+        assert_eq!(
+            Ok(Pitch::new_with_accidental(E, DOUBLE_FLAT, 5)),
+            "Ebb5".parse()
+        );
+        assert_eq!(Ok(Bb3), "B\u{266d}3".parse());
+        assert_eq!(Ok(C4), "c'".parse());
+        assert_eq!(Ok(C3), "c".parse());
+        assert_eq!(Ok(C0), "C,,".parse());
+        assert_eq!(Ok(Bb4), "bb'".parse());
+        assert_eq!(Ok(Fs2), "F#".parse());
+        assert!("".parse::<Pitch>().is_err());
+        assert!("H4".parse::<Pitch>().is_err());
+        assert!("C10".parse::<Pitch>().is_err());
+        assert!("C-2".parse::<Pitch>().is_err());
+        assert!("c,".parse::<Pitch>().is_err());
+        assert!("C4x".parse::<Pitch>().is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let pitches = [
+            C_1,
+            Bb_1,
+            Cs4,
+            G9,
+            Pitch::new_with_accidental(F, DOUBLE_SHARP, 3),
+            Pitch::new_with_accidental(E, SESQUI_FLAT, 5),
+            Pitch::new_with_accidental(A, Accidental::from_cents(-14), 4),
+        ];
+        for pitch in pitches.iter() {
+            assert_eq!(Ok(*pitch), pitch.to_string().parse());
+            assert_eq!(Ok(*pitch), pitch.format(Notation::Helmholtz).parse());
+            assert_eq!(Ok(*pitch), pitch.format(Notation::Unicode).parse());
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_from_midi_out_of_range() {
+        let _illegal = Pitch::from_midi(128, Spelling::Sharps);
+    }
+
+    #[test]
+    fn test_from_midi() {
+        assert_eq!(C_1, Pitch::from_midi(0, Spelling::Sharps));
+        assert_eq!(C4, Pitch::from_midi(60, Spelling::Flats));
+        assert_eq!(Cs4, Pitch::from_midi(61, Spelling::Sharps));
+        assert_eq!(Db4, Pitch::from_midi(61, Spelling::Flats));
+        assert_eq!(A4, Pitch::from_midi(69, Spelling::Sharps));
+        assert_eq!(G9, Pitch::from_midi(127, Spelling::Flats));
+        assert_eq!(Gb9, Pitch::from_midi(126, Spelling::Flats));
+
+        let key = Spelling::Key(Key::new(C, SHARP, Mode::Major));
+        assert_eq!(
+            Pitch::new_with_accidental(B, SHARP, 3),
+            Pitch::from_midi(60, key)
+        );
+        assert_eq!(
+            Pitch::new_with_accidental(E, SHARP, 4),
+            Pitch::from_midi(65, key)
+        );
+        assert_eq!(C_1, Pitch::from_midi(0, key));
+        let key = Spelling::Key(Key::new(G, NATURAL, Mode::Minor));
+        assert_eq!(Fs4, Pitch::from_midi(66, key));
+        assert_eq!(Eb4, Pitch::from_midi(63, key));
+        let key = Spelling::Key(Key::new(G, FLAT, Mode::Major));
+        assert_eq!(
+            Pitch::new_with_accidental(C, FLAT, 5),
+            Pitch::from_midi(71, key)
+        );
+    }
+
+    #[test]
+    fn test_to_midi() {
+        assert_eq!(Some(0), C_1.to_midi());
+        assert_eq!(Some(60), C4.to_midi());
+        assert_eq!(Some(69), A4.to_midi());
+        assert_eq!(Some(127), G9.to_midi());
+        assert_eq!(Some(60), Pitch::new_with_accidental(B, SHARP, 3).to_midi());
+        assert_eq!(Some(11), Pitch::new_with_accidental(C, FLAT, 0).to_midi());
+        assert_eq!(None, Pitch::new_with_accidental(C, FLAT, -1).to_midi());
+        assert_eq!(None, Pitch::new_with_accidental(B, SHARP, 9).to_midi());
+        assert_eq!(None, Gs9.to_midi());
+        assert_eq!(None, Pitch::new_with_accidental(A, HALF_SHARP, 4).to_midi());
+        for number in 0..=127 {
+            assert_eq!(
+                Some(number),
+                Pitch::from_midi(number, Spelling::Sharps).to_midi()
+            );
+            assert_eq!(
+                Some(number),
+                Pitch::from_midi(number, Spelling::Flats).to_midi()
+            );
+        }
+    }
+}
@@ -0,0 +1,416 @@
+use super::*;
+use contracts::requires;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ScaleKind {
+    Ionian,
+    Dorian,
+    Phrygian,
+    Lydian,
+    Mixolydian,
+    Aeolian,
+    Locrian,
+    HarmonicMinor,
+    LocrianNatural6,
+    IonianAugmented,
+    DorianSharp4,
+    PhrygianDominant,
+    LydianSharp2,
+    SuperLocrian,
+    MelodicMinor,
+    DorianFlat2,
+    LydianAugmented,
+    LydianDominant,
+    MixolydianFlat6,
+    LocrianNatural2,
+    Altered,
+    MajorPentatonic,
+    MinorPentatonic,
+    Blues,
+    WholeTone,
+    OctatonicHalfWhole,
+    OctatonicWholeHalf,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Scale {
+    tonic: Pitch,
+    intervals: Vec<Interval>,
+}
+
+impl ScaleKind {
+    pub fn intervals(&self) -> Vec<Interval> {
+        let major = || {
+            vec![
+                Interval::PERFECT_UNISON,
+                Interval::MAJOR_SECOND,
+                Interval::MAJOR_THIRD,
+                Interval::PERFECT_FOURTH,
+                Interval::PERFECT_FIFTH,
+                Interval::MAJOR_SIXTH,
+                Interval::MAJOR_SEVENTH,
+            ]
+        };
+        let harmonic_minor = || {
+            vec![
+                Interval::PERFECT_UNISON,
+                Interval::MAJOR_SECOND,
+                Interval::MINOR_THIRD,
+                Interval::PERFECT_FOURTH,
+                Interval::PERFECT_FIFTH,
+                Interval::MINOR_SIXTH,
+                Interval::MAJOR_SEVENTH,
+            ]
+        };
+        let melodic_minor = || {
+            vec![
+                Interval::PERFECT_UNISON,
+                Interval::MAJOR_SECOND,
+                Interval::MINOR_THIRD,
+                Interval::PERFECT_FOURTH,
+                Interval::PERFECT_FIFTH,
+                Interval::MAJOR_SIXTH,
+                Interval::MAJOR_SEVENTH,
+            ]
+        };
+
+        match self {
+            ScaleKind::Ionian => major(),
+            ScaleKind::Dorian => rotate(&major(), 1),
+            ScaleKind::Phrygian => rotate(&major(), 2),
+            ScaleKind::Lydian => rotate(&major(), 3),
+            ScaleKind::Mixolydian => rotate(&major(), 4),
+            ScaleKind::Aeolian => rotate(&major(), 5),
+            ScaleKind::Locrian => rotate(&major(), 6),
+            ScaleKind::HarmonicMinor => harmonic_minor(),
+            ScaleKind::LocrianNatural6 => rotate(&harmonic_minor(), 1),
+            ScaleKind::IonianAugmented => rotate(&harmonic_minor(), 2),
+            ScaleKind::DorianSharp4 => rotate(&harmonic_minor(), 3),
+            ScaleKind::PhrygianDominant => rotate(&harmonic_minor(), 4),
+            ScaleKind::LydianSharp2 => rotate(&harmonic_minor(), 5),
+            ScaleKind::SuperLocrian => rotate(&harmonic_minor(), 6),
+            ScaleKind::MelodicMinor => melodic_minor(),
+            ScaleKind::DorianFlat2 => rotate(&melodic_minor(), 1),
+            ScaleKind::LydianAugmented => rotate(&melodic_minor(), 2),
+            ScaleKind::LydianDominant => rotate(&melodic_minor(), 3),
+            ScaleKind::MixolydianFlat6 => rotate(&melodic_minor(), 4),
+            ScaleKind::LocrianNatural2 => rotate(&melodic_minor(), 5),
+            ScaleKind::Altered => rotate(&melodic_minor(), 6),
+            ScaleKind::MajorPentatonic => vec![
+                Interval::PERFECT_UNISON,
+                Interval::MAJOR_SECOND,
+                Interval::MAJOR_THIRD,
+                Interval::PERFECT_FIFTH,
+                Interval::MAJOR_SIXTH,
+            ],
+            ScaleKind::MinorPentatonic => vec![
+                Interval::PERFECT_UNISON,
+                Interval::MINOR_THIRD,
+                Interval::PERFECT_FOURTH,
+                Interval::PERFECT_FIFTH,
+                Interval::MINOR_SEVENTH,
+            ],
+            ScaleKind::Blues => vec![
+                Interval::PERFECT_UNISON,
+                Interval::MINOR_THIRD,
+                Interval::PERFECT_FOURTH,
+                Interval::AUGMENTED_FOURTH,
+                Interval::PERFECT_FIFTH,
+                Interval::MINOR_SEVENTH,
+            ],
+            ScaleKind::WholeTone => vec![
+                Interval::PERFECT_UNISON,
+                Interval::MAJOR_SECOND,
+                Interval::MAJOR_THIRD,
+                Interval::AUGMENTED_FOURTH,
+                Interval::new(Quality::Augmented(1), 5),
+                Interval::new(Quality::Augmented(1), 6),
+            ],
+            ScaleKind::OctatonicHalfWhole => vec![
+                Interval::PERFECT_UNISON,
+                Interval::MINOR_SECOND,
+                Interval::MINOR_THIRD,
+                Interval::MAJOR_THIRD,
+                Interval::AUGMENTED_FOURTH,
+                Interval::PERFECT_FIFTH,
+                Interval::MAJOR_SIXTH,
+                Interval::MINOR_SEVENTH,
+            ],
+            ScaleKind::OctatonicWholeHalf => vec![
+                Interval::PERFECT_UNISON,
+                Interval::MAJOR_SECOND,
+                Interval::MINOR_THIRD,
+                Interval::PERFECT_FOURTH,
+                Interval::DIMINISHED_FIFTH,
+                Interval::MINOR_SIXTH,
+                Interval::MAJOR_SIXTH,
+                Interval::MAJOR_SEVENTH,
+            ],
+        }
+    }
+}
+
+fn rotate(intervals: &[Interval], mode: usize) -> Vec<Interval> {
+    let base = intervals[mode];
+    (0..intervals.len())
+        .map(|i| {
+            let j = (i + mode) % intervals.len();
+            if j < mode {
+                intervals[j] - base + Interval::PERFECT_OCTAVE
+            } else {
+                intervals[j] - base
+            }
+        })
+        .collect()
+}
+
+impl Scale {
+    pub fn new(tonic: Pitch, kind: ScaleKind) -> Scale {
+        Scale {
+            tonic,
+            intervals: kind.intervals(),
+        }
+    }
+
+    #[requires(intervals.first() == Some(&Interval::PERFECT_UNISON), "pattern must start with a unison")]
+    #[requires(intervals.windows(2).all(|w| w[0].cents() < w[1].cents() && w[0].steps() <= w[1].steps()), "pattern must be ascending")]
+    #[requires(intervals.last().is_some_and(|i| i.cents() < 1200), "pattern must fit in an octave")]
+    pub fn from_intervals(tonic: Pitch, intervals: Vec<Interval>) -> Scale {
+        Scale { tonic, intervals }
+    }
+
+    pub fn tonic(&self) -> Pitch {
+        self.tonic
+    }
+
+    pub fn intervals(&self) -> &[Interval] {
+        &self.intervals
+    }
+
+    pub fn len(&self) -> usize {
+        self.intervals.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    pub fn degree(&self, degree: i32) -> Result<Pitch, &'static str> {
+        let len = self.len() as i32;
+        let index = degree - 1;
+        let octaves = Interval::from_cents(7 * index.div_euclid(len), 1200 * index.div_euclid(len));
+        self.tonic
+            .transpose(self.intervals[index.rem_euclid(len) as usize] + octaves)
+    }
+
+    pub fn degree_of(&self, pitch: &Pitch) -> Option<usize> {
+        self.intervals
+            .iter()
+            .position(|i| {
+                let offset = (*pitch - self.tonic) - *i;
+                offset.steps().rem_euclid(7) == 0 && offset.cents() == offset.steps() / 7 * 1200
+            })
+            .map(|index| index + 1)
+    }
+
+    pub fn contains(&self, pitch: &Pitch) -> bool {
+        self.degree_of(pitch).is_some()
+    }
+
+    #[requires((to - from).cents() >= 0, "range must be ascending")]
+    pub fn pitches(&self, from: Pitch, to: Pitch) -> Vec<Pitch> {
+        let len = self.len() as i32;
+        let start = ((from - self.tonic).cents().div_euclid(1200) - 1) * len + 1;
+
+        let mut pitches = Vec::new();
+        for degree in start.. {
+            match self.degree(degree) {
+                Ok(pitch) if (pitch - to).cents() > 0 => break,
+                Ok(pitch) if (pitch - from).cents() >= 0 => pitches.push(pitch),
+                Ok(_) => {}
+                Err(_) if degree > 0 => break,
+                Err(_) => {}
+            }
+        }
+        pitches
+    }
+
+    #[requires(degree >= 1, "degree must be at least 1")]
+    pub fn mode(&self, degree: usize) -> Result<Scale, &'static str> {
+        let index = (degree - 1) % self.len();
+        Ok(Scale {
+            tonic: self.tonic.transpose(self.intervals[index])?,
+            intervals: rotate(&self.intervals, index),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spell(scale: &Scale) -> Vec<String> {
+        (1..=scale.len() as i32)
+            .map(|d| scale.degree(d).unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_diatonic_modes() {
+        assert_eq!(
+            vec!["C4", "D4", "E4", "F4", "G4", "A4", "B4"],
+            spell(&Scale::new(C4, ScaleKind::Ionian))
+        );
+        assert_eq!(
+            vec!["D4", "E4", "F4", "G4", "A4", "B4", "C5"],
+            spell(&Scale::new(D4, ScaleKind::Dorian))
+        );
+        assert_eq!(
+            vec!["F#4", "G#4", "A#4", "B4", "C#5", "D#5", "E#5"],
+            spell(&Scale::new(Fs4, ScaleKind::Ionian))
+        );
+        assert_eq!(
+            vec!["Eb4", "Fb4", "Gb4", "Ab4", "Bbb4", "Cb5", "Db5"],
+            spell(&Scale::new(Eb4, ScaleKind::Locrian))
+        );
+        assert_eq!(
+            vec!["Bb3", "C4", "D4", "E4", "F4", "G4", "A4"],
+            spell(&Scale::new(Bb3, ScaleKind::Lydian))
+        );
+    }
+
+    #[test]
+    fn test_minor_scales() {
+        assert_eq!(
+            vec!["A4", "B4", "C5", "D5", "E5", "F5", "G#5"],
+            spell(&Scale::new(A4, ScaleKind::HarmonicMinor))
+        );
+        assert_eq!(
+            vec!["E4", "F4", "G#4", "A4", "B4", "C5", "D5"],
+            spell(&Scale::new(E4, ScaleKind::PhrygianDominant))
+        );
+        assert_eq!(
+            vec!["C4", "D4", "Eb4", "F4", "G4", "A4", "B4"],
+            spell(&Scale::new(C4, ScaleKind::MelodicMinor))
+        );
+        assert_eq!(
+            vec!["G4", "Ab4", "Bb4", "Cb5", "Db5", "Eb5", "F5"],
+            spell(&Scale::new(G4, ScaleKind::Altered))
+        );
+        assert_eq!(
+            vec!["F4", "G4", "A4", "B4", "C5", "D5", "Eb5"],
+            spell(&Scale::new(F4, ScaleKind::LydianDominant))
+        );
+    }
+
+    #[test]
+    fn test_symmetric_scales() {
+        assert_eq!(
+            vec!["C4", "D4", "E4", "F#4", "G#4", "A#4"],
+            spell(&Scale::new(C4, ScaleKind::WholeTone))
+        );
+        assert_eq!(
+            vec!["C4", "Db4", "Eb4", "E4", "F#4", "G4", "A4", "Bb4"],
+            spell(&Scale::new(C4, ScaleKind::OctatonicHalfWhole))
+        );
+        assert_eq!(
+            vec!["C4", "D4", "Eb4", "F4", "Gb4", "Ab4", "A4", "B4"],
+            spell(&Scale::new(C4, ScaleKind::OctatonicWholeHalf))
+        );
+        assert_eq!(
+            vec!["A3", "C4", "D4", "D#4", "E4", "G4"],
+            spell(&Scale::new(A3, ScaleKind::Blues))
+        );
+        assert_eq!(
+            vec!["G4", "A4", "B4", "D5", "E5"],
+            spell(&Scale::new(G4, ScaleKind::MajorPentatonic))
+        );
+    }
+
+    #[test]
+    fn test_from_intervals() {
+        let hirajoshi = Scale::from_intervals(
+            E4,
+            vec![
+                Interval::PERFECT_UNISON,
+                Interval::MINOR_SECOND,
+                Interval::PERFECT_FOURTH,
+                Interval::PERFECT_FIFTH,
+                Interval::MINOR_SIXTH,
+            ],
+        );
+        assert_eq!(vec!["E4", "F4", "A4", "B4", "C5"], spell(&hirajoshi));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_from_intervals_not_ascending() {
+        let _illegal = Scale::from_intervals(
+            C4,
+            vec![
+                Interval::PERFECT_UNISON,
+                Interval::MAJOR_THIRD,
+                Interval::MAJOR_SECOND,
+            ],
+        );
+    }
+
+    #[test]
+    fn test_degree() {
+        let scale = Scale::new(C4, ScaleKind::Ionian);
+        assert_eq!(Ok(C5), scale.degree(8));
+        assert_eq!(Ok(E5), scale.degree(10));
+        assert_eq!(Ok(B3), scale.degree(0));
+        assert_eq!(Ok(C3), scale.degree(-6));
+        assert!(Scale::new(C9, ScaleKind::Ionian).degree(8).is_err());
+    }
+
+    #[test]
+    fn test_degree_of() {
+        let scale = Scale::new(D4, ScaleKind::Ionian);
+        assert_eq!(Some(1), scale.degree_of(&D2));
+        assert_eq!(Some(3), scale.degree_of(&Fs5));
+        assert_eq!(None, scale.degree_of(&F4));
+        assert_eq!(None, scale.degree_of(&Gb4));
+        assert!(scale.contains(&Cs6));
+        assert!(!scale.contains(&Db4));
+
+        let scale = Scale::new(G9, ScaleKind::Ionian);
+        assert!(scale.contains(&C4));
+        assert!(scale.contains(&Fs_1));
+        assert!(!scale.contains(&F9));
+    }
+
+    #[test]
+    fn test_pitches() {
+        let scale = Scale::new(G4, ScaleKind::MajorPentatonic);
+        assert_eq!(vec![A3, B3, D4, E4, G4, A4], scale.pitches(A3, A4));
+        assert_eq!(
+            vec![C_1, D_1],
+            Scale::new(C4, ScaleKind::Ionian).pitches(C_1, D_1)
+        );
+        assert_eq!(
+            vec![F9, G9, A9, B9],
+            Scale::new(C4, ScaleKind::Ionian).pitches(F9, B9)
+        );
+        assert!(scale.pitches(C4, Cs4).is_empty());
+    }
+
+    #[test]
+    fn test_mode() {
+        let dorian = Scale::new(C4, ScaleKind::Ionian).mode(2);
+        assert_eq!(Ok(Scale::new(D4, ScaleKind::Dorian)), dorian);
+        let minor_pentatonic = Scale::new(C4, ScaleKind::MajorPentatonic).mode(5);
+        assert_eq!(
+            Ok(Scale::new(A4, ScaleKind::MinorPentatonic)),
+            minor_pentatonic
+        );
+        assert!(Scale::new(G9, ScaleKind::Ionian).mode(5).is_err());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_mode_zero() {
+        let _illegal = Scale::new(C4, ScaleKind::Ionian).mode(0);
+    }
+}
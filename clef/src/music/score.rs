@@ -0,0 +1,427 @@
+use super::{Clef, Event, KeySignature, Mode, TimeSignature};
+use crate::math::Fraction;
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Voice {
+    events: Vec<Event>,
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Measure {
+    voices: Vec<Voice>,
+    time_signature: Option<TimeSignature>,
+    key_signature: Option<KeySignature>,
+    mode: Option<Mode>,
+    clef: Option<Clef>,
+    pickup: bool,
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Staff {
+    measures: Vec<Measure>,
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Part {
+    name: String,
+    staves: Vec<Staff>,
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Score {
+    title: Option<String>,
+    parts: Vec<Part>,
+}
+
+impl Voice {
+    pub fn new(events: Vec<Event>) -> Voice {
+        Voice { events }
+    }
+
+    pub fn push(&mut self, event: Event) {
+        self.events.push(event);
+    }
+
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    pub fn length(&self) -> Fraction {
+        self.events
+            .iter()
+            .fold(Fraction::ZERO, |length, event| length + event.length())
+    }
+
+    pub fn offsets(&self) -> Vec<Fraction> {
+        let mut offset = Fraction::ZERO;
+        self.events
+            .iter()
+            .map(|event| {
+                let start = offset;
+                offset += event.length();
+                start
+            })
+            .collect()
+    }
+
+    pub fn event_at(&self, offset: Fraction) -> Option<&Event> {
+        self.offsets()
+            .into_iter()
+            .zip(self.events.iter())
+            .find(|(start, event)| *start <= offset && offset < *start + event.length())
+            .map(|(_, event)| event)
+    }
+}
+
+impl Measure {
+    pub fn new(voices: Vec<Voice>) -> Measure {
+        Measure {
+            voices,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_time_signature(self, time_signature: TimeSignature) -> Measure {
+        Measure {
+            time_signature: Some(time_signature),
+            ..self
+        }
+    }
+
+    pub fn with_key_signature(self, key_signature: KeySignature) -> Measure {
+        Measure {
+            key_signature: Some(key_signature),
+            ..self
+        }
+    }
+
+    pub fn with_mode(self, mode: Mode) -> Measure {
+        Measure {
+            mode: Some(mode),
+            ..self
+        }
+    }
+
+    pub fn with_clef(self, clef: Clef) -> Measure {
+        Measure {
+            clef: Some(clef),
+            ..self
+        }
+    }
+
+    pub fn with_pickup(self) -> Measure {
+        Measure {
+            pickup: true,
+            ..self
+        }
+    }
+
+    pub fn push(&mut self, voice: Voice) {
+        self.voices.push(voice);
+    }
+
+    pub fn voices(&self) -> &[Voice] {
+        &self.voices
+    }
+
+    pub fn time_signature(&self) -> Option<&TimeSignature> {
+        self.time_signature.as_ref()
+    }
+
+    pub fn key_signature(&self) -> Option<KeySignature> {
+        self.key_signature
+    }
+
+    pub fn mode(&self) -> Option<Mode> {
+        self.mode
+    }
+
+    pub fn clef(&self) -> Option<Clef> {
+        self.clef
+    }
+
+    pub fn is_pickup(&self) -> bool {
+        self.pickup
+    }
+
+    pub fn length(&self, meter: &TimeSignature) -> Fraction {
+        if self.pickup {
+            self.voices
+                .iter()
+                .map(Voice::length)
+                .max()
+                .unwrap_or(Fraction::ZERO)
+        } else {
+            meter.measure_length()
+        }
+    }
+
+    pub fn validate(&self, meter: &TimeSignature) -> Result<(), &'static str> {
+        let meter = self.time_signature.as_ref().unwrap_or(meter);
+        let expected = self.length(meter);
+        if self.pickup && expected > meter.measure_length() {
+            return Err("pickup measure is longer than the time signature");
+        }
+        if self.voices.iter().any(|voice| voice.length() != expected) {
+            return Err("voice does not fill its measure");
+        }
+        Ok(())
+    }
+}
+
+impl Staff {
+    pub fn new(measures: Vec<Measure>) -> Staff {
+        Staff { measures }
+    }
+
+    pub fn push(&mut self, measure: Measure) {
+        self.measures.push(measure);
+    }
+
+    pub fn measures(&self) -> &[Measure] {
+        &self.measures
+    }
+
+    pub fn time_signature_at(&self, index: usize) -> TimeSignature {
+        self.measures[..=index]
+            .iter()
+            .rev()
+            .find_map(|measure| measure.time_signature.clone())
+            .unwrap_or_else(|| TimeSignature::new(4, 4))
+    }
+
+    pub fn key_signature_at(&self, index: usize) -> KeySignature {
+        self.measures[..=index]
+            .iter()
+            .rev()
+            .find_map(|measure| measure.key_signature)
+            .unwrap_or_else(|| KeySignature::new(0))
+    }
+
+    // The mode belongs to the measure that set the key signature in effect.
+    pub fn mode_at(&self, index: usize) -> Mode {
+        self.measures[..=index]
+            .iter()
+            .rev()
+            .find(|measure| measure.key_signature.is_some())
+            .and_then(|measure| measure.mode)
+            .unwrap_or(Mode::Major)
+    }
+
+    pub fn clef_at(&self, index: usize) -> Clef {
+        self.measures[..=index]
+            .iter()
+            .rev()
+            .find_map(|measure| measure.clef)
+            .unwrap_or(Clef::TREBLE)
+    }
+
+    pub fn measure_offset(&self, index: usize) -> Fraction {
+        (0..index).fold(Fraction::ZERO, |offset, i| {
+            offset + self.measures[i].length(&self.time_signature_at(i))
+        })
+    }
+
+    pub fn event_offset(&self, measure: usize, voice: usize, event: usize) -> Fraction {
+        self.measure_offset(measure) + self.measures[measure].voices[voice].offsets()[event]
+    }
+
+    pub fn length(&self) -> Fraction {
+        self.measure_offset(self.measures.len())
+    }
+
+    pub fn validate(&self) -> Result<(), &'static str> {
+        let last = self.measures.len().saturating_sub(1);
+        for (index, measure) in self.measures.iter().enumerate() {
+            if measure.pickup && index != 0 && index != last {
+                return Err("pickup measure must be the first or last measure");
+            }
+            measure.validate(&self.time_signature_at(index))?;
+        }
+        Ok(())
+    }
+}
+
+impl Part {
+    pub fn new(name: &str, staves: Vec<Staff>) -> Part {
+        Part {
+            name: name.to_string(),
+            staves,
+        }
+    }
+
+    pub fn push(&mut self, staff: Staff) {
+        self.staves.push(staff);
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn staves(&self) -> &[Staff] {
+        &self.staves
+    }
+
+    pub fn validate(&self) -> Result<(), &'static str> {
+        for staff in self.staves.iter() {
+            staff.validate()?;
+        }
+        if let Some(first) = self.staves.first() {
+            if self.staves.iter().any(|s| s.length() != first.length()) {
+                return Err("staves of a part must have the same length");
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Score {
+    pub fn new(parts: Vec<Part>) -> Score {
+        Score { title: None, parts }
+    }
+
+    pub fn with_title(self, title: &str) -> Score {
+        Score {
+            title: Some(title.to_string()),
+            ..self
+        }
+    }
+
+    pub fn push(&mut self, part: Part) {
+        self.parts.push(part);
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    pub fn parts(&self) -> &[Part] {
+        &self.parts
+    }
+
+    pub fn measure_offset(&self, index: usize) -> Fraction {
+        self.parts
+            .first()
+            .and_then(|part| part.staves.first())
+            .map_or(Fraction::ZERO, |staff| staff.measure_offset(index))
+    }
+
+    pub fn validate(&self) -> Result<(), &'static str> {
+        for part in self.parts.iter() {
+            part.validate()?;
+        }
+        let lengths: Vec<Fraction> = self
+            .parts
+            .iter()
+            .filter_map(|part| part.staves.first().map(Staff::length))
+            .collect();
+        if lengths.windows(2).any(|pair| pair[0] != pair[1]) {
+            return Err("parts of a score must have the same length");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::music::pitch::*;
+    use crate::music::{Duration, Note, Rest};
+
+    fn quarters(count: usize) -> Voice {
+        Voice::new(vec![Note::new(C4, Duration::new(4)).into(); count])
+    }
+
+    #[test]
+    fn test_voice() {
+        let voice = Voice::new(vec![
+            Note::new(C4, Duration::new_with_dots(4, 1)).into(),
+            Note::new(D4, Duration::new(8)).into(),
+            Rest::new(Duration::new(2)).into(),
+        ]);
+        assert_eq!(Fraction::ONE, voice.length());
+        assert_eq!(
+            vec![Fraction::ZERO, Fraction::new(3, 8), Fraction::HALF],
+            voice.offsets()
+        );
+        assert_eq!(
+            vec![D4],
+            voice.event_at(Fraction::new(3, 8)).unwrap().pitches()
+        );
+        assert!(voice.event_at(Fraction::new(3, 4)).unwrap().is_rest());
+        assert_eq!(None, voice.event_at(Fraction::ONE));
+    }
+
+    #[test]
+    fn test_measure_validation() {
+        let meter = TimeSignature::new(3, 4);
+        assert_eq!(Ok(()), Measure::new(vec![quarters(3)]).validate(&meter));
+        assert!(Measure::new(vec![quarters(3), quarters(2)])
+            .validate(&meter)
+            .is_err());
+        assert_eq!(
+            Ok(()),
+            Measure::new(vec![quarters(1)])
+                .with_pickup()
+                .validate(&meter)
+        );
+        assert!(Measure::new(vec![quarters(4)])
+            .with_pickup()
+            .validate(&meter)
+            .is_err());
+        assert_eq!(
+            Ok(()),
+            Measure::new(vec![quarters(2)])
+                .with_time_signature(TimeSignature::new(2, 4))
+                .validate(&meter)
+        );
+    }
+
+    #[test]
+    fn test_staff() {
+        let staff = Staff::new(vec![
+            Measure::new(vec![quarters(1)])
+                .with_pickup()
+                .with_time_signature(TimeSignature::new(3, 4))
+                .with_key_signature(KeySignature::new(-1))
+                .with_mode(Mode::Minor),
+            Measure::new(vec![quarters(3)]).with_clef(Clef::BASS),
+            Measure::new(vec![quarters(2)]).with_time_signature(TimeSignature::new(2, 4)),
+            Measure::new(vec![quarters(2)]),
+        ]);
+        assert_eq!(Ok(()), staff.validate());
+        assert_eq!(TimeSignature::new(3, 4), staff.time_signature_at(1));
+        assert_eq!(TimeSignature::new(2, 4), staff.time_signature_at(3));
+        assert_eq!(KeySignature::new(-1), staff.key_signature_at(3));
+        assert_eq!(Mode::Minor, staff.mode_at(3));
+        assert_eq!(Clef::TREBLE, staff.clef_at(0));
+        assert_eq!(Clef::BASS, staff.clef_at(2));
+        assert_eq!(Fraction::new(1, 4), staff.measure_offset(1));
+        assert_eq!(Fraction::ONE, staff.measure_offset(2));
+        assert_eq!(Fraction::new(5, 4), staff.event_offset(2, 0, 1));
+        assert_eq!(Fraction::new(2, 1), staff.length());
+
+        let mut staff = staff;
+        staff.push(Measure::new(vec![quarters(1)]).with_pickup());
+        staff.push(Measure::new(vec![quarters(2)]));
+        assert!(staff.validate().is_err());
+    }
+
+    #[test]
+    fn test_score() {
+        let staff = Staff::new(vec![Measure::new(vec![quarters(4)]); 2]);
+        let mut score =
+            Score::new(vec![Part::new("Piano", vec![staff.clone(), staff])]).with_title("Etude");
+        assert_eq!(Some("Etude"), score.title());
+        assert_eq!("Piano", score.parts()[0].name());
+        assert_eq!(Ok(()), score.validate());
+        assert_eq!(Fraction::ONE, score.measure_offset(1));
+        assert_eq!(Fraction::ZERO, Score::default().measure_offset(0));
+
+        score.push(Part::new(
+            "Violin",
+            vec![Staff::new(vec![Measure::new(vec![quarters(4)])])],
+        ));
+        assert!(score.validate().is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use super::*;
 use contracts::{contract_trait, ensures, requires};
+use std::str::FromStr;
 
 #[contract_trait]
 pub trait TuningSystem {
@@ -14,8 +15,8 @@ pub struct EqualTemperament {
 }
 
 impl EqualTemperament {
-    const TWELFTH_ROOT_OF_TWO: f32 = 1.05946309435929526456182;
-    const LN_TWELFTH_ROOT_OF_TWO: f32 = 0.05776226504666210911809767902434;
+    const TWELFTH_ROOT_OF_TWO: f32 = 1.059_463_1;
+    const LN_TWELFTH_ROOT_OF_TWO: f32 = 0.057_762_265;
 
     pub fn new(a4_hertz: f32) -> Self {
         Self { a4_hertz }
@@ -25,8 +26,10 @@ impl EqualTemperament {
 #[contract_trait]
 impl TuningSystem for EqualTemperament {
     fn to_hertz(&self, pitch: &Pitch) -> f32 {
-        let intervals: i32 = *pitch - A4;
-        self.a4_hertz * Self::TWELFTH_ROOT_OF_TWO.powi(intervals)
+        let interval = *pitch - A4;
+        let ratio = (Self::TWELFTH_ROOT_OF_TWO as f64).powi(interval.semitones())
+            * 2f64.powf(interval.deviation() as f64 / 1200.0);
+        (self.a4_hertz as f64 * ratio) as f32
     }
 
     fn to_pitch(&self, hertz: f32) -> Pitch {
@@ -90,7 +93,7 @@ pub struct JustIntonation<'a> {
 }
 
 impl<'a> JustIntonation<'a> {
-    const standard_ratio: [f32; 12] = [
+    pub const STANDARD_RATIO: [f32; 12] = [
         1.0,
         25.0 / 24.0,
         9.0 / 8.0,
@@ -108,10 +111,25 @@ impl<'a> JustIntonation<'a> {
 
 impl<'a> JustIntonation<'a> {
     pub fn new(ref_pitch: Pitch, ref_hertz: f32) -> JustIntonation<'a> {
+        Self::with_ratios(ref_pitch, ref_hertz, &Self::STANDARD_RATIO)
+    }
+
+    // Each ratio tunes the tone that many semitones above the reference pitch.
+    #[requires(ref_hertz > 0f32, "reference frequency must be positive")]
+    #[requires(ratio[0] == 1.0, "the first ratio must be the unison")]
+    #[requires(
+        ratio.windows(2).all(|pair| pair[0] < pair[1]) && ratio[11] < 2.0,
+        "ratios must ascend within one octave"
+    )]
+    pub fn with_ratios(
+        ref_pitch: Pitch,
+        ref_hertz: f32,
+        ratio: &'a [f32; 12],
+    ) -> JustIntonation<'a> {
         Self {
             ref_pitch,
             ref_hertz,
-            ratio: &Self::standard_ratio,
+            ratio,
         }
     }
 }
@@ -119,20 +137,337 @@ impl<'a> JustIntonation<'a> {
 #[contract_trait]
 impl<'a> TuningSystem for JustIntonation<'a> {
     fn to_hertz(&self, pitch: &Pitch) -> f32 {
-        let intervals: i32 = *pitch - self.ref_pitch;
+        // Microtonal pitches are tuned from the chromatic note they inflect.
+        let cents = pitch.accidental().cents() % 100;
+        let accidental = Accidental::from_cents(pitch.accidental().cents() - cents);
+        let chromatic = Pitch::new_with_accidental(pitch.name(), accidental, pitch.octave());
+        let intervals = (chromatic - self.ref_pitch).semitones();
         let mut octave = intervals / 12;
         let mut tone = intervals % 12;
-        
+        let deviation = 2.0f32.powf(cents as f32 / 1200.0);
+
         if tone < 0 {
             tone += 12;
             octave -= 1;
         }
-        
-        self.ref_hertz * self.ratio[tone as usize] * 2.0f32.powi(octave)
+
+        self.ref_hertz * self.ratio[tone as usize] * 2.0f32.powi(octave) * deviation
+    }
+
+    // Finds the nearest tone of the ratio table and keeps the remaining
+    // difference as a microtonal accidental. Frequencies beyond the pitch
+    // range are clamped to C-1 or B9 without a deviation, so `to_hertz` does
+    // not give them back.
+    fn to_pitch(&self, hertz: f32) -> Pitch {
+        let cents = 1200.0 * (hertz as f64 / self.ref_hertz as f64).log2();
+        let octave = (cents / 1200.0).floor();
+        let within = cents - octave * 1200.0;
+        // The unison an octave up is a candidate for notes just below it.
+        let (tone, deviation) = (0..=12)
+            .map(|tone| {
+                let just = 1200.0 * (self.ratio[tone % 12] as f64).log2();
+                let just = if tone == 12 { just + 1200.0 } else { just };
+                (tone as i32, within - just)
+            })
+            .min_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))
+            .unwrap();
+
+        // Flats keep a lowered tone's deviation in its accidental, so that
+        // `to_hertz` tunes it from the same tone.
+        let deviation = deviation.round() as i32;
+        let spelling = if deviation < 0 {
+            Spelling::Flats
+        } else {
+            Spelling::Sharps
+        };
+        let semitones = octave as i32 * 12 + tone;
+        match self.ref_pitch.transpose_chromatic(semitones, spelling) {
+            Ok(pitch) => {
+                let cents = pitch.accidental().cents() + deviation;
+                Pitch::new_with_accidental(
+                    pitch.name(),
+                    Accidental::from_cents(cents),
+                    pitch.octave(),
+                )
+            }
+            Err(_) if semitones < 0 => C_1,
+            Err(_) => B9,
+        }
+    }
+}
+
+// A scale read from a Scala `.scl` file. Degree 0 is the unison and the last
+// degree is the interval of repetition, usually the octave.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ScalaScale {
+    description: String,
+    cents: Vec<f64>,
+}
+
+impl ScalaScale {
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn len(&self) -> usize {
+        self.cents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cents.is_empty()
+    }
+
+    pub fn period(&self) -> f64 {
+        self.cents.last().copied().unwrap_or(1200.0)
+    }
+
+    #[requires(degree <= self.len(), "degree must be within the scale")]
+    pub fn cents(&self, degree: usize) -> f64 {
+        match degree {
+            0 => 0.0,
+            _ => self.cents[degree - 1],
+        }
     }
 
-    fn to_pitch(&self, _hertz: f32) -> Pitch {
-        C4
+    // Entries with a period are in cents; others are ratios such as `3/2` or `2`.
+    fn parse_entry(line: &str) -> Result<f64, &'static str> {
+        let value = line
+            .split_whitespace()
+            .next()
+            .ok_or("missing scale entry")?;
+        if value.contains('.') {
+            return value.parse().map_err(|_| "invalid cents value");
+        }
+        let (numerator, denominator) = value.split_once('/').unwrap_or((value, "1"));
+        let numerator: f64 = numerator.parse().map_err(|_| "invalid ratio")?;
+        let denominator: f64 = denominator.parse().map_err(|_| "invalid ratio")?;
+        if numerator <= 0.0 || denominator <= 0.0 {
+            return Err("ratio must be positive");
+        }
+        Ok(1200.0 * (numerator / denominator).log2())
+    }
+}
+
+impl FromStr for ScalaScale {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines().filter(|line| !line.starts_with('!'));
+        let description = lines
+            .next()
+            .ok_or("missing description")?
+            .trim()
+            .to_string();
+        let count: usize = lines
+            .next()
+            .and_then(|line| line.split_whitespace().next())
+            .ok_or("missing note count")?
+            .parse()
+            .map_err(|_| "invalid note count")?;
+        let cents = lines
+            .filter(|line| !line.trim().is_empty())
+            .take(count)
+            .map(Self::parse_entry)
+            .collect::<Result<Vec<f64>, _>>()?;
+        if cents.len() != count {
+            return Err("fewer scale entries than the note count");
+        }
+        if count == 0 {
+            return Err("scale has no notes");
+        }
+        Ok(Self { description, cents })
+    }
+}
+
+// A keyboard mapping read from a Scala `.kbm` file, assigning scale degrees
+// to MIDI keys.
+#[derive(Clone, PartialEq, Debug)]
+pub struct KeyboardMapping {
+    first: u8,
+    last: u8,
+    middle: u8,
+    reference: u8,
+    reference_hertz: f32,
+    octave_degree: usize,
+    // An empty map is linear: each key is the next scale degree.
+    map: Vec<Option<usize>>,
+}
+
+impl KeyboardMapping {
+    #[requires(reference_hertz > 0f32, "reference frequency must be positive")]
+    #[requires(middle <= 127 && reference <= 127, "keys must be in the range 0 to 127")]
+    pub fn linear(middle: u8, reference: u8, reference_hertz: f32) -> Self {
+        Self {
+            first: 0,
+            last: 127,
+            middle,
+            reference,
+            reference_hertz,
+            octave_degree: 0,
+            map: Vec::new(),
+        }
+    }
+
+    pub fn reference(&self) -> (u8, f32) {
+        (self.reference, self.reference_hertz)
+    }
+
+    // The scale degree counted from the middle key, or `None` for keys that are not retuned.
+    fn degree(&self, key: i32, scale_len: usize) -> Option<i64> {
+        if key < self.first as i32 || key > self.last as i32 {
+            return None;
+        }
+        let offset = (key - self.middle as i32) as i64;
+        if self.map.is_empty() {
+            return Some(offset);
+        }
+        let size = self.map.len() as i64;
+        let octave = match self.octave_degree {
+            0 => scale_len,
+            degree => degree,
+        } as i64;
+        let degree = self.map[offset.rem_euclid(size) as usize]? as i64;
+        Some(offset.div_euclid(size) * octave + degree)
+    }
+}
+
+impl Default for KeyboardMapping {
+    fn default() -> Self {
+        Self::linear(60, 60, 261.625_58)
+    }
+}
+
+impl FromStr for KeyboardMapping {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut values = s
+            .lines()
+            .filter(|line| !line.starts_with('!') && !line.trim().is_empty())
+            .map(|line| line.split_whitespace().next().unwrap_or(""));
+        let mut number = |error: &'static str| -> Result<usize, &'static str> {
+            values.next().ok_or(error)?.parse().map_err(|_| error)
+        };
+        let size = number("invalid map size")?;
+        let mut key = |error| -> Result<u8, &'static str> {
+            match number(error)? {
+                key if key <= 127 => Ok(key as u8),
+                _ => Err(error),
+            }
+        };
+        let first = key("invalid first key")?;
+        let last = key("invalid last key")?;
+        let middle = key("invalid middle key")?;
+        let reference = key("invalid reference key")?;
+        let reference_hertz: f32 = values
+            .next()
+            .and_then(|value| value.parse().ok())
+            .filter(|hertz| *hertz > 0.0)
+            .ok_or("invalid reference frequency")?;
+        let octave_degree = values
+            .next()
+            .and_then(|value| value.parse().ok())
+            .ok_or("invalid octave degree")?;
+        let mut map = values
+            .take(size)
+            .map(|value| match value {
+                "x" | "X" => Ok(None),
+                degree => degree
+                    .parse()
+                    .map(Some)
+                    .map_err(|_| "invalid mapping entry"),
+            })
+            .collect::<Result<Vec<Option<usize>>, _>>()?;
+        // Missing entries at the end of the map are unmapped.
+        map.resize(size, None);
+        Ok(Self {
+            first,
+            last,
+            middle,
+            reference,
+            reference_hertz,
+            octave_degree,
+            map,
+        })
+    }
+}
+
+// Tunes MIDI keys with a Scala scale and keyboard mapping. Keys the mapping
+// leaves out keep their equal tempered frequency with A4 at 440 Hz.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ScalaTuning {
+    scale: ScalaScale,
+    mapping: KeyboardMapping,
+}
+
+impl ScalaTuning {
+    pub fn new(scale: ScalaScale, mapping: KeyboardMapping) -> Result<Self, &'static str> {
+        let degrees = mapping
+            .map
+            .iter()
+            .flatten()
+            .chain(Some(&mapping.octave_degree));
+        if degrees.into_iter().any(|degree| *degree > scale.len()) {
+            return Err("mapping refers to a degree outside the scale");
+        }
+        let tuning = Self { scale, mapping };
+        if tuning.cents(tuning.mapping.reference as i32).is_none() {
+            return Err("reference key is not mapped");
+        }
+        Ok(tuning)
+    }
+
+    pub fn parse(scl: &str, kbm: Option<&str>) -> Result<Self, &'static str> {
+        let mapping = match kbm {
+            Some(kbm) => kbm.parse()?,
+            None => KeyboardMapping::default(),
+        };
+        Self::new(scl.parse()?, mapping)
+    }
+
+    pub fn scale(&self) -> &ScalaScale {
+        &self.scale
+    }
+
+    pub fn mapping(&self) -> &KeyboardMapping {
+        &self.mapping
+    }
+
+    // Cents above the middle key's degree 0.
+    fn cents(&self, key: i32) -> Option<f64> {
+        let len = self.scale.len() as i64;
+        let degree = self.mapping.degree(key, self.scale.len())?;
+        let octave = degree.div_euclid(len) as f64;
+        Some(octave * self.scale.period() + self.scale.cents(degree.rem_euclid(len) as usize))
+    }
+
+    fn key_hertz(&self, key: i32) -> f64 {
+        let (reference, hertz) = self.mapping.reference();
+        match (self.cents(key), self.cents(reference as i32)) {
+            (Some(cents), Some(reference)) => {
+                hertz as f64 * 2f64.powf((cents - reference) / 1200.0)
+            }
+            _ => 440.0 * 2f64.powf((key - 69) as f64 / 12.0),
+        }
+    }
+}
+
+#[contract_trait]
+impl TuningSystem for ScalaTuning {
+    fn to_hertz(&self, pitch: &Pitch) -> f32 {
+        let cents = (*pitch - A4).cents();
+        let key = 69 + cents.div_euclid(100);
+        let deviation = cents.rem_euclid(100) as f64;
+        (self.key_hertz(key) * 2f64.powf(deviation / 1200.0)) as f32
+    }
+
+    // Finds the key whose frequency is nearest, spelled with sharps.
+    fn to_pitch(&self, hertz: f32) -> Pitch {
+        let distance = |key: u8| (self.key_hertz(key as i32) / hertz as f64).log2().abs();
+        let key = (0..=127u8)
+            .min_by(|a, b| distance(*a).total_cmp(&distance(*b)))
+            .unwrap();
+        Pitch::from_midi(key, Spelling::Sharps)
     }
 }
 
@@ -148,6 +483,14 @@ mod tests {
         assert_eq!(830.6098, et.to_hertz(&Gs5));
         assert_eq!(233.08176, et.to_hertz(&Bb3));
         assert_eq!(219.99988, et.to_hertz(&A3));
+        assert_eq!(
+            440.0,
+            et.to_hertz(&Pitch::new_with_accidental(B, DOUBLE_FLAT, 4))
+        );
+        assert_eq!(
+            452.89297,
+            et.to_hertz(&Pitch::new_with_accidental(A, HALF_SHARP, 4))
+        );
     }
 
     #[test]
@@ -170,5 +513,184 @@ mod tests {
         assert_eq!(418.608, just.to_hertz(&Ab4));
         assert_eq!(523.26, just.to_hertz(&C5));
         assert_eq!(418.608 * 2.0, just.to_hertz(&Ab5));
+        assert_eq!(
+            just.to_hertz(&Gs4),
+            just.to_hertz(&Pitch::new_with_accidental(F, Accidental::new(3), 4))
+        );
+    }
+
+    #[test]
+    fn just_microtone_to_hertz_test() {
+        let just = JustIntonation::new(C4, 261.63);
+        let low_e = Pitch::new_with_accidental(E, Accidental::from_cents(-20), 4);
+        assert_eq!(
+            just.to_hertz(&E4) * 2.0f32.powf(-20.0 / 1200.0),
+            just.to_hertz(&low_e)
+        );
+        let high_e = Pitch::new_with_accidental(E, Accidental::from_cents(30), 4);
+        assert_eq!(
+            just.to_hertz(&E4) * 2.0f32.powf(30.0 / 1200.0),
+            just.to_hertz(&high_e)
+        );
+    }
+
+    #[test]
+    fn just_to_pitch_test() {
+        let just = JustIntonation::new(C4, 261.63);
+        for pitch in [C4, E4, Fs4, Gs4, A4, B4, C5, Cs2, G6].iter() {
+            assert_eq!(*pitch, just.to_pitch(just.to_hertz(pitch)));
+        }
+        assert_eq!(
+            Pitch::new_with_accidental(A, Accidental::from_cents(16), 4),
+            just.to_pitch(440.0)
+        );
+        let low_c = Pitch::new_with_accidental(C, Accidental::from_cents(-10), 5);
+        assert_eq!(low_c, just.to_pitch(just.to_hertz(&low_c)));
+        assert_eq!(C_1, just.to_pitch(1.0));
+        assert_eq!(B9, just.to_pitch(100_000.0));
+    }
+
+    #[test]
+    fn just_custom_ratio_test() {
+        let seven_limit = [
+            1.0,
+            15.0 / 14.0,
+            8.0 / 7.0,
+            6.0 / 5.0,
+            5.0 / 4.0,
+            4.0 / 3.0,
+            7.0 / 5.0,
+            3.0 / 2.0,
+            8.0 / 5.0,
+            5.0 / 3.0,
+            7.0 / 4.0,
+            15.0 / 8.0,
+        ];
+        let just = JustIntonation::with_ratios(C4, 261.63, &seven_limit);
+        assert_eq!(261.63 * 7.0 / 4.0, just.to_hertz(&Bb4));
+        assert_eq!(As4, just.to_pitch(261.63 * 7.0 / 4.0));
+        let low_ab = Pitch::new_with_accidental(A, Accidental::from_cents(-145), 4);
+        let hertz = 261.63 * 7.0 / 4.0 * 2.0f32.powf(-200.0 / 1200.0);
+        assert_eq!(low_ab, just.to_pitch(hertz));
+        assert!((hertz - just.to_hertz(&low_ab)).abs() < 0.1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn just_descending_ratio_test() {
+        let mut ratios = JustIntonation::STANDARD_RATIO;
+        ratios.swap(3, 4);
+        JustIntonation::with_ratios(C4, 261.63, &ratios);
+    }
+
+    const PTOLEMY: &str = "! ptolemy.scl
+!
+Ptolemy's intense diatonic
+ 7
+!
+ 9/8
+ 5/4
+ 4/3
+ 3/2
+ 5/3
+ 15/8
+ 2/1
+";
+
+    const WHITE_KEYS: &str = "! white.kbm
+12
+0
+127
+60
+69
+440.0
+7
+! mapping
+0
+x
+1
+x
+2
+3
+x
+4
+x
+5
+x
+6
+";
+
+    fn assert_hertz(expected: f32, actual: f32) {
+        assert!(
+            (expected - actual).abs() < 0.01,
+            "{} != {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn scala_scale_test() {
+        let scale: ScalaScale = "!\nMixed\n 3\n 9/8\n 700.0 fifth\n 2\n".parse().unwrap();
+        assert_eq!("Mixed", scale.description());
+        assert_eq!(3, scale.len());
+        assert_eq!(0.0, scale.cents(0));
+        assert!((scale.cents(1) - 203.91).abs() < 0.01);
+        assert_eq!(700.0, scale.cents(2));
+        assert_eq!(1200.0, scale.period());
+
+        assert!("Short\n 3\n 9/8\n 2\n".parse::<ScalaScale>().is_err());
+        assert!("Bad\n 1\n 3/0\n".parse::<ScalaScale>().is_err());
+        assert!("Empty\n 0\n".parse::<ScalaScale>().is_err());
+    }
+
+    #[test]
+    fn keyboard_mapping_test() {
+        let mapping: KeyboardMapping = WHITE_KEYS.parse().unwrap();
+        assert_eq!((69, 440.0), mapping.reference());
+        assert_eq!(Some(0), mapping.degree(60, 7));
+        assert_eq!(None, mapping.degree(61, 7));
+        assert_eq!(Some(9), mapping.degree(76, 7));
+        assert_eq!(Some(-1), mapping.degree(59, 7));
+
+        assert!("12\n0\n127\n60\n69\n".parse::<KeyboardMapping>().is_err());
+        assert!("0\n0\n128\n60\n69\n440\n0\n"
+            .parse::<KeyboardMapping>()
+            .is_err());
+        let scale: ScalaScale = PTOLEMY.parse().unwrap();
+        let mapping = "1\n0\n127\n60\n69\n440\n0\n9\n".parse().unwrap();
+        assert!(ScalaTuning::new(scale, mapping).is_err());
+    }
+
+    #[test]
+    fn scala_to_hertz_test() {
+        let tuning = ScalaTuning::parse(PTOLEMY, Some(WHITE_KEYS)).unwrap();
+        assert_hertz(440.0, tuning.to_hertz(&A4));
+        assert_hertz(264.0, tuning.to_hertz(&C4));
+        assert_hertz(330.0, tuning.to_hertz(&E4));
+        assert_hertz(528.0, tuning.to_hertz(&C5));
+        assert_hertz(247.5, tuning.to_hertz(&B3));
+        assert_hertz(277.18, tuning.to_hertz(&Cs4));
+
+        let equal = "12-TET\n12\n100.0\n200.0\n300.0\n400.0\n500.0\n600.0\n\
+                     700.0\n800.0\n900.0\n1000.0\n1100.0\n2/1\n";
+        let tuning = ScalaTuning::parse(equal, None).unwrap();
+        let et = EqualTemperament::new(440.0);
+        for pitch in [C4, A4, Bb3, Gs5].iter() {
+            assert_hertz(et.to_hertz(pitch), tuning.to_hertz(pitch));
+        }
+        assert_hertz(
+            et.to_hertz(&Pitch::new_with_accidental(A, HALF_SHARP, 4)),
+            tuning.to_hertz(&Pitch::new_with_accidental(A, HALF_SHARP, 4)),
+        );
+    }
+
+    #[test]
+    fn scala_to_pitch_test() {
+        let tuning = ScalaTuning::parse(PTOLEMY, Some(WHITE_KEYS)).unwrap();
+        assert_eq!(A4, tuning.to_pitch(440.0));
+        assert_eq!(E4, tuning.to_pitch(330.0));
+        assert_eq!(C5, tuning.to_pitch(529.0));
+        assert_eq!(Cs4, tuning.to_pitch(276.0));
     }
 }